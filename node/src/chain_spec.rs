@@ -2,14 +2,15 @@ use sp_core::{Pair, Public, sr25519};
 use node_template_runtime::{
 	AccountId, BabeConfig,BalancesConfig, GenesisConfig, GrandpaConfig,
 	SudoConfig, SystemConfig, WASM_BINARY, Signature, SessionConfig, StakingConfig, StakerStatus,
-	opaque::SessionKeys, Balance, GenericAssetConfig
+	opaque::SessionKeys, Balance, GenericAssetConfig, TemplateModuleConfig
 };
+use node_template_runtime::template::OrderType;
 // use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_consensus_babe::{AuthorityId as BabeId};
 use sp_finality_grandpa::AuthorityId as GrandpaId;
 use sp_runtime::traits::{Verify, IdentifyAccount};
 use sc_service::ChainType;
-use sp_runtime::{Perbill};
+use sp_runtime::{Perbill, FixedU128, FixedPointNumber};
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -140,6 +141,11 @@ fn testnet_genesis(
 ) -> GenesisConfig {
 	const STASH: Balance = 100;
 	const UNIT: u128 = 1_000_000_000_000;
+	// Asset 0 is the staking and spending asset, asset 1 is traded against it on the DEX.
+	const NATIVE_ASSET: u32 = 0;
+	const TRADED_ASSET: u32 = 1;
+	// Both assets use the 12 decimals of UNIT.
+	const ASSET_DECIMALS: u8 = 12;
 	GenesisConfig {
 		frame_system: Some(SystemConfig {
 			// Add Wasm runtime to storage.
@@ -181,14 +187,29 @@ fn testnet_genesis(
 			.. Default::default()
 		}),
 		pallet_generic_asset: Some(GenericAssetConfig{
-			assets: vec![0],
+			assets: vec![NATIVE_ASSET, TRADED_ASSET],
 			initial_balance: 3*UNIT,
 			endowed_accounts: endowed_accounts
 				.clone().into_iter().map(Into::into).collect(),
-			next_asset_id: 1,
-			staking_asset_id: 0,
-			spending_asset_id: 0
-		})
+			next_asset_id: 2,
+			staking_asset_id: NATIVE_ASSET,
+			spending_asset_id: NATIVE_ASSET
+		}),
+		template: Some(TemplateModuleConfig {
+			// (asset_id, decimals)
+			whitelisted_assets: vec![(NATIVE_ASSET, ASSET_DECIMALS), (TRADED_ASSET, ASSET_DECIMALS)],
+			// (asset_id, symbol)
+			asset_symbols: vec![(NATIVE_ASSET, b"UNIT".to_vec()), (TRADED_ASSET, b"TRADE".to_vec())],
+			// (quote_asset_id, base_asset_id, tick_size, lot_size)
+			orderbooks: vec![
+				(TRADED_ASSET, NATIVE_ASSET, FixedU128::saturating_from_rational(1, 100), FixedU128::saturating_from_rational(1, 10)),
+			],
+			// Seed liquidity around a price of 1 from the first endowed account
+			orders: endowed_accounts.first().map(|trader| vec![
+				(trader.clone(), OrderType::BidLimit, TRADED_ASSET, NATIVE_ASSET, FixedU128::saturating_from_rational(99, 100), FixedU128::from(1)),
+				(trader.clone(), OrderType::AskLimit, TRADED_ASSET, NATIVE_ASSET, FixedU128::saturating_from_rational(101, 100), FixedU128::from(1)),
+			]).unwrap_or_default(),
+		}),
 	}
 }
 
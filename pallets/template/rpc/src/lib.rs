@@ -10,7 +10,7 @@ use runtime_api::DexStorageApi as DexStorageRuntimeApi;
 use sp_arithmetic::FixedU128;
 use sp_core::H256;
 use sp_std::vec::Vec;
-use pallet_template::{LinkedPriceLevelRpc, OrderbookRpc, MarketDataRpc};
+use pallet_template::{LinkedPriceLevelRpc, OrderbookRpc, MarketDataRpc, TickerRpc, IntegrityIssue, SwapPathRpc, LiquidityPoolRpc, ReferralRewardRpc, OrderRecordRpc, DepthRpc, OrderType, SimulatedOrderRpc};
 use pallet_template::Trait;
 
 #[rpc]
@@ -32,6 +32,48 @@ pub trait DexStorageApi<BlockHash> {
 
     #[rpc(name = "get_market_info")]
     fn get_market_info(&self, at: Option<BlockHash>, trading_pair: H256, blocknum: u32) -> Result<MarketDataRpc>;
+
+    #[rpc(name = "get_market_info_rollup")]
+    fn get_market_info_rollup(&self, at: Option<BlockHash>, trading_pair: H256, bucket: u32) -> Result<Option<MarketDataRpc>>;
+
+    #[rpc(name = "get_ticker")]
+    fn get_ticker(&self, at: Option<BlockHash>, trading_pair: H256) -> Result<TickerRpc>;
+
+    #[rpc(name = "get_all_tickers")]
+    fn get_all_tickers(&self, at: Option<BlockHash>) -> Result<Vec<TickerRpc>>;
+
+    #[rpc(name = "get_twap")]
+    fn get_twap(&self, at: Option<BlockHash>, trading_pair: H256, window: u32) -> Result<Option<u128>>;
+
+    #[rpc(name = "get_vwap")]
+    fn get_vwap(&self, at: Option<BlockHash>, trading_pair: H256, window: u32) -> Result<Option<u128>>;
+
+    #[rpc(name = "check_orderbook_integrity")]
+    fn check_orderbook_integrity(&self, at: Option<BlockHash>, trading_pair: H256) -> Result<Vec<IntegrityIssue>>;
+
+    #[rpc(name = "get_trading_pair")]
+    fn get_trading_pair(&self, at: Option<BlockHash>, base_asset_id: u32, quote_asset_id: u32) -> Result<Option<H256>>;
+
+    #[rpc(name = "get_trading_pair_by_symbol")]
+    fn get_trading_pair_by_symbol(&self, at: Option<BlockHash>, base_symbol: String, quote_symbol: String) -> Result<Option<H256>>;
+
+    #[rpc(name = "get_best_swap_path")]
+    fn get_best_swap_path(&self, at: Option<BlockHash>, asset_in: u32, asset_out: u32, amount_in: FixedU128) -> Result<Option<SwapPathRpc>>;
+
+    #[rpc(name = "get_liquidity_pool")]
+    fn get_liquidity_pool(&self, at: Option<BlockHash>, trading_pair: H256) -> Result<Option<LiquidityPoolRpc>>;
+
+    #[rpc(name = "get_referral_rewards")]
+    fn get_referral_rewards(&self, at: Option<BlockHash>, referrer: [u8; 32]) -> Result<Vec<ReferralRewardRpc>>;
+
+    #[rpc(name = "get_order")]
+    fn get_order(&self, at: Option<BlockHash>, order_id: H256) -> Result<Option<OrderRecordRpc>>;
+
+    #[rpc(name = "get_depth")]
+    fn get_depth(&self, at: Option<BlockHash>, trading_pair: H256, levels: u32) -> Result<DepthRpc>;
+
+    #[rpc(name = "simulate_order")]
+    fn simulate_order(&self, at: Option<BlockHash>, trader: [u8; 32], order_type: OrderType, trading_pair: H256, price: FixedU128, quantity: FixedU128) -> Result<SimulatedOrderRpc>;
 }
 
 /// A struct that implements the `SumStorageApi`.
@@ -168,4 +210,200 @@ impl<C, Block> DexStorageApi<<Block as BlockT>::Hash> for DexStorage<C, Block>
 
     }
 
+    fn get_market_info_rollup(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256, bucket: u32) -> Result<Option<MarketDataRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_market_info_rollup(&at, trading_pair, bucket);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_ticker(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256) -> Result<TickerRpc> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_ticker(&at, trading_pair);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_all_tickers(&self, _at: Option<<Block as BlockT>::Hash>) -> Result<Vec<TickerRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_all_tickers(&at);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_twap(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256, window: u32) -> Result<Option<u128>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_twap(&at, trading_pair, window);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_vwap(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256, window: u32) -> Result<Option<u128>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_vwap(&at, trading_pair, window);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn check_orderbook_integrity(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256) -> Result<Vec<IntegrityIssue>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.check_orderbook_integrity(&at, trading_pair);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_trading_pair(&self, _at: Option<<Block as BlockT>::Hash>, base_asset_id: u32, quote_asset_id: u32) -> Result<Option<H256>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_trading_pair(&at, base_asset_id, quote_asset_id);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_trading_pair_by_symbol(&self, _at: Option<<Block as BlockT>::Hash>, base_symbol: String, quote_symbol: String) -> Result<Option<H256>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_trading_pair_by_symbol(&at, base_symbol.into_bytes(), quote_symbol.into_bytes());
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_best_swap_path(&self, _at: Option<<Block as BlockT>::Hash>, asset_in: u32, asset_out: u32, amount_in: FixedU128) -> Result<Option<SwapPathRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_best_swap_path(&at, asset_in, asset_out, amount_in);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_liquidity_pool(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256) -> Result<Option<LiquidityPoolRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_liquidity_pool(&at, trading_pair);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_referral_rewards(&self, _at: Option<<Block as BlockT>::Hash>, referrer: [u8; 32]) -> Result<Vec<ReferralRewardRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_referral_rewards(&at, referrer);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_order(&self, _at: Option<<Block as BlockT>::Hash>, order_id: H256) -> Result<Option<OrderRecordRpc>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_order(&at, order_id);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn get_depth(&self, _at: Option<<Block as BlockT>::Hash>, trading_pair: H256, levels: u32) -> Result<DepthRpc> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.get_depth(&at, trading_pair, levels);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
+    fn simulate_order(&self, _at: Option<<Block as BlockT>::Hash>, trader: [u8; 32], order_type: OrderType, trading_pair: H256, price: FixedU128, quantity: FixedU128) -> Result<SimulatedOrderRpc> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(
+            // Always take the best block hash for this RPC
+            self.client.info().best_hash);
+
+        let runtime_api_result = api.simulate_order(&at, trader, order_type, trading_pair, price, quantity);
+        runtime_api_result.map_err(|e| RpcError {
+            code: ErrorCode::ServerError(9876), // No real reason for this value
+            message: "Something wrong".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+
 }
@@ -8,6 +8,15 @@ use sp_std::vec::Vec;
 use pallet_template::LinkedPriceLevelRpc;
 use pallet_template::MarketDataRpc;
 use pallet_template::OrderbookRpc;
+use pallet_template::TickerRpc;
+use pallet_template::IntegrityIssue;
+use pallet_template::SwapPathRpc;
+use pallet_template::LiquidityPoolRpc;
+use pallet_template::ReferralRewardRpc;
+use pallet_template::OrderRecordRpc;
+use pallet_template::DepthRpc;
+use pallet_template::OrderType;
+use pallet_template::SimulatedOrderRpc;
 use pallet_template::Trait;
 use sp_runtime::{generic::BlockId, traits::Block as BlockT};
 
@@ -25,5 +34,33 @@ sp_api::decl_runtime_apis!{
 	    fn get_all_orderbook() -> Vec<OrderbookRpc>;
 
         fn get_market_info(trading_pair: H256,blocknum: u32) -> MarketDataRpc;
+
+        fn get_market_info_rollup(trading_pair: H256, bucket: u32) -> Option<MarketDataRpc>;
+
+        fn get_ticker(trading_pair: H256) -> TickerRpc;
+
+        fn get_all_tickers() -> Vec<TickerRpc>;
+
+        fn get_twap(trading_pair: H256, window: u32) -> Option<u128>;
+
+        fn get_vwap(trading_pair: H256, window: u32) -> Option<u128>;
+
+        fn check_orderbook_integrity(trading_pair: H256) -> Vec<IntegrityIssue>;
+
+        fn get_trading_pair(base_asset_id: u32, quote_asset_id: u32) -> Option<H256>;
+
+        fn get_trading_pair_by_symbol(base_symbol: Vec<u8>, quote_symbol: Vec<u8>) -> Option<H256>;
+
+        fn get_best_swap_path(asset_in: u32, asset_out: u32, amount_in: FixedU128) -> Option<SwapPathRpc>;
+
+        fn get_liquidity_pool(trading_pair: H256) -> Option<LiquidityPoolRpc>;
+
+        fn get_referral_rewards(referrer: [u8; 32]) -> Vec<ReferralRewardRpc>;
+
+        fn get_order(order_id: H256) -> Option<OrderRecordRpc>;
+
+        fn get_depth(trading_pair: H256, levels: u32) -> DepthRpc;
+
+        fn simulate_order(trader: [u8; 32], order_type: OrderType, trading_pair: H256, price: FixedU128, quantity: FixedU128) -> SimulatedOrderRpc;
 	}
 }
\ No newline at end of file
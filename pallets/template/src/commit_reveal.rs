@@ -0,0 +1,115 @@
+//! Orders submitted in two phases so block authors can't see them while ordering transactions.
+//!
+//! A trader first commits the hash of an order with its salt, then reveals the order in a later block
+//! within RevealWindow blocks of the commitment. Orders revealed in a block are executed together at
+//! the start of the next block, sorted by the block of their commitment and, for commitments of the
+//! same block, by a tie-break drawn from Randomness, so the position of the reveal transactions in
+//! the block doesn't change the outcome. At most MaxRevealsPerBlock orders are revealed per block.
+//! Every commitment reserves CommitmentDeposit, which is returned when the order is revealed and
+//! slashed when the commitment is pruned because it wasn't revealed in time.
+
+use codec::{Decode, Encode};
+use frame_support::{ensure, transactional};
+use frame_support::storage::{StorageDoubleMap, StorageMap, StorageValue};
+use frame_support::traits::{Get, Randomness};
+use pallet_generic_asset::AssetIdProvider;
+use frame_support::weights::Weight;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{One, Saturating};
+use sp_runtime::traits::Hash;
+use sp_std::vec::Vec;
+
+use crate::{CommitmentDeposits, CommitmentExpiries, Error, Module, OrderCommitments, Orderbooks, OrderType, RawEvent, RevealedOrders, Trait};
+
+/// Order revealed against an earlier commitment, waiting to be executed at the start of the next block
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct RevealedOrder<AccountId, Hash, BlockNumber> {
+    pub trader: AccountId,
+    pub trading_pair: Hash,
+    pub order_type: OrderType,
+    pub price: FixedU128,
+    pub quantity: FixedU128,
+    /// Block in which the order was committed
+    pub commit_block: BlockNumber,
+    pub commitment: Hash,
+}
+
+impl<T: Trait> Module<T> {
+    /// Hash a trader commits to before revealing the order with the same salt
+    pub fn order_commitment_hash(trader: &T::AccountId, order_type: &OrderType, trading_pair: &T::Hash,
+                                 price: FixedU128, quantity: FixedU128, salt: u64) -> T::Hash {
+        T::Hashing::hash_of(&(trader, order_type, trading_pair, price, quantity, salt))
+    }
+
+    pub(crate) fn do_commit_order(trader: T::AccountId, commitment: T::Hash) -> Result<(), Error<T>> {
+        ensure!(!<OrderCommitments<T>>::contains_key(&trader, &commitment), <Error<T>>::OrderCommitmentExists);
+        let deposit = T::CommitmentDeposit::get();
+        pallet_generic_asset::Module::<T>::reserve(&Self::commitment_deposit_asset(), &trader, deposit)
+            .map_err(|_| <Error<T>>::InsufficientAssetBalance)?;
+        let now = <frame_system::Module<T>>::block_number();
+        <OrderCommitments<T>>::insert(&trader, &commitment, now);
+        <CommitmentDeposits<T>>::insert(&trader, &commitment, deposit);
+        <CommitmentExpiries<T>>::mutate(now.saturating_add(T::RevealWindow::get()), |expiring| expiring.push((trader.clone(), commitment)));
+        Self::deposit_event(RawEvent::OrderCommitted(trader, commitment));
+        Ok(())
+    }
+
+    pub(crate) fn do_reveal_order(trader: T::AccountId, order_type: OrderType, trading_pair: T::Hash,
+                                  price: FixedU128, quantity: FixedU128, salt: u64) -> Result<(), Error<T>> {
+        let commitment = Self::order_commitment_hash(&trader, &order_type, &trading_pair, price, quantity, salt);
+        let commit_block = <OrderCommitments<T>>::get(&trader, &commitment).ok_or(<Error<T>>::NoOrderCommitment)?;
+        let now = <frame_system::Module<T>>::block_number();
+        // Revealing in the block of the commitment would let the block author see the order before ordering it
+        ensure!(commit_block < now, <Error<T>>::RevealTooEarly);
+        ensure!(now <= commit_block.saturating_add(T::RevealWindow::get()), <Error<T>>::NoOrderCommitment);
+        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+        let mut revealed = <RevealedOrders<T>>::get();
+        ensure!(revealed.len() < T::MaxRevealsPerBlock::get() as usize, <Error<T>>::TooManyReveals);
+        <OrderCommitments<T>>::remove(&trader, &commitment);
+        pallet_generic_asset::Module::<T>::unreserve(&Self::commitment_deposit_asset(), &trader, <CommitmentDeposits<T>>::take(&trader, &commitment));
+        revealed.push(RevealedOrder { trader: trader.clone(), trading_pair, order_type, price, quantity, commit_block, commitment });
+        <RevealedOrders<T>>::put(revealed);
+        Self::deposit_event(RawEvent::OrderRevealed(trader, commitment));
+        Ok(())
+    }
+
+    // Executes the orders revealed in the previous block and prunes the commitments whose reveal
+    // window ended with it
+    pub(crate) fn execute_revealed_orders(now: T::BlockNumber) -> Weight {
+        let expired = <CommitmentExpiries<T>>::take(now.saturating_sub(One::one()));
+        for (trader, commitment) in expired.iter() {
+            <OrderCommitments<T>>::remove(trader, commitment);
+            // A commitment that was revealed has no deposit left
+            pallet_generic_asset::Module::<T>::slash_reserved(&Self::commitment_deposit_asset(), trader, <CommitmentDeposits<T>>::take(trader, commitment));
+        }
+        let mut weight = T::DbWeight::get().reads_writes(2 + 3 * expired.len() as Weight, 1 + 3 * expired.len() as Weight);
+
+        let mut revealed: Vec<RevealedOrder<T::AccountId, T::Hash, T::BlockNumber>> = <RevealedOrders<T>>::take();
+        if revealed.is_empty() {
+            return weight;
+        }
+        let seed: T::Hash = T::Randomness::random(b"dex/reveal");
+        revealed.sort_by_cached_key(|order| (order.commit_block, T::Hashing::hash_of(&(seed, order.commitment))));
+        for order in revealed {
+            let (trader, commitment) = (order.trader.clone(), order.commitment);
+            if Self::execute_revealed_order(order).is_err() {
+                Self::deposit_event(RawEvent::RevealedOrderFailed(trader, commitment));
+            }
+            // Flat weight of an order, the same as submit_order
+            weight = weight.saturating_add(10000);
+        }
+        weight.saturating_add(T::DbWeight::get().writes(1))
+    }
+
+    // Commitment deposits are reserved in SpendingAssetCurrency like the TradingPair registration fee
+    fn commitment_deposit_asset() -> T::AssetId {
+        pallet_generic_asset::SpendingAssetIdProvider::<T>::asset_id()
+    }
+
+    // Nothing of a revealed order that fails is kept
+    #[transactional]
+    fn execute_revealed_order(order: RevealedOrder<T::AccountId, T::Hash, T::BlockNumber>) -> Result<(), Error<T>> {
+        Self::execute_order(order.trader, order.order_type, order.trading_pair, order.price, order.quantity)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Trading permissions an account grants to other accounts, e.g. the hot key of a trading bot.
+//!
+//! A delegate can submit and cancel orders on behalf of the account that granted the permission,
+//! optionally only on some TradingPairs and only up to a notional per order. Orders are always
+//! placed for the granting account, so a delegate can never move its assets anywhere else.
+
+use codec::{Decode, Encode};
+use frame_support::ensure;
+use frame_support::storage::{StorageDoubleMap, StorageMap};
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{CheckedDiv, CheckedMul};
+use sp_std::vec::Vec;
+
+use crate::{Error, MAX_PERMITTED_TRADING_PAIRS, Module, Orderbook, Orderbooks, OrderType, RawEvent, Trait, TradingPermissions};
+
+/// What a delegate is allowed to trade on behalf of the account that granted it
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TradingPermission<Hash> {
+    /// TradingPairs the delegate can trade, all of them if empty
+    pub trading_pairs: Vec<Hash>,
+    /// Highest notional of a single order in the base asset, no limit if None
+    pub max_notional: Option<FixedU128>,
+}
+
+impl<T: Trait> Module<T> {
+    pub(crate) fn do_grant_trading_permission(owner: T::AccountId, delegate: T::AccountId, trading_pairs: Vec<T::Hash>,
+                                              max_notional: Option<FixedU128>) -> Result<(), Error<T>> {
+        ensure!(owner != delegate, <Error<T>>::InvalidDelegate);
+        ensure!(trading_pairs.len() <= MAX_PERMITTED_TRADING_PAIRS, <Error<T>>::TooManyPermittedPairs);
+        ensure!(trading_pairs.iter().all(|trading_pair| <Orderbooks<T>>::contains_key(trading_pair)), <Error<T>>::InvalidTradingPair);
+        <TradingPermissions<T>>::insert(&owner, &delegate, TradingPermission { trading_pairs, max_notional });
+        Self::deposit_event(RawEvent::TradingPermissionGranted(owner, delegate));
+        Ok(())
+    }
+
+    pub(crate) fn do_revoke_trading_permission(owner: T::AccountId, delegate: T::AccountId) -> Result<(), Error<T>> {
+        ensure!(<TradingPermissions<T>>::contains_key(&owner, &delegate), <Error<T>>::NoTradingPermission);
+        <TradingPermissions<T>>::remove(&owner, &delegate);
+        Self::deposit_event(RawEvent::TradingPermissionRevoked(owner, delegate));
+        Ok(())
+    }
+
+    // Account an order is submitted for, the caller itself unless it trades on behalf of another account
+    pub(crate) fn ordering_account(caller: T::AccountId, on_behalf_of: Option<T::AccountId>, order_type: &OrderType,
+                                   trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128) -> Result<T::AccountId, Error<T>> {
+        let owner = match on_behalf_of {
+            Some(owner) => owner,
+            None => return Ok(caller),
+        };
+        let permission = Self::trading_permission_on(&owner, &caller, trading_pair)?;
+        if let Some(max_notional) = permission.max_notional {
+            let notional = Self::order_notional(order_type, trading_pair, price, quantity)?;
+            ensure!(notional <= max_notional, <Error<T>>::NotionalAboveLimit);
+        }
+        Ok(owner)
+    }
+
+    // Account an order is cancelled for, the caller itself unless it trades on behalf of another account
+    pub(crate) fn cancelling_account(caller: T::AccountId, on_behalf_of: Option<T::AccountId>, trading_pair: &T::Hash) -> Result<T::AccountId, Error<T>> {
+        match on_behalf_of {
+            Some(owner) => {
+                Self::trading_permission_on(&owner, &caller, trading_pair)?;
+                Ok(owner)
+            }
+            None => Ok(caller),
+        }
+    }
+
+    fn trading_permission_on(owner: &T::AccountId, delegate: &T::AccountId, trading_pair: &T::Hash) -> Result<TradingPermission<T::Hash>, Error<T>> {
+        let permission = <TradingPermissions<T>>::get(owner, delegate).ok_or(<Error<T>>::NoTradingPermission)?;
+        ensure!(permission.trading_pairs.is_empty() || permission.trading_pairs.contains(trading_pair), <Error<T>>::TradingPairNotPermitted);
+        Ok(permission)
+    }
+
+    // Value of the order in the base asset. A market ask is valued at the best bid or the pool price,
+    // whichever is higher, as it can't sell into anything better; with neither it can't be valued.
+    fn order_notional(order_type: &OrderType, trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128) -> Result<FixedU128, Error<T>> {
+        match order_type {
+            OrderType::BidLimit | OrderType::AskLimit => price.checked_mul(&quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow),
+            OrderType::BidMarket => Ok(price),
+            OrderType::AskMarket => {
+                let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+                let pool_price = Self::pool_reserves(&orderbook)
+                    .and_then(|(base_reserve, quote_reserve)| base_reserve.checked_div(&quote_reserve))
+                    .unwrap_or_else(|| FixedU128::from(0));
+                let best_price = orderbook.best_bid_price.max(pool_price);
+                ensure!(best_price > FixedU128::from(0), <Error<T>>::UnknownNotional);
+                quantity.checked_mul(&best_price).ok_or(<Error<T>>::MulUnderflowOrOverflow)
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Consistency checks and repair of the orderbook storage of a TradingPair.
+//!
+//! Price levels are kept in three places that must agree: the sorted AsksLevels/BidsLevels
+//! vectors, the PriceLevels linked list and the best prices cached in the Orderbook. For asks
+//! `next` points to the next higher price and `prev` to the next lower one, for bids `next`
+//! points to the next lower price and `prev` to the next higher one.
+
+use codec::{Decode, Encode};
+use frame_support::storage::{IterableStorageDoubleMap, IterableStorageMap, StorageDoubleMap, StorageMap};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::Saturating;
+use sp_std::collections::btree_map::BTreeMap;
+use sp_std::convert::TryInto;
+use sp_std::vec::Vec;
+
+use crate::{AsksLevels, BidsLevels, LinkedPriceLevel, Module, Orderbook, Orderbooks, OrderType, PriceLevels, Trait};
+
+/// Inconsistency found by the orderbook integrity check
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum IntegrityIssue {
+    /// AsksLevels or BidsLevels is not sorted or contains duplicates
+    UnsortedLevels,
+    /// Price is in AsksLevels or BidsLevels but PriceLevels has no orders for it [Price]
+    MissingPriceLevel(FixedU128),
+    /// PriceLevels has an entry that is in neither AsksLevels nor BidsLevels [Price]
+    OrphanPriceLevel(FixedU128),
+    /// Price is in both AsksLevels and BidsLevels [Price]
+    PriceOnBothSides(FixedU128),
+    /// next or prev of the level doesn't point to its neighbours in the level vector [Price]
+    BrokenLink(FixedU128),
+    /// Level holds an order of another price, TradingPair or side [Price]
+    MisplacedOrder(FixedU128),
+    /// best_bid_price doesn't match BidsLevels [Stored, Expected]
+    BestBidMismatch(FixedU128, FixedU128),
+    /// best_ask_price doesn't match AsksLevels [Stored, Expected]
+    BestAskMismatch(FixedU128, FixedU128),
+    /// Reserved balance is lower than what the trader's resting orders require [Encoded AccountId, AssetId]
+    InsufficientReserve(Vec<u8>, u32),
+    /// Sum of the trading balances doesn't match the balance of the ledger account [AssetId]
+    TradingLedgerMismatch(u32),
+}
+
+impl<T: Trait> Module<T> {
+    /// Cross checks PriceLevels, AsksLevels/BidsLevels, the Orderbook best prices, the reserved
+    /// balances of the traders and the trading ledger of both assets for the TradingPair. Returns an
+    /// empty vector if all of them agree.
+    pub fn check_orderbook_integrity(trading_pair: T::Hash) -> Vec<IntegrityIssue> {
+        let mut issues: Vec<IntegrityIssue> = Vec::new();
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let asks_levels: Vec<FixedU128> = <AsksLevels<T>>::get(&trading_pair);
+        let bids_levels: Vec<FixedU128> = <BidsLevels<T>>::get(&trading_pair);
+
+        if !Self::is_strictly_ascending(&asks_levels) || !Self::is_strictly_ascending(&bids_levels) {
+            issues.push(IntegrityIssue::UnsortedLevels);
+        }
+        for price in asks_levels.iter() {
+            if bids_levels.contains(price) {
+                issues.push(IntegrityIssue::PriceOnBothSides(*price));
+            }
+        }
+
+        Self::check_side(&trading_pair, &asks_levels, OrderType::AskLimit, &mut issues);
+        Self::check_side(&trading_pair, &bids_levels, OrderType::BidLimit, &mut issues);
+
+        for (price, _) in <PriceLevels<T>>::iter_prefix(&trading_pair) {
+            if !asks_levels.contains(&price) && !bids_levels.contains(&price) {
+                issues.push(IntegrityIssue::OrphanPriceLevel(price));
+            }
+        }
+
+        let expected_best_ask = asks_levels.first().copied().unwrap_or(FixedU128::from(0));
+        if orderbook.best_ask_price != expected_best_ask {
+            issues.push(IntegrityIssue::BestAskMismatch(orderbook.best_ask_price, expected_best_ask));
+        }
+        let expected_best_bid = bids_levels.last().copied().unwrap_or(FixedU128::from(0));
+        if orderbook.best_bid_price != expected_best_bid {
+            issues.push(IntegrityIssue::BestBidMismatch(orderbook.best_bid_price, expected_best_bid));
+        }
+
+        Self::check_reserves(&trading_pair, &orderbook, &asks_levels, &bids_levels, &mut issues);
+        for asset_id in [orderbook.base_asset_id, orderbook.quote_asset_id].iter() {
+            if !Self::is_trading_ledger_balanced(asset_id) {
+                issues.push(IntegrityIssue::TradingLedgerMismatch(TryInto::<u32>::try_into(*asset_id).ok().unwrap_or_default()));
+            }
+        }
+        issues
+    }
+
+    /// Rebuilds the PriceLevels linked list and the best prices of the TradingPair from AsksLevels
+    /// and BidsLevels. Levels without orders are dropped and levels with orders that are missing from
+    /// both vectors are put back on the side of their orders.
+    pub fn rebuild_orderbook(trading_pair: T::Hash) {
+        let mut asks_levels: Vec<FixedU128> = <AsksLevels<T>>::get(&trading_pair);
+        let mut bids_levels: Vec<FixedU128> = <BidsLevels<T>>::get(&trading_pair);
+
+        let stored_levels: Vec<(FixedU128, LinkedPriceLevel<T>)> = <PriceLevels<T>>::iter_prefix(&trading_pair).collect();
+        for (price, linked_pricelevel) in stored_levels.iter() {
+            match linked_pricelevel.orders.front().map(|order| order.order_type.clone()) {
+                Some(OrderType::AskLimit) if !asks_levels.contains(price) => asks_levels.push(*price),
+                Some(OrderType::BidLimit) if !bids_levels.contains(price) => bids_levels.push(*price),
+                None => <PriceLevels<T>>::remove(&trading_pair, price),
+                _ => {}
+            }
+        }
+
+        asks_levels.sort();
+        asks_levels.dedup();
+        bids_levels.sort();
+        bids_levels.dedup();
+        // Keep only the levels that have orders, on the side the orders belong to
+        asks_levels.retain(|price| Self::level_side(&trading_pair, price) == Some(OrderType::AskLimit));
+        bids_levels.retain(|price| Self::level_side(&trading_pair, price) == Some(OrderType::BidLimit));
+
+        Self::relink_side(&trading_pair, &asks_levels, OrderType::AskLimit);
+        Self::relink_side(&trading_pair, &bids_levels, OrderType::BidLimit);
+
+        let mut orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        orderbook.best_ask_price = asks_levels.first().copied().unwrap_or(FixedU128::from(0));
+        orderbook.best_bid_price = bids_levels.last().copied().unwrap_or(FixedU128::from(0));
+        <Orderbooks<T>>::insert(&trading_pair, orderbook);
+        <AsksLevels<T>>::insert(&trading_pair, asks_levels);
+        <BidsLevels<T>>::insert(&trading_pair, bids_levels);
+    }
+
+    // Expected (next, prev) of the level at index in the ascending level vector of the given side
+    fn expected_links(levels: &[FixedU128], index: usize, side: &OrderType) -> (Option<FixedU128>, Option<FixedU128>) {
+        let lower = if index > 0 { levels.get(index - 1).copied() } else { None };
+        let higher = levels.get(index + 1).copied();
+        match side {
+            OrderType::AskLimit => (higher, lower),
+            _ => (lower, higher),
+        }
+    }
+
+    fn check_side(trading_pair: &T::Hash, levels: &[FixedU128], side: OrderType, issues: &mut Vec<IntegrityIssue>) {
+        for (index, price) in levels.iter().enumerate() {
+            if !<PriceLevels<T>>::contains_key(trading_pair, price) {
+                issues.push(IntegrityIssue::MissingPriceLevel(*price));
+                continue;
+            }
+            let linked_pricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::get(trading_pair, price);
+            if linked_pricelevel.orders.is_empty() {
+                issues.push(IntegrityIssue::MissingPriceLevel(*price));
+            }
+            let (next, prev) = Self::expected_links(levels, index, &side);
+            if linked_pricelevel.next != next || linked_pricelevel.prev != prev {
+                issues.push(IntegrityIssue::BrokenLink(*price));
+            }
+            if linked_pricelevel.orders.iter().any(|order| order.price != *price || order.trading_pair != *trading_pair || order.order_type != side) {
+                issues.push(IntegrityIssue::MisplacedOrder(*price));
+            }
+        }
+    }
+
+    // Sums up what the resting orders of every trader hold reserved and compares it with the
+    // reserved balance in the trading ledger or pallet_generic_asset
+    fn check_reserves(trading_pair: &T::Hash, orderbook: &Orderbook<T>, asks_levels: &[FixedU128], bids_levels: &[FixedU128], issues: &mut Vec<IntegrityIssue>) {
+        let mut required: BTreeMap<(T::AccountId, T::AssetId), FixedU128> = BTreeMap::new();
+        for price in asks_levels.iter().chain(bids_levels.iter()) {
+            let linked_pricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::get(trading_pair, price);
+            for order in linked_pricelevel.orders.iter() {
+                let entry = required.entry((order.trader.clone(), Self::reserved_asset_id(&order.order_type, orderbook))).or_insert(FixedU128::from(0));
+                *entry = entry.saturating_add(order.reserved);
+            }
+        }
+        // A reserved balance is shared by the orders of the trader on every TradingPair of the asset
+        for (other_pair, other_orderbook) in <Orderbooks<T>>::iter() {
+            if other_pair == *trading_pair {
+                continue;
+            }
+            for (_, linked_pricelevel) in <PriceLevels<T>>::iter_prefix(&other_pair) {
+                for order in linked_pricelevel.orders.iter() {
+                    if let Some(entry) = required.get_mut(&(order.trader.clone(), Self::reserved_asset_id(&order.order_type, &other_orderbook))) {
+                        *entry = entry.saturating_add(order.reserved);
+                    }
+                }
+            }
+        }
+        for ((trader, asset_id), amount) in required.into_iter() {
+            match Self::reserved_tradable_balance(&asset_id, &trader) {
+                Some(reserved) if reserved >= amount => {}
+                _ => issues.push(IntegrityIssue::InsufficientReserve(trader.encode(), TryInto::<u32>::try_into(asset_id).ok().unwrap_or_default())),
+            }
+        }
+    }
+
+    // Rewrites next and prev of every level of the side to match the ascending level vector
+    fn relink_side(trading_pair: &T::Hash, levels: &[FixedU128], side: OrderType) {
+        for (index, price) in levels.iter().enumerate() {
+            let (next, prev) = Self::expected_links(levels, index, &side);
+            <PriceLevels<T>>::mutate(trading_pair, price, |linked_pricelevel| {
+                linked_pricelevel.next = next;
+                linked_pricelevel.prev = prev;
+            });
+        }
+    }
+
+    // Side of the level's orders, None if the level has no orders
+    fn level_side(trading_pair: &T::Hash, price: &FixedU128) -> Option<OrderType> {
+        <PriceLevels<T>>::get(trading_pair, price).orders.front().map(|order| order.order_type.clone())
+    }
+
+    fn is_strictly_ascending(levels: &[FixedU128]) -> bool {
+        levels.windows(2).all(|pair| pair[0] < pair[1])
+    }
+}
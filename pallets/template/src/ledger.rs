@@ -0,0 +1,187 @@
+//! Internal trading balances of the DEX.
+//!
+//! A trader can deposit an asset into the trading ledger, the deposited balance is held by the ledger
+//! account and recorded in TradingBalances. While a trader has a trading balance in an asset, its
+//! orders reserve, pay and receive that asset in the ledger only, so matching two such traders
+//! touches no pallet_generic_asset storage at all. Assets only move in or out of the ledger account
+//! on deposits and withdrawals, or when the other side of a transfer trades from pallet_generic_asset.
+//! TradingBalanceTotals always equals the balance of the ledger account, which makes the ledger auditable.
+
+use codec::{Decode, Encode};
+use frame_support::{ensure, transactional};
+use frame_support::storage::{StorageDoubleMap, StorageMap};
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{CheckedAdd, CheckedSub, Saturating, Zero};
+use sp_runtime::traits::AccountIdConversion;
+
+use crate::{Error, LEDGER_MODULE_ID, Module, RawEvent, Trait, TradingBalances, TradingBalanceTotals};
+
+/// Balance of a trader in one asset of the trading ledger
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct TradingBalance {
+    pub free: FixedU128,
+    /// Held by the trader's open orders
+    pub reserved: FixedU128,
+}
+
+impl<T: Trait> Module<T> {
+    /// Account holding every balance of the trading ledger
+    pub fn ledger_account() -> T::AccountId {
+        LEDGER_MODULE_ID.into_account()
+    }
+
+    #[transactional]
+    pub(crate) fn do_deposit_trading_balance(trader: T::AccountId, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        let amount = Self::valid_ledger_amount(&asset_id, amount)?;
+        let mut balance = match <TradingBalances<T>>::get(&trader, &asset_id) {
+            Some(balance) => balance,
+            None => {
+                // Orders reserving in pallet_generic_asset could no longer release their reserve once the trader trades from the ledger
+                ensure!(pallet_generic_asset::Module::<T>::reserved_balance(&asset_id, &trader).is_zero(), <Error<T>>::ReservedOutsideTradingLedger);
+                TradingBalance::default()
+            }
+        };
+        Self::generic_transfer(asset_id, amount, &trader, &Self::ledger_account())?;
+        balance.free = balance.free.checked_add(&amount).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        <TradingBalances<T>>::insert(&trader, &asset_id, balance);
+        <TradingBalanceTotals<T>>::mutate(&asset_id, |total| *total = total.saturating_add(amount));
+        Self::deposit_event(RawEvent::TradingBalanceDeposited(trader, asset_id, amount));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_withdraw_trading_balance(trader: T::AccountId, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        let amount = Self::valid_ledger_amount(&asset_id, amount)?;
+        let mut balance = <TradingBalances<T>>::get(&trader, &asset_id).ok_or(<Error<T>>::InsufficientTradingBalance)?;
+        balance.free = balance.free.checked_sub(&amount).ok_or(<Error<T>>::InsufficientTradingBalance)?;
+        Self::put_trading_balance(&trader, &asset_id, balance);
+        <TradingBalanceTotals<T>>::mutate(&asset_id, |total| *total = total.saturating_sub(amount));
+        Self::generic_transfer(asset_id, amount, &Self::ledger_account(), &trader)?;
+        Self::deposit_event(RawEvent::TradingBalanceWithdrawn(trader, asset_id, amount));
+        Ok(())
+    }
+
+    /// Whether the trading balances of the asset add up to what the ledger account holds
+    pub fn is_trading_ledger_balanced(asset_id: &T::AssetId) -> bool {
+        let held = Self::convert_balance_to_fixed_u128(asset_id, pallet_generic_asset::Module::<T>::total_balance(asset_id, &Self::ledger_account()));
+        held == Some(<TradingBalanceTotals<T>>::get(asset_id))
+    }
+
+    /// Balance of the asset the trader can spend on orders, its trading balance if it has one
+    pub fn tradable_balance(asset_id: &T::AssetId, who: &T::AccountId) -> Option<FixedU128> {
+        match <TradingBalances<T>>::get(who, asset_id) {
+            Some(balance) => Some(balance.free),
+            None => Self::convert_balance_to_fixed_u128(asset_id, pallet_generic_asset::Module::<T>::free_balance(asset_id, who)),
+        }
+    }
+
+    // Balance of the asset held by the trader's orders
+    pub(crate) fn reserved_tradable_balance(asset_id: &T::AssetId, who: &T::AccountId) -> Option<FixedU128> {
+        match <TradingBalances<T>>::get(who, asset_id) {
+            Some(balance) => Some(balance.reserved),
+            None => Self::convert_balance_to_fixed_u128(asset_id, pallet_generic_asset::Module::<T>::reserved_balance(asset_id, who)),
+        }
+    }
+
+    pub(crate) fn reserve_tradable(asset_id: &T::AssetId, who: &T::AccountId, amount: FixedU128) -> Result<(), Error<T>> {
+        match <TradingBalances<T>>::get(who, asset_id) {
+            Some(mut balance) => {
+                balance.free = balance.free.checked_sub(&amount).ok_or(<Error<T>>::ReserveAmountFailed)?;
+                balance.reserved = balance.reserved.saturating_add(amount);
+                <TradingBalances<T>>::insert(who, asset_id, balance);
+                Ok(())
+            }
+            None => {
+                let amount_balance = Self::convert_fixed_u128_to_balance(asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+                pallet_generic_asset::Module::<T>::reserve(asset_id, who, amount_balance).map_err(|_| <Error<T>>::ReserveAmountFailed)
+            }
+        }
+    }
+
+    pub(crate) fn unreserve_tradable(asset_id: &T::AssetId, who: &T::AccountId, amount: FixedU128) -> Result<(), Error<T>> {
+        match <TradingBalances<T>>::get(who, asset_id) {
+            Some(mut balance) => {
+                let amount = amount.min(balance.reserved);
+                balance.reserved = balance.reserved.saturating_sub(amount);
+                balance.free = balance.free.saturating_add(amount);
+                <TradingBalances<T>>::insert(who, asset_id, balance);
+            }
+            None => {
+                let amount_balance = Self::convert_fixed_u128_to_balance(asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+                pallet_generic_asset::Module::<T>::unreserve(asset_id, who, amount_balance);
+            }
+        }
+        Ok(())
+    }
+
+    // Moves the amount from the free, or reserved if from_reserved, tradable balance of `from` to the
+    // free tradable balance of `to`, through the ledger account when only one of them trades from the ledger
+    pub(crate) fn transfer_tradable(asset_id: T::AssetId, amount: FixedU128, from: &T::AccountId, to: &T::AccountId, from_reserved: bool) -> Result<(), Error<T>> {
+        let amount = Self::round_to_asset_precision(&asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        let from_ledger = <TradingBalances<T>>::get(from, &asset_id);
+        let to_in_ledger = <TradingBalances<T>>::contains_key(to, &asset_id);
+        match (from_ledger, to_in_ledger) {
+            (None, false) => {
+                if from_reserved {
+                    Self::unreserve_tradable(&asset_id, from, amount)?;
+                }
+                Self::generic_transfer(asset_id, amount, from, to)
+            }
+            (Some(mut from_balance), to_in_ledger) => {
+                let debited = if from_reserved { &mut from_balance.reserved } else { &mut from_balance.free };
+                *debited = debited.checked_sub(&amount).ok_or(<Error<T>>::ErrorWhileTransferingAsset)?;
+                <TradingBalances<T>>::insert(from, &asset_id, from_balance);
+                if to_in_ledger {
+                    // Read after the debit is written in case the trader pays itself
+                    Self::credit_trading_balance(to, &asset_id, amount)
+                } else {
+                    <TradingBalanceTotals<T>>::mutate(&asset_id, |total| *total = total.saturating_sub(amount));
+                    Self::generic_transfer(asset_id, amount, &Self::ledger_account(), to)
+                }
+            }
+            (None, true) => {
+                if from_reserved {
+                    Self::unreserve_tradable(&asset_id, from, amount)?;
+                }
+                Self::generic_transfer(asset_id, amount, from, &Self::ledger_account())?;
+                <TradingBalanceTotals<T>>::mutate(&asset_id, |total| *total = total.saturating_add(amount));
+                Self::credit_trading_balance(to, &asset_id, amount)
+            }
+        }
+    }
+
+    // Makes the account trade the asset from the ledger, with nothing in it yet
+    pub(crate) fn open_trading_balance(who: &T::AccountId, asset_id: &T::AssetId) {
+        if !<TradingBalances<T>>::contains_key(who, asset_id) {
+            <TradingBalances<T>>::insert(who, asset_id, TradingBalance::default());
+        }
+    }
+
+    fn credit_trading_balance(who: &T::AccountId, asset_id: &T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        let mut balance = <TradingBalances<T>>::get(who, asset_id).unwrap_or_default();
+        balance.free = balance.free.checked_add(&amount).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        <TradingBalances<T>>::insert(who, asset_id, balance);
+        Ok(())
+    }
+
+    fn generic_transfer(asset_id: T::AssetId, amount: FixedU128, from: &T::AccountId, to: &T::AccountId) -> Result<(), Error<T>> {
+        let amount_balance = Self::convert_fixed_u128_to_balance(&asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        pallet_generic_asset::Module::<T>::make_transfer(&asset_id, from, to, amount_balance)
+            .map_err(|_| <Error<T>>::ErrorWhileTransferingAsset)
+    }
+
+    // A trading balance that is entirely withdrawn is removed so the trader trades from pallet_generic_asset again
+    fn put_trading_balance(who: &T::AccountId, asset_id: &T::AssetId, balance: TradingBalance) {
+        if balance == TradingBalance::default() {
+            <TradingBalances<T>>::remove(who, asset_id);
+        } else {
+            <TradingBalances<T>>::insert(who, asset_id, balance);
+        }
+    }
+
+    fn valid_ledger_amount(asset_id: &T::AssetId, amount: FixedU128) -> Result<FixedU128, Error<T>> {
+        ensure!(amount > FixedU128::from(0), <Error<T>>::InvalidTradingBalanceAmount);
+        ensure!(Self::round_to_asset_precision(asset_id, amount) == Some(amount), <Error<T>>::InvalidTradingBalanceAmount);
+        Ok(amount)
+    }
+}
@@ -5,20 +5,23 @@ use codec::{Decode, Encode};
 /// Learn more about FRAME and the core library of Substrate FRAME pallets:
 /// https://substrate.dev/docs/en/knowledgebase/runtime/frame
 
-use frame_support::{decl_error, decl_event, decl_module, decl_storage, dispatch, ensure};
-use frame_support::traits::Get;
-use frame_system::ensure_signed;
-use pallet_generic_asset::AssetIdProvider;
+use frame_support::{decl_error, decl_event, decl_module, decl_storage, dispatch, ensure, Parameter, transactional};
+use frame_support::traits::{EnsureOrigin, Get, Randomness};
+use frame_support::weights::Weight;
+use frame_system::{ensure_none, ensure_signed};
+use frame_system::offchain::SendTransactionTypes;
+use pallet_generic_asset::{AssetIdProvider, AssetOptions, Owner, PermissionLatest};
 //use sp_core::crypto::{AccountId32, Ss58Codec};
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
 use sp_arithmetic::{FixedPointNumber, FixedU128};
-use sp_arithmetic::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, UniqueSaturatedFrom};
-use sp_core::H256;
-use sp_runtime::DispatchError;
-use sp_runtime::traits::Hash;
+use sp_arithmetic::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, One, Saturating, UniqueSaturatedFrom, UniqueSaturatedInto, Zero};
+use sp_core::{H256, U256};
+use sp_runtime::{DispatchError, ModuleId, Permill};
+use sp_runtime::traits::{AccountIdConversion, Hash, IdentifyAccount, Verify};
+use sp_runtime::transaction_validity::TransactionPriority;
 use sp_std::collections::vec_deque::VecDeque;
-use sp_std::convert::TryInto;
+use sp_std::convert::{TryFrom, TryInto};
 use sp_std::str;
 use sp_std::vec::Vec;
 
@@ -26,28 +29,169 @@ use sp_std::vec::Vec;
 use crate::OrderType::{AskLimit, BidLimit};
 
 //use sp_core::H256;
+pub mod commit_reveal;
+pub mod delegation;
+pub mod integrity;
+pub mod ledger;
+pub mod margin;
+pub mod market_makers;
+pub mod migrations;
+pub mod offchain;
+pub mod order_history;
+pub mod referral;
+pub mod relayer;
+pub mod settlement;
+pub mod simulation;
+
+pub use commit_reveal::RevealedOrder;
+pub use delegation::TradingPermission;
+pub use integrity::IntegrityIssue;
+pub use ledger::TradingBalance;
+pub use margin::{LendingPool, MarginAccount};
+pub use market_makers::MarketMakerObligation;
+pub use offchain::{OffchainBatch, OffchainFill};
+pub use order_history::{OrderRecord, OrderStatus};
+pub use relayer::SignedOrder;
+pub use settlement::SettlementDelta;
+
 #[cfg(test)]
 mod mock;
 
 #[cfg(test)]
 mod tests;
 
+/// Decimals of assets that were never whitelisted with their own, the precision of the native asset.
+pub const DEFAULT_ASSET_DECIMALS: u8 = 12;
+/// FixedU128 carries 18 decimals so no asset can be more precise than that.
+pub const MAX_ASSET_DECIMALS: u8 = 18;
+/// Longest ticker symbol an asset can be given.
+pub const MAX_SYMBOL_LENGTH: usize = 16;
+/// Most TradingPairs a single swap can go through.
+pub const MAX_SWAP_PATH_LENGTH: usize = 4;
+/// Most hops the search for the best swap path quotes, every hop extends a candidate path.
+pub const MAX_SWAP_PATH_CANDIDATES: usize = 64;
+/// Prefix of the payload a trader signs for a SignedOrder, so no other signed message can pass for an order.
+pub const SIGNED_ORDER_DOMAIN: &[u8] = b"dex/signed-order";
+/// Most TradingPairs a trading permission can be restricted to.
+pub const MAX_PERMITTED_TRADING_PAIRS: usize = 16;
+/// Most price levels of each side a depth snapshot returns.
+pub const MAX_DEPTH_LEVELS: u32 = 100;
+/// Most limit orders a margin account can have open on its TradingPair.
+pub const MAX_MARGIN_OPEN_ORDERS: usize = 16;
+/// Liquidity pool of every TradingPair is held by a sub account of this id.
+pub const POOL_MODULE_ID: ModuleId = ModuleId(*b"dex/pool");
+/// Maker rewards are paid out of the account of this id, anyone can fund it with a transfer.
+pub const MAKER_REWARD_MODULE_ID: ModuleId = ModuleId(*b"dex/mkrw");
+/// Lending pool of every asset is held by a sub account of this id.
+pub const LENDING_MODULE_ID: ModuleId = ModuleId(*b"dex/lend");
+/// Isolated margin account of every trader and TradingPair is a sub account of this id.
+pub const MARGIN_MODULE_ID: ModuleId = ModuleId(*b"dex/mrgn");
+/// Trading balances deposited into the DEX are held by the account of this id.
+pub const LEDGER_MODULE_ID: ModuleId = ModuleId(*b"dex/ledg");
+/// Net amounts of the limit order matches of a block are settled through the account of this id.
+pub const SETTLEMENT_MODULE_ID: ModuleId = ModuleId(*b"dex/sttl");
+/// Id of the account holding the referral rewards that are not claimed yet
+pub const REFERRAL_MODULE_ID: ModuleId = ModuleId(*b"dex/rfrl");
+
+/// Number of FixedU128 inner units in one balance unit of an asset with the given decimals.
+pub fn decimals_scale(decimals: u8) -> Option<u128> {
+    let exponent = MAX_ASSET_DECIMALS.checked_sub(decimals)?;
+    10u128.checked_pow(u32::from(exponent))
+}
 
 /// Configure the pallet by specifying the parameters and types on which it depends.
 /// pallet_generic_asset::Trait bounds this DEX pallet with pallet_generic_asset. DEX is available
 /// only for runtimes that also install pallet_generic_asset.
-pub trait Trait: frame_system::Trait + pallet_generic_asset::Trait {
+pub trait Trait: frame_system::Trait + pallet_generic_asset::Trait + SendTransactionTypes<Call<Self>> {
     /// Because this pallet emits events, it depends on the runtime's definition of an event.
     type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
     /// Amount in SpendingAssetCurrency that must reserved to register a tradingPair
     type TradingPairReservationFee: Get<<Self as pallet_generic_asset::Trait>::Balance>;
+    /// Number of blocks for which MarketData of a block is kept before it is pruned, zero disables pruning
+    type MarketDataRetention: Get<Self::BlockNumber>;
+    /// Number of blocks rolled up into a single MarketData before pruning, zero disables the roll up
+    type MarketDataRollupPeriod: Get<Self::BlockNumber>;
+    /// Maximum number of blocks whose MarketData is pruned in a single on_initialize
+    type MaxMarketDataPrunedPerBlock: Get<u32>;
+    /// Number of blocks covered by the rolling ticker statistics (24 hours worth of blocks)
+    type TickerWindow: Get<Self::BlockNumber>;
+    /// Number of blocks aggregated into one ticker bucket, the rolling window advances by this step
+    type TickerBucketPeriod: Get<Self::BlockNumber>;
+    /// Minimum number of blocks between two snapshots of the price accumulators
+    type OracleObservationPeriod: Get<Self::BlockNumber>;
+    /// Number of price accumulator snapshots kept per TradingPair, the longest TWAP/VWAP window
+    /// is OracleObservationPeriod * OracleMaxObservations blocks
+    type OracleMaxObservations: Get<u32>;
+    /// Origin allowed to register trading pairs and to manage the whitelist of tradable assets
+    type RegisterPairOrigin: EnsureOrigin<Self::Origin>;
+    /// Origin allowed to rebuild the price levels of an orderbook
+    type RepairOrigin: EnsureOrigin<Self::Origin>;
+    /// Part of every amount paid into a liquidity pool that is kept by the pool for its providers
+    type PoolSwapFee: Get<Permill>;
+    /// Index of the current reward period, e.g. the session or era index, maker rewards are paid whenever it changes
+    type RewardPeriodIndex: Get<u32>;
+    /// Farthest distance from the mid price, as a part of the mid price, at which resting limit orders earn maker rewards
+    type MakerRewardSpread: Get<Permill>;
+    /// Number of blocks in a year, borrow rates are yearly
+    type BlocksPerYear: Get<Self::BlockNumber>;
+    /// Yearly interest rate of a loan while nothing else is borrowed from its lending pool
+    type BaseBorrowRate: Get<Permill>;
+    /// Yearly interest rate added to BaseBorrowRate once everything in a lending pool is borrowed,
+    /// the rate grows linearly with the borrowed part of the pool in between
+    type BorrowRateSlope: Get<Permill>;
+    /// Highest debt to holdings value of a margin account after borrowing, withdrawing or trading
+    type MaxLoanToValue: Get<Permill>;
+    /// Debt to holdings value above which a margin account can be liquidated
+    type LiquidationLoanToValue: Get<Permill>;
+    /// Number of blocks of the time weighted average price margin accounts are valued at
+    type MarginPriceWindow: Get<Self::BlockNumber>;
+    /// Farthest the market orders of a liquidation may fill from the price margin accounts are valued at
+    type MaxLiquidationSlippage: Get<Permill>;
+    /// Part of the value of the debt a liquidation repays that is paid to the liquidator
+    type LiquidationReward: Get<Permill>;
+    /// Most queued orders the off-chain worker matches in a single batch
+    type MaxOffchainBatchOrders: Get<u32>;
+    /// Priority of the unsigned transactions carrying off-chain matched batches
+    type OffchainBatchPriority: Get<TransactionPriority>;
+    /// Signature of a SignedOrder, checked against the trader of the order
+    type OrderSignature: Parameter + Verify<Signer = Self::OrderSigner>;
+    /// Public key whose account signs a SignedOrder
+    type OrderSigner: IdentifyAccount<AccountId = Self::AccountId>;
+    /// Most signed orders a relayer can submit in a single transaction
+    type MaxSignedOrdersPerBatch: Get<u32>;
+    /// Number of blocks after its commitment in which an order can be revealed
+    type RevealWindow: Get<Self::BlockNumber>;
+    /// Amount of SpendingAssetCurrency a commitment reserves until it is revealed, forfeited if it never is
+    type CommitmentDeposit: Get<<Self as pallet_generic_asset::Trait>::Balance>;
+    /// Most orders that can be revealed in a single block
+    type MaxRevealsPerBlock: Get<u32>;
+    /// Source of the tie-break between orders revealed for commitments of the same block
+    type Randomness: Randomness<Self::Hash>;
+    /// Part of the PoolSwapFee paid by a referred trader that is credited to its referrer
+    type ReferralFeeShare: Get<Permill>;
+    /// Number of blocks for which a filled or cancelled order can still be queried, zero keeps it forever
+    type OrderHistoryRetention: Get<Self::BlockNumber>;
+    /// Most PendingSettlements a block can have, matches that would add more are refused
+    type MaxSettlementsPerBlock: Get<u32>;
+}
+
+/// Time and volume weighted average prices of the DEX orderbooks that other pallets can consume.
+/// All prices are in base asset per unit of quote asset of the TradingPair.
+pub trait DexPriceOracle<Hash, BlockNumber> {
+    /// Price of the last trade of the TradingPair
+    fn last_price(trading_pair: Hash) -> Option<FixedU128>;
+    /// Time weighted average price over the last `window` blocks, None if there is not enough history
+    fn twap(trading_pair: Hash, window: BlockNumber) -> Option<FixedU128>;
+    /// Volume weighted average price over the last `window` blocks, None if nothing was traded
+    fn vwap(trading_pair: Hash, window: BlockNumber) -> Option<FixedU128>;
 }
 
 // Pallets use events to inform users when important changes are made.
 // https://substrate.dev/docs/en/knowledgebase/runtime/events
 decl_event!(
 	pub enum Event<T> where Hash = <T as frame_system::Trait>::Hash,
-	                        AccountId = <T as frame_system::Trait>::AccountId{
+	                        AccountId = <T as frame_system::Trait>::AccountId,
+	                        AssetId = <T as pallet_generic_asset::Trait>::AssetId{
 		/// New Trading pair is created [TradingPairHash]
 		TradingPairCreated(Hash),
 		/// New Limit Order Created [OrderId,TradingPairID,OrderType,Price,Quantity,Trader]
@@ -60,6 +204,92 @@ decl_event!(
 		FulfilledLimitOrder(Hash,Hash,OrderType,FixedU128,FixedU128,AccountId),
 		/// Limit Order Partial Fill  [OrderId,TradingPairID,OrderType,Price,Quantity,Trader]
 		PartialFillLimitOrder(Hash,Hash,OrderType,FixedU128,FixedU128,AccountId),
+		/// Asset is whitelisted for trading [AssetId,Decimals]
+		AssetWhitelisted(AssetId,u8),
+		/// Asset is removed from the trading whitelist [AssetId]
+		AssetRemovedFromWhitelist(AssetId),
+		/// Asset is given a ticker symbol [AssetId,Symbol]
+		AssetSymbolSet(AssetId,Vec<u8>),
+		/// Assets are swapped through a path of TradingPairs [Trader,AssetIn,AmountIn,AssetOut,AmountOut]
+		SwapExecuted(AccountId,AssetId,FixedU128,AssetId,FixedU128),
+		/// Liquidity is added to the pool of the TradingPair [TradingPairID,Provider,BaseAmount,QuoteAmount,Shares]
+		LiquidityAdded(Hash,AccountId,FixedU128,FixedU128,FixedU128),
+		/// Liquidity is removed from the pool of the TradingPair [TradingPairID,Provider,BaseAmount,QuoteAmount,Shares]
+		LiquidityRemoved(Hash,AccountId,FixedU128,FixedU128,FixedU128),
+		/// Order is filled by the liquidity pool [OrderId,TradingPairID,OrderType,BaseAmount,QuoteAmount,Trader]
+		PoolFill(Hash,Hash,OrderType,FixedU128,FixedU128,AccountId),
+		/// Price levels and best prices of the TradingPair were rebuilt [TradingPairHash]
+		OrderbookRepaired(Hash),
+		/// Unused balance reserved for an order is released [OrderId,Trader,AssetId,Amount]
+		ReservationReleased(Hash,AccountId,AssetId,FixedU128),
+		/// Maker reward paid every reward period to the TradingPair is set [TradingPairID,AssetId,RewardPerPeriod]
+		MakerRewardSet(Hash,AssetId,FixedU128),
+		/// Maker reward is paid for the orders resting near the mid price [TradingPairID,Trader,AssetId,Amount]
+		MakerRewardPaid(Hash,AccountId,AssetId,FixedU128),
+		/// Asset is lent to its lending pool [AssetId,Lender,Amount,Shares]
+		LendingDeposited(AssetId,AccountId,FixedU128,FixedU128),
+		/// Asset is withdrawn from its lending pool [AssetId,Lender,Amount,Shares]
+		LendingWithdrawn(AssetId,AccountId,FixedU128,FixedU128),
+		/// Collateral is deposited to a margin account [TradingPairID,Trader,AssetId,Amount]
+		MarginDeposited(Hash,AccountId,AssetId,FixedU128),
+		/// Asset is withdrawn from a margin account [TradingPairID,Trader,AssetId,Amount]
+		MarginWithdrawn(Hash,AccountId,AssetId,FixedU128),
+		/// Margin account borrowed from a lending pool [TradingPairID,Trader,AssetId,Amount]
+		MarginBorrowed(Hash,AccountId,AssetId,FixedU128),
+		/// Margin account repaid a loan [TradingPairID,Trader,AssetId,Amount]
+		MarginRepaid(Hash,AccountId,AssetId,FixedU128),
+		/// Margin account is liquidated [TradingPairID,Trader,Liquidator]
+		MarginLiquidated(Hash,AccountId,AccountId),
+		/// Liquidator is paid its reward out of a liquidated margin account [TradingPairID,Liquidator,AssetId,Amount]
+		LiquidationRewardPaid(Hash,AccountId,AssetId,FixedU128),
+		/// Off-chain matching of the TradingPair is turned on or off [TradingPairID,Enabled]
+		OffchainMatchingSet(Hash,bool),
+		/// Limit Order is queued for the off-chain worker  [OrderId,TradingPairID,OrderType,Price,Quantity,Trader]
+		OrderQueued(Hash,Hash,OrderType,FixedU128,FixedU128,AccountId),
+		/// Batch of off-chain matched fills is settled [TradingPairID,Orders,Fills]
+		OffchainBatchSettled(Hash,u32,u32),
+		/// Relayer submitted signed orders of traders [Relayer,Orders]
+		SignedOrdersRelayed(AccountId,u32),
+		/// Trader committed to an order [Trader,Commitment]
+		OrderCommitted(AccountId,Hash),
+		/// Trader revealed a committed order, it is executed in the next block [Trader,Commitment]
+		OrderRevealed(AccountId,Hash),
+		/// Revealed order could not be executed [Trader,Commitment]
+		RevealedOrderFailed(AccountId,Hash),
+		/// Account allowed another account to trade on its behalf [Owner,Delegate]
+		TradingPermissionGranted(AccountId,AccountId),
+		/// Account no longer allows another account to trade on its behalf [Owner,Delegate]
+		TradingPermissionRevoked(AccountId,AccountId),
+		/// Quoting obligations of the market makers of the TradingPair are set [TradingPairID]
+		MarketMakerObligationSet(Hash),
+		/// Market maker program of the TradingPair ended [TradingPairID]
+		MarketMakerProgramEnded(Hash),
+		/// Account is a designated market maker of the TradingPair [TradingPairID,Maker]
+		MarketMakerRegistered(Hash,AccountId),
+		/// Account is no longer a designated market maker of the TradingPair [TradingPairID,Maker]
+		MarketMakerRemoved(Hash,AccountId),
+		/// Market maker met its obligations for the reward period [TradingPairID,Maker,AssetId,Amount]
+		MarketMakerRewarded(Hash,AccountId,AssetId,FixedU128),
+		/// Market maker missed its obligations for the reward period and lost its status [TradingPairID,Maker]
+		MarketMakerStatusLost(Hash,AccountId),
+		/// Trader deposited into its trading balance [Trader,AssetId,Amount]
+		TradingBalanceDeposited(AccountId,AssetId,FixedU128),
+		/// Trader withdrew from its trading balance [Trader,AssetId,Amount]
+		TradingBalanceWithdrawn(AccountId,AssetId,FixedU128),
+		/// Settlement account doesn't hold the same balance of the asset after the block is settled as before [AssetId]
+		SettlementNotConserved(AssetId),
+		/// Net amount of the trader in the asset could not be settled and was dropped [Trader,AssetId]
+		SettlementFailed(AccountId,AssetId),
+		/// Trader was paid less than its net credit in the asset because a debit of the block was dropped [Trader,AssetId,Unpaid]
+		SettlementShortfall(AccountId,AssetId,FixedU128),
+		/// Account registered the account that referred it [Referee,Referrer]
+		ReferrerRegistered(AccountId,AccountId),
+		/// Referrer earned its share of a fee paid by a referred trader [Referrer,Referee,AssetId,Amount]
+		ReferralRewardAccrued(AccountId,AccountId,AssetId,FixedU128),
+		/// Referrer claimed its referral rewards in the asset [Referrer,AssetId,Amount]
+		ReferralRewardsClaimed(AccountId,AssetId,FixedU128),
+		/// Migration found a TradingPair registered with quote and base swapped, only the first one is indexed [IndexedTradingPair,SkippedTradingPair]
+		ReversedTradingPairNotIndexed(Hash,Hash),
 	}
 );
 
@@ -70,6 +300,8 @@ decl_error! {
 		SameAssetIdsError,
 		/// TradingPair already exists in the system
 		TradingPairIDExists,
+		/// TradingPair exists with quote and base asset swapped
+		ReversedTradingPairExists,
 		/// Insufficent Balance to Execute
 		InsufficientAssetBalance,
 		/// Invalid Price or Quantity for a Limit Order
@@ -103,7 +335,117 @@ decl_error! {
 		/// TradingPair mismatch
 		TradingPairMismatch,
 		/// Invalid OrderID
-		InvalidOrderID
+		InvalidOrderID,
+		/// Asset doesn't exist in pallet_generic_asset
+		AssetDoesNotExist,
+		/// Asset is not whitelisted for trading
+		AssetNotWhitelisted,
+		/// Price is not a multiple of the TradingPair's tick size
+		InvalidTickSize,
+		/// Quantity is not a multiple of the TradingPair's lot size
+		InvalidLotSize,
+		/// Asset has more decimals than FixedU128 can represent
+		InvalidAssetDecimals,
+		/// Asset was already given different decimals
+		AssetDecimalsMismatch,
+		/// Symbol is empty or longer than MAX_SYMBOL_LENGTH
+		InvalidAssetSymbol,
+		/// Symbol is already used by another asset
+		AssetSymbolTaken,
+		/// Swap path is empty, too long or doesn't connect its TradingPairs
+		InvalidSwapPath,
+		/// Swap would return less than the minimum amount asked for
+		SwapOutputTooLow,
+		/// TradingPair has no liquidity pool or it is empty
+		NoLiquidityPool,
+		/// Liquidity amounts must be greater than zero
+		InvalidLiquidityAmount,
+		/// Quote amount needed to keep the pool's ratio is above the maximum given
+		PoolRatioMismatch,
+		/// Pool shares are fewer than asked for or than the provider owns
+		InsufficientPoolShares,
+		/// Pool share asset could not be created, minted or burned
+		PoolShareAssetError,
+		/// Asset has no lending pool
+		NoLendingPool,
+		/// Lending amount must be greater than zero
+		InvalidLendingAmount,
+		/// Lending shares are fewer than the lender owns
+		InsufficientLendingShares,
+		/// Lending pool doesn't hold enough of the asset that isn't lent out
+		InsufficientLendingLiquidity,
+		/// Margin amount must be greater than zero
+		InvalidMarginAmount,
+		/// Asset is neither the base nor the quote asset of the TradingPair
+		AssetNotInTradingPair,
+		/// Trader has no margin account on the TradingPair
+		NoMarginAccount,
+		/// Debt of the margin account would exceed MaxLoanToValue of its holdings
+		MarginAccountUnhealthy,
+		/// Margin account is not below the liquidation threshold
+		MarginAccountHealthy,
+		/// Margin account has MAX_MARGIN_OPEN_ORDERS limit orders open on the TradingPair
+		TooManyMarginOrders,
+		/// TradingPair has not traded for long enough to value a margin account at its average price
+		NoMarkPrice,
+		/// TradingPair is matched on chain
+		NotOffchainMatched,
+		/// Batch doesn't match the queued orders the way the matching engine would
+		InvalidOffchainBatch,
+		/// Queued orders must be settled before off-chain matching is turned off
+		PendingOrdersNotSettled,
+		/// Relayer submitted no signed orders
+		NoSignedOrders,
+		/// Relayer submitted more signed orders than MaxSignedOrdersPerBatch
+		TooManySignedOrders,
+		/// Signature of a signed order is not the one of its trader
+		InvalidOrderSignature,
+		/// Expiry block of a signed order has passed
+		SignedOrderExpired,
+		/// Signed order with the same salt was already submitted by the trader
+		SignedOrderReplayed,
+		/// Trader already committed to the same hash
+		OrderCommitmentExists,
+		/// No commitment of the trader matches the revealed order, or its reveal window has passed
+		NoOrderCommitment,
+		/// Order can only be revealed in a later block than its commitment
+		RevealTooEarly,
+		/// MaxRevealsPerBlock orders were already revealed in this block
+		TooManyReveals,
+		/// Account can't grant a trading permission to itself
+		InvalidDelegate,
+		/// Trading permission is restricted to more TradingPairs than MAX_PERMITTED_TRADING_PAIRS
+		TooManyPermittedPairs,
+		/// Account is not allowed to trade on behalf of the other account
+		NoTradingPermission,
+		/// Trading permission doesn't include the TradingPair
+		TradingPairNotPermitted,
+		/// Order is worth more than the trading permission allows
+		NotionalAboveLimit,
+		/// TradingPair has no market maker program
+		NoMarketMakerObligation,
+		/// Account is already a market maker of the TradingPair
+		AlreadyMarketMaker,
+		/// Account is not a market maker of the TradingPair
+		NotMarketMaker,
+		/// Amount is zero or more precise than the decimals of the asset
+		InvalidTradingBalanceAmount,
+		/// Trading balance is lower than the amount
+		InsufficientTradingBalance,
+		/// Trader must cancel its orders reserving the asset before trading it from the ledger
+		ReservedOutsideTradingLedger,
+		/// Account can't refer itself
+		InvalidReferrer,
+		/// Account registered its referrer before
+		ReferrerAlreadyRegistered,
+		/// Referrer has no rewards to claim
+		NoReferralRewards,
+		/// Balance reserved for the order doesn't cover what it pays for a match
+		InsufficientReservedBalance,
+		/// Market ask can't be valued against the trading permission, there are no bids nor pool liquidity
+		UnknownNotional,
+		/// Block already has MaxSettlementsPerBlock traders and assets to settle
+		TooManySettlements
 	}
 }
 
@@ -120,9 +462,143 @@ decl_storage! {
 	Orderbooks get(fn get_orderbooks): map hasher(identity) T::Hash => Orderbook<T>;
 	// Store MarketData of TradingPairs
 	// If the market data is returning None, then no trades were present for that trading in that block.
-	// TODO: Currently we store market data for all the blocks
+	// Market data older than MarketDataRetention blocks is pruned in on_initialize.
 	MarketInfo get(fn get_marketdata): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::BlockNumber => Option<MarketData>;
+	// Pruned MarketInfo rolled up per MarketDataRollupPeriod, keyed by block_number / MarketDataRollupPeriod.
+	MarketInfoRollup get(fn get_marketdata_rollup): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::BlockNumber => Option<MarketData>;
+	// Next block number whose MarketInfo is yet to be pruned.
+	MarketInfoPruneCursor get(fn get_marketinfo_prune_cursor): T::BlockNumber;
+	// Trading pairs that have MarketInfo in a block, so pruning only visits the entries that were written.
+	MarketInfoPairs get(fn get_marketinfo_pairs): map hasher(twox_64_concat) T::BlockNumber => Vec<T::Hash>;
+	// Ring buffer of ticker buckets for every TradingPair, slot is bucket % number of buckets in TickerWindow.
+	TickerBuckets get(fn get_tickerbuckets): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) u32 => Option<TickerBucket<T::BlockNumber>>;
+	// Price at which the last trade of a TradingPair was executed.
+	LastTradePrice get(fn get_last_trade_price): map hasher(identity) T::Hash => FixedU128;
+	// Cumulative price and volume accumulators of a TradingPair used by the price oracle.
+	PriceAccumulators get(fn get_price_accumulator): map hasher(identity) T::Hash => Option<PriceAccumulator<T::BlockNumber>>;
+	// Ring buffer of accumulator snapshots for every TradingPair, at most one per OracleObservationPeriod.
+	PriceObservations get(fn get_price_observations): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) u32 => Option<PriceObservation<T::BlockNumber>>;
 	Nonce: u128;
+	// Assets that are allowed to be traded on the DEX.
+	WhitelistedAssets get(fn is_whitelisted_asset): map hasher(blake2_128_concat) T::AssetId => bool;
+	// Decimals of an asset's balance, assets without an entry use DEFAULT_ASSET_DECIMALS.
+	AssetDecimals get(fn asset_decimals): map hasher(blake2_128_concat) T::AssetId => Option<u8>;
+	// Ticker symbol of an asset, e.g. "BTC".
+	AssetSymbols get(fn asset_symbol): map hasher(blake2_128_concat) T::AssetId => Option<Vec<u8>>;
+	// Reverse lookup of AssetSymbols, a symbol belongs to at most one asset.
+	SymbolAssets get(fn symbol_asset): map hasher(blake2_128_concat) Vec<u8> => Option<T::AssetId>;
+	// TradingPair of (base_asset_id, quote_asset_id), a set of two assets has at most one TradingPair.
+	TradingPairs get(fn trading_pair_of): double_map hasher(blake2_128_concat) T::AssetId, hasher(blake2_128_concat) T::AssetId => Option<T::Hash>;
+	// Share asset of the liquidity pool of a TradingPair, the pool's reserves are the balances of its pool account.
+	PoolShareAssets get(fn pool_share_asset): map hasher(identity) T::Hash => Option<T::AssetId>;
+	// Maker reward of a TradingPair as (reward_asset_id, reward_per_period), split among its makers by their points.
+	MakerRewards get(fn maker_reward): map hasher(identity) T::Hash => Option<(T::AssetId, FixedU128)>;
+	// Points of a trader in the current reward period, the quantity of its orders resting near the mid price summed over blocks.
+	MakerRewardPoints get(fn maker_reward_points): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => FixedU128;
+	// Sum of the MakerRewardPoints of a TradingPair in the current reward period.
+	MakerRewardTotalPoints get(fn maker_reward_total_points): map hasher(identity) T::Hash => FixedU128;
+	// Reward period the current MakerRewardPoints are earned in.
+	CurrentRewardPeriod get(fn current_reward_period): u32;
+	// Lending pool of an asset that margin accounts borrow from.
+	LendingPools get(fn lending_pool): map hasher(blake2_128_concat) T::AssetId => Option<LendingPool<T::BlockNumber>>;
+	// Shares of a lender in the lending pool of an asset.
+	LendingShares get(fn lending_shares): double_map hasher(blake2_128_concat) T::AssetId, hasher(blake2_128_concat) T::AccountId => FixedU128;
+	// Loans of the isolated margin account of a trader on a TradingPair.
+	MarginAccounts get(fn margin_account): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => Option<MarginAccount>;
+	// Limit orders of the margin account of a trader on a TradingPair that may still be open, by id and price.
+	MarginOpenOrders get(fn margin_open_orders): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => Vec<(T::Hash, FixedU128)>;
+	// TradingPairs whose limit orders are matched by the off-chain worker.
+	OffchainMatching get(fn is_offchain_matched): map hasher(identity) T::Hash => bool;
+	// Limit orders of an off-chain matched TradingPair waiting for the off-chain worker, oldest first.
+	PendingOrders get(fn pending_orders): map hasher(identity) T::Hash => Vec<Order<T>>;
+	// Salts of the signed orders of a trader that were already submitted by relayers.
+	SignedOrderSalts get(fn is_signed_order_salt_used): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) u64 => bool;
+	// Salts of signed orders that expire with the block, pruned at the start of the next one.
+	SignedOrderSaltExpiries: map hasher(blake2_128_concat) T::BlockNumber => Vec<(T::AccountId, u64)>;
+	// Block in which a trader committed to the hash of an order that is not revealed yet.
+	OrderCommitments get(fn order_commitment): double_map hasher(blake2_128_concat) T::AccountId, hasher(identity) T::Hash => Option<T::BlockNumber>;
+	// Deposit reserved by a commitment, returned when it is revealed and slashed when it expires.
+	CommitmentDeposits get(fn commitment_deposit): double_map hasher(blake2_128_concat) T::AccountId, hasher(identity) T::Hash => T::Balance;
+	// Commitments whose reveal window ends with the block, pruned at the start of the next one.
+	CommitmentExpiries: map hasher(blake2_128_concat) T::BlockNumber => Vec<(T::AccountId, T::Hash)>;
+	// Orders revealed in the current block, executed together at the start of the next block.
+	RevealedOrders get(fn revealed_orders): Vec<RevealedOrder<T::AccountId, T::Hash, T::BlockNumber>>;
+	// Permission the first account gave the second one to trade on its behalf.
+	TradingPermissions get(fn trading_permission): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AccountId => Option<TradingPermission<T::Hash>>;
+	// Quoting obligations and reward of the designated market makers of a TradingPair.
+	MarketMakerObligations get(fn market_maker_obligation): map hasher(identity) T::Hash => Option<MarketMakerObligation<T::AssetId>>;
+	// Designated market makers of a TradingPair with the blocks they quoted in during the current reward period.
+	MarketMakers get(fn market_maker_quoted_blocks): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => Option<u32>;
+	// Blocks of the current reward period in which the market makers of a TradingPair were measured.
+	MarketMakerMeasuredBlocks get(fn market_maker_measured_blocks): map hasher(identity) T::Hash => u32;
+	// Measured blocks of the current reward period of the TradingPair when the market maker was registered.
+	MarketMakerRegisteredAt get(fn market_maker_registered_at): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => u32;
+	// Balance of a trader in an asset of the trading ledger, its orders trade from it while it exists.
+	TradingBalances get(fn trading_balance): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AssetId => Option<TradingBalance>;
+	// Sum of the trading balances in an asset, always the balance of the ledger account.
+	TradingBalanceTotals get(fn trading_balance_total): map hasher(blake2_128_concat) T::AssetId => FixedU128;
+	// Amounts exchanged by limit order matches in the current block, settled in on_finalize.
+	PendingSettlements get(fn pending_settlement): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AssetId => SettlementDelta;
+	// Number of PendingSettlements of the current block, at most MaxSettlementsPerBlock.
+	PendingSettlementCount get(fn pending_settlement_count): u32;
+	// Account that referred a trader, registered once by the trader.
+	Referrers get(fn referrer_of): map hasher(blake2_128_concat) T::AccountId => Option<T::AccountId>;
+	// Referral rewards a referrer accrued in an asset and didn't claim yet, held by the referral account.
+	ReferralRewards get(fn referral_reward): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AssetId => FixedU128;
+	// Current state of an order, pruned OrderHistoryRetention blocks after it is filled or cancelled.
+	OrderRecords get(fn order_record): map hasher(identity) T::Hash => Option<OrderRecord<T::AccountId, T::Hash, T::BlockNumber>>;
+	// Orders whose records are pruned at the start of a block.
+	ClosedOrderExpiries: map hasher(blake2_128_concat) T::BlockNumber => Vec<T::Hash>;
+	// Encoding version of the pallet's storage, chains started before versioning was added are on V1_0_0.
+	StorageVersion get(fn storage_version) build(|_: &GenesisConfig<T>| migrations::LATEST_RELEASE): Releases;
+	}
+	add_extra_genesis {
+		// Assets whitelisted for trading at genesis as (asset_id, decimals).
+		config(whitelisted_assets): Vec<(u32, u8)>;
+		// Ticker symbols given to assets at genesis as (asset_id, symbol).
+		config(asset_symbols): Vec<(u32, Vec<u8>)>;
+		// TradingPairs registered at genesis as (quote_asset_id, base_asset_id, tick_size, lot_size).
+		config(orderbooks): Vec<(u32, u32, FixedU128, FixedU128)>;
+		// Resting orders placed at genesis as (trader, order_type, quote_asset_id, base_asset_id, price, quantity).
+		config(orders): Vec<(T::AccountId, OrderType, u32, u32, FixedU128, FixedU128)>;
+		build(|config: &GenesisConfig<T>| {
+			for (asset_id, decimals) in config.whitelisted_assets.iter() {
+				assert!(*decimals <= MAX_ASSET_DECIMALS, "Genesis asset has more decimals than FixedU128 can represent");
+				<WhitelistedAssets<T>>::insert(T::AssetId::from(*asset_id), true);
+				<AssetDecimals<T>>::insert(T::AssetId::from(*asset_id), decimals);
+			}
+			for (asset_id, symbol) in config.asset_symbols.iter() {
+				assert!(Module::<T>::is_valid_symbol(symbol), "Genesis asset symbol is empty or too long");
+				assert!(!<SymbolAssets<T>>::contains_key(symbol), "Genesis asset symbol is used twice");
+				<AssetSymbols<T>>::insert(T::AssetId::from(*asset_id), symbol);
+				<SymbolAssets<T>>::insert(symbol, T::AssetId::from(*asset_id));
+			}
+			for (quote_asset_id, base_asset_id, tick_size, lot_size) in config.orderbooks.iter() {
+				assert!(quote_asset_id != base_asset_id, "Genesis TradingPair has the same asset for quote and base");
+				assert!(Module::<T>::is_whitelisted_asset(T::AssetId::from(*quote_asset_id)) && Module::<T>::is_whitelisted_asset(T::AssetId::from(*base_asset_id)),
+					"Genesis TradingPair has an asset that is not whitelisted");
+				let trading_pair_id = Module::<T>::create_trading_pair_id(quote_asset_id, base_asset_id);
+				assert!(!<Orderbooks<T>>::contains_key(&trading_pair_id), "Genesis TradingPair is registered twice");
+				assert!(Module::<T>::trading_pair_of(T::AssetId::from(*quote_asset_id), T::AssetId::from(*base_asset_id)).is_none(),
+					"Genesis TradingPair is registered with quote and base swapped");
+				Module::<T>::create_order_book((*quote_asset_id).into(), (*base_asset_id).into(), &trading_pair_id, *tick_size, *lot_size);
+			}
+			for (trader, order_type, quote_asset_id, base_asset_id, price, quantity) in config.orders.iter() {
+				let trading_pair_id = Module::<T>::create_trading_pair_id(quote_asset_id, base_asset_id);
+				assert!(<Orderbooks<T>>::contains_key(&trading_pair_id), "Genesis order refers to an unknown TradingPair");
+				let orderbook = <Orderbooks<T>>::get(&trading_pair_id);
+				// BidMarket orders carry the amount to spend in price, their quantity is not filled
+				if *order_type != OrderType::BidMarket {
+					assert!(Orderbook::<T>::is_multiple_of(*quantity, orderbook.lot_size), "Genesis order quantity is not a multiple of the lot size");
+				}
+				if *order_type == OrderType::BidLimit || *order_type == OrderType::AskLimit {
+					assert!(Orderbook::<T>::is_multiple_of(*price, orderbook.tick_size), "Genesis order price is not a multiple of the tick size");
+				}
+				// Genesis storage is built without transaction support, a failing order aborts the build anyway
+				assert!(Module::<T>::execute_order_without_transaction(trader.clone(), order_type.clone(), trading_pair_id, *price, *quantity).is_ok(),
+					"Genesis order failed to execute");
+			}
+		});
 	}
 }
 
@@ -139,47 +615,419 @@ decl_module! {
 		// Events must be initialized if they are used by the pallet.
 		fn deposit_event() = default;
 
+		/// Number of blocks for which MarketData of a block is kept.
+		const MarketDataRetention: T::BlockNumber = T::MarketDataRetention::get();
+
+		/// Number of blocks rolled up into a single MarketData before pruning.
+		const MarketDataRollupPeriod: T::BlockNumber = T::MarketDataRollupPeriod::get();
+
+		fn on_runtime_upgrade() -> Weight {
+			migrations::migrate::<T>()
+		}
+
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			Self::execute_revealed_orders(now)
+				.saturating_add(Self::prune_signed_order_salts(now))
+				.saturating_add(Self::prune_market_data(now))
+				.saturating_add(Self::prune_order_history(now))
+				.saturating_add(Self::accrue_maker_rewards())
+				.saturating_add(Self::settlement_weight())
+		}
+
+		fn on_finalize(_now: T::BlockNumber) {
+			Self::settle_pending_transfers();
+		}
+
+		fn offchain_worker(_now: T::BlockNumber) {
+			Self::match_pending_orders_offchain();
+		}
+
 		// TODO: Note for enabling feeless trades use dispatch::DispatchResultWithPostInfo
 		// TODO: then in the Ok(()) replace it with Ok(Some(0).into()) to make it fee-less
 
 		/// Registers a new trading pair in the system
 		#[weight = 10000]
-		pub fn register_new_orderbook(origin, quote_asset_id: u32, base_asset_id: u32) -> dispatch::DispatchResultWithPostInfo{
-		    let trader = ensure_signed(origin)?;
-		    let a =
+		pub fn register_new_orderbook(origin, quote_asset_id: u32, base_asset_id: u32, tick_size: FixedU128, lot_size: FixedU128) -> dispatch::DispatchResultWithPostInfo{
+		    T::RegisterPairOrigin::ensure_origin(origin.clone())?;
 
 		    ensure!(!(&quote_asset_id == &base_asset_id), <Error<T>>::SameAssetIdsError);
 
+		    // Both the assets must exist and be whitelisted for trading
+		    Self::ensure_tradable_asset(quote_asset_id.into())?;
+		    Self::ensure_tradable_asset(base_asset_id.into())?;
+
 		    // Checks the tradingPair whether exists
 		    let trading_pair_id = Self::create_trading_pair_id(&quote_asset_id,&base_asset_id);
 		    ensure!(!<Orderbooks<T>>::contains_key(&trading_pair_id), <Error<T>>::TradingPairIDExists);
-
-		    // The origin should reserve a certain amount of SpendingAssetCurrency for registering the pair
-		    ensure!(Self::reserve_balance_registration(&trader), <Error<T>>::InsufficientAssetBalance);
-		    Self::create_order_book(quote_asset_id.into(),base_asset_id.into(),&trading_pair_id);
+		    // The same two assets can't be registered again with quote and base swapped
+		    ensure!(Self::trading_pair_of(T::AssetId::from(quote_asset_id), T::AssetId::from(base_asset_id)).is_none(),
+		            <Error<T>>::ReversedTradingPairExists);
+
+		    // A signed origin should reserve a certain amount of SpendingAssetCurrency for registering the pair,
+		    // other origins like Root don't have an account to reserve it from.
+		    if let Ok(trader) = ensure_signed(origin) {
+		        ensure!(Self::reserve_balance_registration(&trader), <Error<T>>::InsufficientAssetBalance);
+		    }
+		    Self::create_order_book(quote_asset_id.into(),base_asset_id.into(),&trading_pair_id, tick_size, lot_size);
 		    Self::deposit_event(RawEvent::TradingPairCreated(trading_pair_id));
 		    Ok(Some(0).into())
 	    }
 
-        /// Submits the given order for matching to engine.
+	    /// Adds the asset to the whitelist of tradable assets, decimals can't change once they are set
+	    #[weight = 10000]
+	    pub fn whitelist_asset(origin, asset_id: u32, decimals: u8) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        let asset_id: T::AssetId = asset_id.into();
+	        ensure!(Self::asset_exists(&asset_id), <Error<T>>::AssetDoesNotExist);
+	        ensure!(decimals <= MAX_ASSET_DECIMALS, <Error<T>>::InvalidAssetDecimals);
+	        // Resting orders and reserves of a delisted asset are still expressed in its old decimals
+	        if let Some(current_decimals) = Self::asset_decimals(&asset_id) {
+	            ensure!(current_decimals == decimals, <Error<T>>::AssetDecimalsMismatch);
+	        }
+	        <WhitelistedAssets<T>>::insert(&asset_id, true);
+	        <AssetDecimals<T>>::insert(&asset_id, decimals);
+	        Self::deposit_event(RawEvent::AssetWhitelisted(asset_id, decimals));
+	        Ok(Some(0).into())
+	    }
+
+	    /// Removes the asset from the whitelist, new orders on pairs with this asset are rejected
+	    #[weight = 10000]
+	    pub fn remove_whitelisted_asset(origin, asset_id: u32) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        let asset_id: T::AssetId = asset_id.into();
+	        ensure!(Self::is_whitelisted_asset(&asset_id), <Error<T>>::AssetNotWhitelisted);
+	        <WhitelistedAssets<T>>::remove(&asset_id);
+	        Self::deposit_event(RawEvent::AssetRemovedFromWhitelist(asset_id));
+	        Ok(Some(0).into())
+	    }
+
+	    /// Gives the asset a unique ticker symbol, replacing the one it had before
+	    #[weight = 10000]
+	    pub fn set_asset_symbol(origin, asset_id: u32, symbol: Vec<u8>) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        let asset_id: T::AssetId = asset_id.into();
+	        ensure!(Self::asset_exists(&asset_id), <Error<T>>::AssetDoesNotExist);
+	        ensure!(Self::is_valid_symbol(&symbol), <Error<T>>::InvalidAssetSymbol);
+	        ensure!(Self::symbol_asset(&symbol).map_or(true, |owner| owner == asset_id), <Error<T>>::AssetSymbolTaken);
+	        if let Some(old_symbol) = <AssetSymbols<T>>::get(&asset_id) {
+	            <SymbolAssets<T>>::remove(&old_symbol);
+	        }
+	        <AssetSymbols<T>>::insert(&asset_id, &symbol);
+	        <SymbolAssets<T>>::insert(&symbol, &asset_id);
+	        Self::deposit_event(RawEvent::AssetSymbolSet(asset_id, symbol));
+	        Ok(Some(0).into())
+	    }
+
+        /// Submits the given order for matching to engine, for on_behalf_of if given and it allowed the caller to trade
         #[weight = 10000]
-	    pub fn submit_order(origin, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128) -> dispatch::DispatchResultWithPostInfo{
-	        let trader = ensure_signed(origin)?;
+	    pub fn submit_order(origin, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128, on_behalf_of: Option<T::AccountId>) -> dispatch::DispatchResultWithPostInfo{
+	        let caller = ensure_signed(origin)?;
+	        let trader = Self::ordering_account(caller, on_behalf_of, &order_type, &trading_pair, price, quantity)?;
    //         let account: AccountId32 = AccountId32::from(trader);
 	        Self::execute_order(trader, order_type, trading_pair, price, quantity)?; // TODO: It maybe an error in which case take the fees else refund
 	        Ok(Some(0).into())
 	    }
 
 
-	    /// Cancels the order
+	    /// Swaps amount_in of asset_in with market orders on every TradingPair of the path, the asset
+	    /// received on one pair is sold on the next. Nothing is swapped if a hop fails or less than
+	    /// min_amount_out of the last asset would be received.
 	    #[weight = 10000]
-	    pub fn cancel_order(origin, order_id: T::Hash, trading_pair: T::Hash, price: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	    pub fn swap_via_path(origin, asset_in: u32, path: Vec<T::Hash>, amount_in: FixedU128, min_amount_out: FixedU128) -> dispatch::DispatchResultWithPostInfo {
 	        let trader = ensure_signed(origin)?;
+	        Self::execute_swap(trader, asset_in.into(), path, amount_in, min_amount_out)?;
+	        Ok(Some(0).into())
+	    }
 
+	    /// Adds base_amount and the quote amount matching the pool's ratio, at most max_quote_amount, to the
+	    /// liquidity pool of the TradingPair. The first provider creates the pool and sets its price.
+	    #[weight = 10000]
+	    pub fn add_liquidity(origin, trading_pair: T::Hash, base_amount: FixedU128, max_quote_amount: FixedU128, min_shares: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let provider = ensure_signed(origin)?;
+	        Self::do_add_liquidity(provider, trading_pair, base_amount, max_quote_amount, min_shares)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Burns the pool shares and returns their part of both reserves of the pool
+	    #[weight = 10000]
+	    pub fn remove_liquidity(origin, trading_pair: T::Hash, shares: FixedU128, min_base_amount: FixedU128, min_quote_amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let provider = ensure_signed(origin)?;
+	        Self::do_remove_liquidity(provider, trading_pair, shares, min_base_amount, min_quote_amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Sets the reward paid every reward period to the makers of the TradingPair, zero ends the reward program
+	    #[weight = 10000]
+	    pub fn set_maker_reward(origin, trading_pair: T::Hash, reward_asset_id: u32, reward_per_period: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
 	        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+	        let reward_asset_id: T::AssetId = reward_asset_id.into();
+	        ensure!(Self::asset_exists(&reward_asset_id), <Error<T>>::AssetDoesNotExist);
+	        if reward_per_period == FixedU128::from(0) {
+	            // Points earned so far are forfeited together with the program
+	            <MakerRewards<T>>::remove(&trading_pair);
+	            <MakerRewardPoints<T>>::remove_prefix(&trading_pair);
+	            <MakerRewardTotalPoints<T>>::remove(&trading_pair);
+	        } else {
+	            <MakerRewards<T>>::insert(&trading_pair, (reward_asset_id, reward_per_period));
+	        }
+	        Self::deposit_event(RawEvent::MakerRewardSet(trading_pair, reward_asset_id, reward_per_period));
+	        Ok(Some(0).into())
+	    }
+
+	    /// Moves the amount of the asset into the trader's trading balance, orders in the asset then trade from it
+	    #[weight = 10000]
+	    pub fn deposit_trading_balance(origin, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_deposit_trading_balance(trader, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Moves the amount of the asset out of the trader's free trading balance
+	    #[weight = 10000]
+	    pub fn withdraw_trading_balance(origin, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_withdraw_trading_balance(trader, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Registers the account that referred the caller, it earns a share of the caller's trading fees
+	    #[weight = 10000]
+	    pub fn register_referrer(origin, referrer: T::AccountId) -> dispatch::DispatchResultWithPostInfo {
+	        let referee = ensure_signed(origin)?;
+	        Self::do_register_referrer(referee, referrer)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Pays out every referral reward the caller accrued
+	    #[weight = 10000]
+	    pub fn claim_referral_rewards(origin) -> dispatch::DispatchResultWithPostInfo {
+	        let referrer = ensure_signed(origin)?;
+	        Self::do_claim_referral_rewards(referrer)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Sets the quoting obligations of the market makers of the TradingPair and the reward paid every
+	    /// reward period to each maker that meets them, starting the program if there was none
+	    #[weight = 10000]
+	    pub fn set_market_maker_obligation(origin, trading_pair: T::Hash, max_spread: Permill, min_quantity: FixedU128, min_uptime: Permill,
+	                                       reward_asset_id: u32, reward_per_period: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        let obligation = MarketMakerObligation { max_spread, min_quantity, min_uptime, reward_asset_id: reward_asset_id.into(), reward_per_period };
+	        Self::do_set_market_maker_obligation(trading_pair, obligation)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Ends the market maker program of the TradingPair, its makers lose their status without a reward
+	    #[weight = 10000]
+	    pub fn end_market_maker_program(origin, trading_pair: T::Hash) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        Self::do_end_market_maker_program(trading_pair)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Makes the account a designated market maker of the TradingPair
+	    #[weight = 10000]
+	    pub fn register_market_maker(origin, trading_pair: T::Hash, maker: T::AccountId) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        Self::do_register_market_maker(trading_pair, maker)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Takes the designated market maker status of the TradingPair from the account
+	    #[weight = 10000]
+	    pub fn remove_market_maker(origin, trading_pair: T::Hash, maker: T::AccountId) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        Self::do_remove_market_maker(trading_pair, maker)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Turns off-chain matching of the TradingPair on or off, it can only be turned off with no queued orders
+	    #[weight = 10000]
+	    pub fn set_offchain_matching(origin, trading_pair: T::Hash, enabled: bool) -> dispatch::DispatchResultWithPostInfo {
+	        T::RegisterPairOrigin::ensure_origin(origin)?;
+	        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+	        ensure!(enabled || <PendingOrders<T>>::get(&trading_pair).is_empty(), <Error<T>>::PendingOrdersNotSettled);
+	        if enabled {
+	            <OffchainMatching<T>>::insert(&trading_pair, true);
+	        } else {
+	            <OffchainMatching<T>>::remove(&trading_pair);
+	        }
+	        Self::deposit_event(RawEvent::OffchainMatchingSet(trading_pair, enabled));
+	        Ok(Some(0).into())
+	    }
+
+	    /// Settles the fills of queued orders matched by the off-chain worker
+	    #[weight = 10000u64.saturating_mul(batch.orders as Weight + batch.fills.len() as Weight)]
+	    pub fn settle_offchain_batch(origin, batch: OffchainBatch<T::Hash>) -> dispatch::DispatchResultWithPostInfo {
+	        ensure_none(origin)?;
+	        Self::do_settle_offchain_batch(batch)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Executes orders signed off chain by their traders, the relayer only pays for the transaction
+	    #[weight = 10000u64.saturating_mul(orders.len() as Weight)]
+	    pub fn submit_signed_orders(origin, orders: Vec<(SignedOrder<T::AccountId, T::Hash, T::BlockNumber>, T::OrderSignature)>) -> dispatch::DispatchResultWithPostInfo {
+	        let relayer = ensure_signed(origin)?;
+	        Self::do_submit_signed_orders(relayer, orders)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Commits to the hash of an order (see order_commitment_hash) to be revealed in a later block
+	    #[weight = 10000]
+	    pub fn commit_order(origin, commitment: T::Hash) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_commit_order(trader, commitment)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Reveals a committed order, it is executed at the start of the next block with the other
+	    /// orders revealed in this block
+	    #[weight = 10000]
+	    pub fn reveal_order(origin, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128, salt: u64) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_reveal_order(trader, order_type, trading_pair, price, quantity, salt)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Allows the delegate to submit and cancel orders on behalf of the caller, on the given
+	    /// TradingPairs only unless empty and up to max_notional per order if given
+	    #[weight = 10000]
+	    pub fn grant_trading_permission(origin, delegate: T::AccountId, trading_pairs: Vec<T::Hash>, max_notional: Option<FixedU128>) -> dispatch::DispatchResultWithPostInfo {
+	        let owner = ensure_signed(origin)?;
+	        Self::do_grant_trading_permission(owner, delegate, trading_pairs, max_notional)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Takes back the trading permission of the delegate, its open orders stay in the book
+	    #[weight = 10000]
+	    pub fn revoke_trading_permission(origin, delegate: T::AccountId) -> dispatch::DispatchResultWithPostInfo {
+	        let owner = ensure_signed(origin)?;
+	        Self::do_revoke_trading_permission(owner, delegate)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Lends the amount of the asset to its lending pool for shares of the pool
+	    #[weight = 10000]
+	    pub fn deposit_lending(origin, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let lender = ensure_signed(origin)?;
+	        Self::do_deposit_lending(lender, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Redeems the lending pool shares for their part of the pool including the interest earned
+	    #[weight = 10000]
+	    pub fn withdraw_lending(origin, asset_id: u32, shares: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let lender = ensure_signed(origin)?;
+	        Self::do_withdraw_lending(lender, asset_id.into(), shares)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Moves collateral to the margin account of the TradingPair, opening it if needed
+	    #[weight = 10000]
+	    pub fn deposit_margin(origin, trading_pair: T::Hash, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_deposit_margin(trader, trading_pair, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Moves an asset out of the margin account as long as the account stays within MaxLoanToValue
+	    #[weight = 10000]
+	    pub fn withdraw_margin(origin, trading_pair: T::Hash, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_withdraw_margin(trader, trading_pair, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Borrows the base or quote asset of the TradingPair from its lending pool into the margin account
+	    #[weight = 10000]
+	    pub fn borrow(origin, trading_pair: T::Hash, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_borrow(trader, trading_pair, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Repays up to the amount of the margin account's loan out of the margin account
+	    #[weight = 10000]
+	    pub fn repay(origin, trading_pair: T::Hash, asset_id: u32, amount: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_repay(trader, trading_pair, asset_id.into(), amount)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Submits an order on behalf of the margin account of the TradingPair
+	    #[weight = 10000]
+	    pub fn submit_margin_order(origin, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        Self::do_submit_margin_order(trader, order_type, trading_pair, price, quantity)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Cancels an order of the margin account of the TradingPair
+	    #[weight = 10000]
+	    pub fn cancel_margin_order(origin, order_id: T::Hash, trading_pair: T::Hash, price: FixedU128) -> dispatch::DispatchResultWithPostInfo {
+	        let trader = ensure_signed(origin)?;
+	        ensure!(<MarginAccounts<T>>::contains_key(&trading_pair, &trader), <Error<T>>::NoMarginAccount);
+	        Self::cancel_order_from_orderbook(Self::margin_account_id(&trading_pair, &trader), order_id, trading_pair, price)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Liquidates a margin account whose health factor dropped below one, anyone can call it
+	    #[weight = 10000]
+	    pub fn liquidate_margin_account(origin, trading_pair: T::Hash, trader: T::AccountId) -> dispatch::DispatchResultWithPostInfo {
+	        let liquidator = ensure_signed(origin)?;
+	        Self::do_liquidate_margin_account(liquidator, trading_pair, trader)?;
+	        Ok(Some(0).into())
+	    }
+
+	    /// Cancels the order
+	    #[weight = 10000]
+	    pub fn cancel_order(origin, order_id: T::Hash, trading_pair: T::Hash, price: FixedU128, on_behalf_of: Option<T::AccountId>) -> dispatch::DispatchResultWithPostInfo {
+	        let caller = ensure_signed(origin)?;
+
+	        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+	        let trader = Self::cancelling_account(caller, on_behalf_of, &trading_pair)?;
 	        Self::cancel_order_from_orderbook(trader,order_id,trading_pair,price)?;
 	        Ok(Some(0).into())
 	    }
+
+	    /// Rebuilds the price level linked list and best prices of the TradingPair from its level vectors
+	    #[weight = 10000]
+	    pub fn repair_orderbook(origin, trading_pair: T::Hash) -> dispatch::DispatchResultWithPostInfo {
+	        T::RepairOrigin::ensure_origin(origin)?;
+
+	        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+	        Self::rebuild_orderbook(trading_pair);
+	        Self::deposit_event(RawEvent::OrderbookRepaired(trading_pair));
+	        Ok(Some(0).into())
+	    }
+    }
+}
+
+/// Encoding versions of the DEX pallet's storage
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Releases {
+    /// Storage as released before versioning was added
+    V1_0_0,
+    /// Orderbook carries tick_size and lot_size
+    V2_0_0,
+    /// Order tracks the balance reserved for it
+    V3_0_0,
+    /// TradingPairs indexes every Orderbook by its asset ids
+    V4_0_0,
+    /// Assets of every Orderbook are whitelisted
+    V5_0_0,
+    /// MarketInfoPairs indexes the trading pairs with MarketInfo in every block
+    V6_0_0,
+    /// OrderRecords has a record of every resting and queued order
+    V7_0_0,
+    /// CurrentRewardPeriod starts from RewardPeriodIndex instead of zero
+    V8_0_0,
+}
+
+impl Default for Releases {
+    fn default() -> Self {
+        Releases::V1_0_0
     }
 }
 
@@ -213,16 +1061,19 @@ pub struct Order<T> where T: Trait {
     price: FixedU128,
     quantity: FixedU128,
     order_type: OrderType,
+    // Balance still reserved for the order, in base asset for BidLimit and quote asset for AskLimit
+    reserved: FixedU128,
 }
 
 impl<T> Order<T> where T: Trait {
-    pub fn convert(self) -> Order4RPC {
+    // Prices are expressed in decimals of the base asset and quantities in decimals of the quote asset
+    pub fn convert(self, base_decimals: u8, quote_decimals: u8) -> Order4RPC {
         Order4RPC {
             id: Self::account_to_bytes(&self.id).unwrap(),
             trading_pair: Self::account_to_bytes(&self.trading_pair).unwrap(),
             trader: Self::account_to_bytes(&self.trader).unwrap(),
-            price: Self::convert_fixed_u128_to_balance(self.price).unwrap(),
-            quantity: Self::convert_fixed_u128_to_balance(self.quantity).unwrap(),
+            price: Self::convert_fixed_u128_to_balance(self.price, base_decimals).unwrap(),
+            quantity: Self::convert_fixed_u128_to_balance(self.quantity, quote_decimals).unwrap(),
             order_type: self.order_type,
         }
     }
@@ -237,13 +1088,9 @@ impl<T> Order<T> where T: Trait {
         Ok(bytes)
     }
 
-    pub fn convert_fixed_u128_to_balance(x: FixedU128) -> Option<u128> {
-        if let Some(balance_in_fixed_u128) = x.checked_div(&FixedU128::from(1000000)) {
-            let balance_in_u128 = balance_in_fixed_u128.into_inner();
-            Some(balance_in_u128)
-        } else {
-            None
-        }
+    pub fn convert_fixed_u128_to_balance(x: FixedU128, decimals: u8) -> Option<u128> {
+        let scale = decimals_scale(decimals)?;
+        Some(x.into_inner() / scale)
     }
 }
 
@@ -267,26 +1114,22 @@ pub struct LinkedPriceLevel<T> where T: Trait {
 }
 
 impl<T> LinkedPriceLevel<T> where T: Trait {
-    fn covert(self) -> LinkedPriceLevelRpc {
+    fn covert(self, base_decimals: u8, quote_decimals: u8) -> LinkedPriceLevelRpc {
         LinkedPriceLevelRpc {
-            next: Self::convert_fixed_u128_to_balance(self.next.unwrap()).unwrap(),
-            prev: Self::convert_fixed_u128_to_balance(self.prev.unwrap()).unwrap(),
-            orders: Self::cov_de_vec(self.clone().orders),
+            next: Self::convert_fixed_u128_to_balance(self.next.unwrap(), base_decimals).unwrap(),
+            prev: Self::convert_fixed_u128_to_balance(self.prev.unwrap(), base_decimals).unwrap(),
+            orders: Self::cov_de_vec(self.clone().orders, base_decimals, quote_decimals),
         }
     }
 
-    fn cov_de_vec(temp: VecDeque<Order<T>>) -> Vec<Order4RPC> {
-        let temp3: Vec<Order4RPC> = temp.into_iter().map(|element: Order<T>| element.convert()).collect();
+    fn cov_de_vec(temp: VecDeque<Order<T>>, base_decimals: u8, quote_decimals: u8) -> Vec<Order4RPC> {
+        let temp3: Vec<Order4RPC> = temp.into_iter().map(|element: Order<T>| element.convert(base_decimals, quote_decimals)).collect();
         temp3
     }
 
-    fn convert_fixed_u128_to_balance(x: FixedU128) -> Option<u128> {
-        if let Some(balance_in_fixed_u128) = x.checked_div(&FixedU128::from(1000000)) {
-            let balance_in_u128 = balance_in_fixed_u128.into_inner();
-            Some(balance_in_u128)
-        } else {
-            None
-        }
+    fn convert_fixed_u128_to_balance(x: FixedU128, decimals: u8) -> Option<u128> {
+        let scale = decimals_scale(decimals)?;
+        Some(x.into_inner() / scale)
     }
 }
 
@@ -317,16 +1160,24 @@ pub struct Orderbook<T> where T: Trait {
     quote_asset_id: T::AssetId,
     best_bid_price: FixedU128,
     best_ask_price: FixedU128,
+    // Limit prices must be a multiple of tick_size, zero means any price is allowed.
+    tick_size: FixedU128,
+    // Order quantities must be a multiple of lot_size, zero means any quantity is allowed.
+    lot_size: FixedU128,
 }
 
 impl<T> Orderbook<T> where T: Trait {
     fn convert(self) -> OrderbookRpc {
+        let base_decimals = Module::<T>::decimals_of(&self.base_asset_id);
+        let quote_decimals = Module::<T>::decimals_of(&self.quote_asset_id);
         OrderbookRpc {
             trading_pair: Self::account_to_bytes(&self.trading_pair).unwrap(),
             base_asset_id : TryInto::<u32>::try_into(self.base_asset_id).ok().unwrap(),
             quote_asset_id : TryInto::<u32>::try_into(self.quote_asset_id).ok().unwrap(),
-            best_bid_price : Self::convert_fixed_u128_to_balance(self.best_bid_price).unwrap(),
-            best_ask_price : Self::convert_fixed_u128_to_balance(self.best_ask_price).unwrap(),
+            best_bid_price : Self::convert_fixed_u128_to_balance(self.best_bid_price, base_decimals).unwrap(),
+            best_ask_price : Self::convert_fixed_u128_to_balance(self.best_ask_price, base_decimals).unwrap(),
+            tick_size : Self::convert_fixed_u128_to_balance(self.tick_size, base_decimals).unwrap(),
+            lot_size : Self::convert_fixed_u128_to_balance(self.lot_size, quote_decimals).unwrap(),
         }
     }
 
@@ -340,13 +1191,9 @@ impl<T> Orderbook<T> where T: Trait {
         Ok(bytes)
     }
 
-    fn convert_fixed_u128_to_balance(x: FixedU128) -> Option<u128> {
-        if let Some(balance_in_fixed_u128) = x.checked_div(&FixedU128::from(1000000)) {
-            let balance_in_u128 = balance_in_fixed_u128.into_inner();
-            Some(balance_in_u128)
-        } else {
-            None
-        }
+    fn convert_fixed_u128_to_balance(x: FixedU128, decimals: u8) -> Option<u128> {
+        let scale = decimals_scale(decimals)?;
+        Some(x.into_inner() / scale)
     }
 
 }
@@ -359,18 +1206,33 @@ impl<T> Default for Orderbook<T> where T: Trait {
             quote_asset_id: 0.into(),
             best_bid_price: FixedU128::from(0),
             best_ask_price: FixedU128::from(0),
+            tick_size: FixedU128::from(0),
+            lot_size: FixedU128::from(0),
         }
     }
 }
 
 impl<T> Orderbook<T> where T: Trait {
-    fn new(base_asset_id: T::AssetId, quote_asset_id: T::AssetId, trading_pair: T::Hash) -> Self {
+    fn new(base_asset_id: T::AssetId, quote_asset_id: T::AssetId, trading_pair: T::Hash, tick_size: FixedU128, lot_size: FixedU128) -> Self {
         Orderbook {
             trading_pair,
             base_asset_id,
             quote_asset_id,
             best_bid_price: FixedU128::from(0),
             best_ask_price: FixedU128::from(0),
+            tick_size,
+            lot_size,
+        }
+    }
+
+    // Checks that the value is a whole multiple of step, a zero step allows any value
+    fn is_multiple_of(value: FixedU128, step: FixedU128) -> bool {
+        if step == FixedU128::from(0) {
+            return true;
+        }
+        match value.checked_div(&step) {
+            Some(steps) => steps.frac() == FixedU128::from(0),
+            None => false,
         }
     }
 }
@@ -382,6 +1244,8 @@ pub struct OrderbookRpc {
     quote_asset_id: u32,
     best_bid_price: u128,
     best_ask_price: u128,
+    tick_size: u128,
+    lot_size: u128,
 }
 
 
@@ -396,22 +1260,30 @@ pub struct MarketData {
 }
 
 impl MarketData {
-    fn convert (self) -> MarketDataRpc {
+    // Merges the MarketData of another period into this one
+    fn merge(&mut self, other: &MarketData) {
+        if self.low == FixedU128::from(0) || (other.low != FixedU128::from(0) && other.low < self.low) {
+            self.low = other.low
+        }
+        if self.high < other.high {
+            self.high = other.high
+        }
+        self.volume = self.volume.saturating_add(other.volume);
+    }
+
+    // Prices and volume are all expressed in the base asset
+    fn convert (self, base_decimals: u8) -> MarketDataRpc {
         MarketDataRpc {
-            low: Self::convert_fixed_u128_to_balance(self.low).unwrap(),
-            high: Self::convert_fixed_u128_to_balance(self.high).unwrap(),
-            volume: Self::convert_fixed_u128_to_balance(self.volume).unwrap(),
+            low: Self::convert_fixed_u128_to_balance(self.low, base_decimals).unwrap(),
+            high: Self::convert_fixed_u128_to_balance(self.high, base_decimals).unwrap(),
+            volume: Self::convert_fixed_u128_to_balance(self.volume, base_decimals).unwrap(),
         }
 
     }
 
-    fn convert_fixed_u128_to_balance(x: FixedU128) -> Option<u128> {
-        if let Some(balance_in_fixed_u128) = x.checked_div(&FixedU128::from(1000000)) {
-            let balance_in_u128 = balance_in_fixed_u128.into_inner();
-            Some(balance_in_u128)
-        } else {
-            None
-        }
+    fn convert_fixed_u128_to_balance(x: FixedU128, decimals: u8) -> Option<u128> {
+        let scale = decimals_scale(decimals)?;
+        Some(x.into_inner() / scale)
     }
 }
 
@@ -423,6 +1295,212 @@ pub struct MarketDataRpc {
     volume: u128,
 }
 
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub struct TickerBucket<BlockNumber> {
+    // Index of the bucket i.e. block_number / TickerBucketPeriod
+    bucket: BlockNumber,
+    // Price of the first trade in the bucket.
+    open: FixedU128,
+    // Price of the last trade in the bucket.
+    close: FixedU128,
+    high: FixedU128,
+    low: FixedU128,
+    // Volume traded in base asset.
+    base_volume: FixedU128,
+    // Volume traded in quote asset.
+    quote_volume: FixedU128,
+    trade_count: u32,
+}
+
+impl<BlockNumber> TickerBucket<BlockNumber> {
+    fn new(bucket: BlockNumber, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) -> Self {
+        TickerBucket {
+            bucket,
+            open: price,
+            close: price,
+            high: price,
+            low: price,
+            base_volume: trade_amount,
+            quote_volume: quantity,
+            trade_count: 1,
+        }
+    }
+
+    fn add_trade(&mut self, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) {
+        self.close = price;
+        if self.high < price {
+            self.high = price
+        }
+        if self.low > price {
+            self.low = price
+        }
+        self.base_volume = self.base_volume.saturating_add(trade_amount);
+        self.quote_volume = self.quote_volume.saturating_add(quantity);
+        self.trade_count = self.trade_count.saturating_add(1);
+    }
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub struct PriceAccumulator<BlockNumber> {
+    // Sum of price * blocks for which the price was the last traded price.
+    price_cumulative: FixedU128,
+    // Sum of volume traded in base asset.
+    volume_cumulative: FixedU128,
+    // Sum of volume traded in quote asset.
+    quantity_cumulative: FixedU128,
+    last_price: FixedU128,
+    // Block at which price_cumulative was last brought up to date.
+    last_update: BlockNumber,
+    // Slot of the latest snapshot in PriceObservations and the block it was taken at.
+    observation_index: u32,
+    last_observation: BlockNumber,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub struct PriceObservation<BlockNumber> {
+    block: BlockNumber,
+    price_cumulative: FixedU128,
+    volume_cumulative: FixedU128,
+    quantity_cumulative: FixedU128,
+}
+
+// Reserves and issued shares of the liquidity pool of a TradingPair as balances of their assets
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct LiquidityPoolRpc {
+    share_asset_id: u32,
+    base_reserve: u128,
+    quote_reserve: u128,
+    total_shares: u128,
+}
+
+// State of an order with prices in decimals of the base asset and quantities in decimals of the quote
+// asset, quantities of a BidMarket order are in decimals of the base asset
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct OrderRecordRpc {
+    trading_pair: [u8; 32],
+    trader: [u8; 32],
+    order_type: OrderType,
+    price: u128,
+    remaining_quantity: u128,
+    filled_quantity: u128,
+    status: OrderStatus,
+}
+
+// Price level of a depth snapshot with the quantity of all its orders, in decimals of the base and quote asset
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct DepthLevelRpc {
+    price: u128,
+    quantity: u128,
+    order_count: u32,
+}
+
+// Best price levels of both sides of an orderbook, best first
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct DepthRpc {
+    bids: Vec<DepthLevelRpc>,
+    asks: Vec<DepthLevelRpc>,
+}
+
+// Book price level or liquidity pool an order is filled against, in decimals of the base and quote asset
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SimulatedFillRpc {
+    price: u128,
+    quantity: u128,
+    from_pool: bool,
+}
+
+// Outcome of an order executed against the current state without keeping it. Prices are in decimals of
+// the base asset, quantities in decimals of the quote asset except the remaining budget of a BidMarket
+// order, fee is the pool fee in decimals of the asset the order pays with. error is the name of the
+// Error submit_order would fail with.
+#[derive(Encode, Decode, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SimulatedOrderRpc {
+    fills: Vec<SimulatedFillRpc>,
+    filled_quantity: u128,
+    average_price: u128,
+    fee: u128,
+    remaining_quantity: u128,
+    resting: bool,
+    error: Option<Vec<u8>>,
+}
+
+// Referral rewards a referrer can claim in an asset, as a balance of the asset
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ReferralRewardRpc {
+    asset_id: u32,
+    amount: u128,
+}
+
+// Best path of TradingPairs for a swap and the amount it returns in decimals of the asset bought
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SwapPathRpc {
+    path: Vec<[u8; 32]>,
+    amount_out: u128,
+}
+
+// Rolling statistics of a TradingPair over the TickerWindow
+pub struct Ticker<T> where T: Trait {
+    trading_pair: T::Hash,
+    last_price: FixedU128,
+    open_price: FixedU128,
+    high: FixedU128,
+    low: FixedU128,
+    base_volume: FixedU128,
+    quote_volume: FixedU128,
+    best_bid_price: FixedU128,
+    best_ask_price: FixedU128,
+    trade_count: u32,
+}
+
+impl<T> Ticker<T> where T: Trait {
+    fn convert(self, base_decimals: u8, quote_decimals: u8) -> TickerRpc {
+        let last_price = Self::convert_fixed_u128_to_balance(self.last_price, base_decimals).unwrap();
+        let open_price = Self::convert_fixed_u128_to_balance(self.open_price, base_decimals).unwrap();
+        TickerRpc {
+            trading_pair: Order::<T>::account_to_bytes(&self.trading_pair).unwrap(),
+            last_price,
+            open_price,
+            price_change: i128::try_from(last_price).unwrap_or(i128::MAX).saturating_sub(i128::try_from(open_price).unwrap_or(i128::MAX)),
+            high: Self::convert_fixed_u128_to_balance(self.high, base_decimals).unwrap(),
+            low: Self::convert_fixed_u128_to_balance(self.low, base_decimals).unwrap(),
+            base_volume: Self::convert_fixed_u128_to_balance(self.base_volume, base_decimals).unwrap(),
+            quote_volume: Self::convert_fixed_u128_to_balance(self.quote_volume, quote_decimals).unwrap(),
+            best_bid_price: Self::convert_fixed_u128_to_balance(self.best_bid_price, base_decimals).unwrap(),
+            best_ask_price: Self::convert_fixed_u128_to_balance(self.best_ask_price, base_decimals).unwrap(),
+            trade_count: self.trade_count,
+        }
+    }
+
+    fn convert_fixed_u128_to_balance(x: FixedU128, decimals: u8) -> Option<u128> {
+        let scale = decimals_scale(decimals)?;
+        Some(x.into_inner() / scale)
+    }
+}
+
+#[derive(Encode, Decode, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct TickerRpc {
+    trading_pair: [u8; 32],
+    last_price: u128,
+    open_price: u128,
+    price_change: i128,
+    high: u128,
+    low: u128,
+    base_volume: u128,
+    quote_volume: u128,
+    best_bid_price: u128,
+    best_ask_price: u128,
+    trade_count: u32,
+}
+
 impl<T: Trait> Module<T> {
     pub fn get_ask_level(trading_pair: T::Hash) -> Vec<FixedU128> {
         <AsksLevels<T>>::get(trading_pair)
@@ -432,48 +1510,440 @@ impl<T: Trait> Module<T> {
         <BidsLevels<T>>::get(trading_pair)
     }
 
-    pub fn get_price_level(trading_pair: T::Hash) -> Vec<LinkedPriceLevelRpc> {
-        let temp: Vec<LinkedPriceLevel<T>> = <PriceLevels<T>>::iter_prefix_values(&trading_pair).collect();
-        let temp2: Vec<LinkedPriceLevelRpc> = temp.into_iter().map(|element| element.covert()).collect();
-        temp2
+    pub fn get_price_level(trading_pair: T::Hash) -> Vec<LinkedPriceLevelRpc> {
+        let (base_decimals, quote_decimals) = Self::pair_decimals(&trading_pair);
+        let temp: Vec<LinkedPriceLevel<T>> = <PriceLevels<T>>::iter_prefix_values(&trading_pair).collect();
+        let temp2: Vec<LinkedPriceLevelRpc> = temp.into_iter().map(|element| element.covert(base_decimals, quote_decimals)).collect();
+        temp2
+    }
+
+    pub fn get_depth(trading_pair: T::Hash, levels: u32) -> DepthRpc {
+        let levels = levels.min(MAX_DEPTH_LEVELS) as usize;
+        let bids: Vec<FixedU128> = <BidsLevels<T>>::get(&trading_pair).into_iter().rev().take(levels).collect();
+        let asks: Vec<FixedU128> = <AsksLevels<T>>::get(&trading_pair).into_iter().take(levels).collect();
+        DepthRpc {
+            bids: Self::depth_levels(&trading_pair, bids),
+            asks: Self::depth_levels(&trading_pair, asks),
+        }
+    }
+
+    // Aggregates the orders of every price level, a level that doesn't fit in a balance is left out
+    fn depth_levels(trading_pair: &T::Hash, prices: Vec<FixedU128>) -> Vec<DepthLevelRpc> {
+        let (base_decimals, quote_decimals) = Self::pair_decimals(trading_pair);
+        prices.into_iter().filter_map(|price| {
+            let price_level: LinkedPriceLevel<T> = <PriceLevels<T>>::get(trading_pair, price);
+            let quantity = price_level.orders.iter().fold(FixedU128::from(0), |total, order| total.saturating_add(order.quantity));
+            Some(DepthLevelRpc {
+                price: Order::<T>::convert_fixed_u128_to_balance(price, base_decimals)?,
+                quantity: Order::<T>::convert_fixed_u128_to_balance(quantity, quote_decimals)?,
+                order_count: price_level.orders.len() as u32,
+            })
+        }).collect()
+    }
+
+    pub fn get_orderbook(trading_pair: T::Hash) -> OrderbookRpc {
+        let orderbook = <Orderbooks<T>>::get(trading_pair);
+
+        orderbook.convert()
+    }
+
+    pub fn get_all_orderbook() -> Vec<OrderbookRpc> {
+        let orderbook:Vec<OrderbookRpc> = <Orderbooks<T>>::iter().map(|(_key, value)| value).map(|orderbook| orderbook.convert()).collect();
+        orderbook
+    }
+
+    pub fn get_market_info(trading_pair: T::Hash,blocknum: u32) -> MarketDataRpc {
+        let blocknum = Self::u32_to_blocknum(blocknum);
+        let temp = <MarketInfo<T>>::get(trading_pair, blocknum);
+        temp.unwrap().convert(Self::pair_decimals(&trading_pair).0)
+    }
+
+    pub fn get_market_info_rollup(trading_pair: T::Hash, bucket: u32) -> Option<MarketDataRpc> {
+        let bucket = Self::u32_to_blocknum(bucket);
+        let base_decimals = Self::pair_decimals(&trading_pair).0;
+        <MarketInfoRollup<T>>::get(trading_pair, bucket).map(|market_data| market_data.convert(base_decimals))
+    }
+
+    pub fn get_ticker(trading_pair: T::Hash) -> TickerRpc {
+        let (base_decimals, quote_decimals) = Self::pair_decimals(&trading_pair);
+        Self::compute_ticker(trading_pair).convert(base_decimals, quote_decimals)
+    }
+
+    pub fn get_all_tickers() -> Vec<TickerRpc> {
+        let tickers: Vec<TickerRpc> = <Orderbooks<T>>::iter().map(|(trading_pair, _)| Self::get_ticker(trading_pair)).collect();
+        tickers
+    }
+
+    pub fn get_twap(trading_pair: T::Hash, window: u32) -> Option<u128> {
+        let twap = <Self as DexPriceOracle<T::Hash, T::BlockNumber>>::twap(trading_pair, Self::u32_to_blocknum(window))?;
+        Order::<T>::convert_fixed_u128_to_balance(twap, Self::pair_decimals(&trading_pair).0)
+    }
+
+    pub fn get_vwap(trading_pair: T::Hash, window: u32) -> Option<u128> {
+        let vwap = <Self as DexPriceOracle<T::Hash, T::BlockNumber>>::vwap(trading_pair, Self::u32_to_blocknum(window))?;
+        Order::<T>::convert_fixed_u128_to_balance(vwap, Self::pair_decimals(&trading_pair).0)
+    }
+
+    pub fn get_trading_pair(base_asset_id: u32, quote_asset_id: u32) -> Option<T::Hash> {
+        Self::trading_pair_of(T::AssetId::from(base_asset_id), T::AssetId::from(quote_asset_id))
+    }
+
+    pub fn get_trading_pair_by_symbol(base_symbol: Vec<u8>, quote_symbol: Vec<u8>) -> Option<T::Hash> {
+        let base_asset_id = Self::symbol_asset(base_symbol)?;
+        let quote_asset_id = Self::symbol_asset(quote_symbol)?;
+        Self::trading_pair_of(base_asset_id, quote_asset_id)
+    }
+
+    pub fn get_best_swap_path(asset_in: u32, asset_out: u32, amount_in: FixedU128) -> Option<SwapPathRpc> {
+        let asset_out: T::AssetId = asset_out.into();
+        let (path, amount_out) = Self::best_swap_path(asset_in.into(), asset_out, amount_in)?;
+        Some(SwapPathRpc {
+            path: path.iter().map(|trading_pair| Order::<T>::account_to_bytes(trading_pair).unwrap()).collect(),
+            amount_out: Order::<T>::convert_fixed_u128_to_balance(amount_out, Self::decimals_of(&asset_out))?,
+        })
+    }
+
+    pub fn get_liquidity_pool(trading_pair: T::Hash) -> Option<LiquidityPoolRpc> {
+        let share_asset_id = Self::pool_share_asset(&trading_pair)?;
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let pool = Self::pool_account(&trading_pair);
+        Some(LiquidityPoolRpc {
+            share_asset_id: TryInto::<u32>::try_into(share_asset_id).ok()?,
+            base_reserve: TryInto::<u128>::try_into(pallet_generic_asset::Module::<T>::free_balance(&orderbook.base_asset_id, &pool)).ok()?,
+            quote_reserve: TryInto::<u128>::try_into(pallet_generic_asset::Module::<T>::free_balance(&orderbook.quote_asset_id, &pool)).ok()?,
+            total_shares: TryInto::<u128>::try_into(pallet_generic_asset::Module::<T>::total_issuance(&share_asset_id)).ok()?,
+        })
+    }
+
+    pub fn get_order(order_id: T::Hash) -> Option<OrderRecordRpc> {
+        let record = Self::order_record(&order_id)?;
+        let (base_decimals, quote_decimals) = Self::pair_decimals(&record.trading_pair);
+        let quantity_decimals = if record.order_type == OrderType::BidMarket { base_decimals } else { quote_decimals };
+        Some(OrderRecordRpc {
+            trading_pair: Order::<T>::account_to_bytes(&record.trading_pair).ok()?,
+            trader: Order::<T>::account_to_bytes(&record.trader).ok()?,
+            price: Order::<T>::convert_fixed_u128_to_balance(record.price, base_decimals)?,
+            remaining_quantity: Order::<T>::convert_fixed_u128_to_balance(record.remaining, quantity_decimals)?,
+            filled_quantity: Order::<T>::convert_fixed_u128_to_balance(record.quantity.saturating_sub(record.remaining), quantity_decimals)?,
+            order_type: record.order_type,
+            status: record.status,
+        })
+    }
+
+    pub fn get_referral_rewards(referrer: [u8; 32]) -> Vec<ReferralRewardRpc> {
+        let referrer = match T::AccountId::decode(&mut &referrer[..]) {
+            Ok(referrer) => referrer,
+            Err(_) => return Vec::new(),
+        };
+        <ReferralRewards<T>>::iter_prefix(&referrer).filter_map(|(asset_id, amount)| Some(ReferralRewardRpc {
+            asset_id: TryInto::<u32>::try_into(asset_id).ok()?,
+            amount: TryInto::<u128>::try_into(Self::convert_fixed_u128_to_balance(&asset_id, amount)?).ok()?,
+        })).collect()
+    }
+
+    // Decimals of the base and quote asset of the TradingPair
+    fn pair_decimals(trading_pair: &T::Hash) -> (u8, u8) {
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+        (Self::decimals_of(&orderbook.base_asset_id), Self::decimals_of(&orderbook.quote_asset_id))
+    }
+
+    pub fn u32_to_blocknum(input: u32) -> T::BlockNumber {
+        input.into()
+    }
+}
+
+impl<T: Trait> DexPriceOracle<T::Hash, T::BlockNumber> for Module<T> {
+    fn last_price(trading_pair: T::Hash) -> Option<FixedU128> {
+        <PriceAccumulators<T>>::get(&trading_pair).map(|accumulator| accumulator.last_price)
+    }
+
+    fn twap(trading_pair: T::Hash, window: T::BlockNumber) -> Option<FixedU128> {
+        let (accumulator, observation, next) = Self::oracle_window(&trading_pair, window)?;
+        let now = <frame_system::Module<T>>::block_number();
+        let blocks = |from: T::BlockNumber, to: T::BlockNumber| -> u128 { UniqueSaturatedInto::<u128>::unique_saturated_into(to.saturating_sub(from)) };
+        let cumulative_now = Self::price_cumulative_at(&accumulator, now);
+        // The cumulative price at the start of the window is interpolated between the observations around it
+        let (next_block, next_cumulative) = next.map_or((now, cumulative_now), |next| (next.block, next.price_cumulative));
+        let span = blocks(observation.block, next_block);
+        let cumulative_start = if span == 0 {
+            observation.price_cumulative
+        } else {
+            let part = FixedU128::saturating_from_rational(blocks(observation.block, now - window), span);
+            observation.price_cumulative.saturating_add(next_cumulative.checked_sub(&observation.price_cumulative)?.saturating_mul(part))
+        };
+        cumulative_now
+            .checked_sub(&cumulative_start)?
+            .checked_div(&FixedU128::from(blocks(now - window, now)))
+    }
+
+    fn vwap(trading_pair: T::Hash, window: T::BlockNumber) -> Option<FixedU128> {
+        let (accumulator, observation, _) = Self::oracle_window(&trading_pair, window)?;
+        let quantity = accumulator.quantity_cumulative.checked_sub(&observation.quantity_cumulative)?;
+        if quantity == FixedU128::from(0) {
+            return None;
+        }
+        accumulator.volume_cumulative
+            .checked_sub(&observation.volume_cumulative)?
+            .checked_div(&quantity)
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // Reserves TradingPairReservationFee (defined in configuration trait) balance of SpendingAssetCurrency
+    fn reserve_balance_registration(origin: &<T as frame_system::Trait>::AccountId) -> bool {
+        pallet_generic_asset::Module::<T>::reserve(
+            &pallet_generic_asset::SpendingAssetIdProvider::<T>::asset_id(),
+            origin, <T as Trait>::TradingPairReservationFee::get()).is_ok()
+    }
+
+    // An asset exists in pallet_generic_asset once it has been issued
+    fn asset_exists(asset_id: &T::AssetId) -> bool {
+        !pallet_generic_asset::Module::<T>::total_issuance(asset_id).is_zero()
+    }
+
+    // Symbols are non empty and at most MAX_SYMBOL_LENGTH bytes
+    fn is_valid_symbol(symbol: &[u8]) -> bool {
+        !symbol.is_empty() && symbol.len() <= MAX_SYMBOL_LENGTH
+    }
+
+    // Checks that the asset exists and is whitelisted for trading
+    fn ensure_tradable_asset(asset_id: T::AssetId) -> Result<(), Error<T>> {
+        ensure!(Self::asset_exists(&asset_id), <Error<T>>::AssetDoesNotExist);
+        ensure!(Self::is_whitelisted_asset(&asset_id), <Error<T>>::AssetNotWhitelisted);
+        Ok(())
+    }
+
+    // Initializes a new Orderbook and stores it in the Orderbooks
+    fn create_order_book(quote_asset_id: T::AssetId, base_asset_id: T::AssetId, trading_pair_id: &T::Hash, tick_size: FixedU128, lot_size: FixedU128) {
+        let orderbook = Orderbook::new(base_asset_id, quote_asset_id, trading_pair_id.clone(), tick_size, lot_size);
+        <Orderbooks<T>>::insert(trading_pair_id, orderbook);
+        <TradingPairs<T>>::insert(base_asset_id, quote_asset_id, trading_pair_id);
+        <AsksLevels<T>>::insert(trading_pair_id, Vec::<FixedU128>::new());
+        <BidsLevels<T>>::insert(trading_pair_id, Vec::<FixedU128>::new());
+    }
+
+    // Prunes MarketInfo of blocks older than MarketDataRetention, starting from MarketInfoPruneCursor.
+    // If MarketDataRollupPeriod is non zero, pruned entries are first merged into MarketInfoRollup.
+    fn prune_market_data(now: T::BlockNumber) -> Weight {
+        let retention = T::MarketDataRetention::get();
+        if retention.is_zero() || now <= retention {
+            return 0;
+        }
+        // All blocks strictly before prune_until are outside the retention window
+        let prune_until = now.saturating_sub(retention);
+        let mut cursor = <MarketInfoPruneCursor<T>>::get();
+        if cursor >= prune_until {
+            return T::DbWeight::get().reads(1);
+        }
+
+        let rollup_period = T::MarketDataRollupPeriod::get();
+        let mut reads: Weight = 1;
+        let mut writes: Weight = 1;
+        let mut pruned_blocks: u32 = 0;
+
+        while cursor < prune_until && pruned_blocks < T::MaxMarketDataPrunedPerBlock::get() {
+            reads = reads.saturating_add(1);
+            writes = writes.saturating_add(1);
+            for trading_pair in <MarketInfoPairs<T>>::take(cursor).iter() {
+                reads = reads.saturating_add(1);
+                if let Some(market_data) = <MarketInfo<T>>::take(trading_pair, cursor) {
+                    writes = writes.saturating_add(1);
+                    if !rollup_period.is_zero() {
+                        let bucket = cursor / rollup_period;
+                        <MarketInfoRollup<T>>::mutate(trading_pair, bucket, |rollup| {
+                            match rollup {
+                                Some(rollup) => rollup.merge(&market_data),
+                                None => *rollup = Some(market_data),
+                            }
+                        });
+                        reads = reads.saturating_add(1);
+                        writes = writes.saturating_add(1);
+                    }
+                }
+            }
+            cursor = cursor.saturating_add(One::one());
+            pruned_blocks += 1;
+        }
+        <MarketInfoPruneCursor<T>>::put(cursor);
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+
+    // Adds the trading pair to MarketInfoPairs of the block before its first MarketInfo of the block is written
+    pub(crate) fn index_market_data(trading_pair: &T::Hash, block_number: T::BlockNumber) {
+        if !<MarketInfo<T>>::contains_key(trading_pair, block_number) {
+            <MarketInfoPairs<T>>::mutate(block_number, |trading_pairs| trading_pairs.push(*trading_pair));
+        }
+    }
+
+    // Number of ticker buckets needed to cover the TickerWindow including the current partial bucket
+    fn ticker_bucket_count() -> u32 {
+        let period = T::TickerBucketPeriod::get();
+        if period.is_zero() {
+            return 0;
+        }
+        let buckets: u32 = UniqueSaturatedInto::<u32>::unique_saturated_into(T::TickerWindow::get() / period);
+        buckets.saturating_add(1)
     }
 
-    pub fn get_orderbook(trading_pair: T::Hash) -> OrderbookRpc {
-        let orderbook = <Orderbooks<T>>::get(trading_pair);
-
-        orderbook.convert()
+    // Records an executed trade in the market statistics and the price oracle of the TradingPair
+    fn record_trade(trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) {
+        Self::update_ticker(trading_pair, price, quantity, trade_amount);
+        Self::update_price_accumulator(trading_pair, price, quantity, trade_amount);
     }
 
-    pub fn get_all_orderbook() -> Vec<OrderbookRpc> {
-        let orderbook:Vec<OrderbookRpc> = <Orderbooks<T>>::iter().map(|(_key, value)| value).map(|orderbook| orderbook.convert()).collect();
-        orderbook
+    // Brings the price accumulators of the TradingPair up to date with the trade and takes a
+    // snapshot in PriceObservations if OracleObservationPeriod has passed since the last one
+    fn update_price_accumulator(trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) {
+        let now = <frame_system::Module<T>>::block_number();
+        let max_observations = T::OracleMaxObservations::get();
+        let mut take_observation = false;
+        let accumulator = match <PriceAccumulators<T>>::get(trading_pair) {
+            Some(mut accumulator) => {
+                accumulator.price_cumulative = Self::price_cumulative_at(&accumulator, now);
+                accumulator.last_update = now;
+                accumulator.last_price = price;
+                accumulator.volume_cumulative = accumulator.volume_cumulative.saturating_add(trade_amount);
+                accumulator.quantity_cumulative = accumulator.quantity_cumulative.saturating_add(quantity);
+                if max_observations > 0 && now >= accumulator.last_observation.saturating_add(T::OracleObservationPeriod::get()) {
+                    accumulator.observation_index = (accumulator.observation_index + 1) % max_observations;
+                    accumulator.last_observation = now;
+                    take_observation = true;
+                }
+                accumulator
+            }
+            None => {
+                take_observation = max_observations > 0;
+                PriceAccumulator {
+                    price_cumulative: FixedU128::from(0),
+                    volume_cumulative: trade_amount,
+                    quantity_cumulative: quantity,
+                    last_price: price,
+                    last_update: now,
+                    observation_index: 0,
+                    last_observation: now,
+                }
+            }
+        };
+        if take_observation {
+            <PriceObservations<T>>::insert(trading_pair, accumulator.observation_index, PriceObservation {
+                block: now,
+                price_cumulative: accumulator.price_cumulative,
+                volume_cumulative: accumulator.volume_cumulative,
+                quantity_cumulative: accumulator.quantity_cumulative,
+            });
+        }
+        <PriceAccumulators<T>>::insert(trading_pair, accumulator);
     }
 
-    pub fn get_market_info(trading_pair: T::Hash,blocknum: u32) -> MarketDataRpc {
-        let blocknum = Self::u32_to_blocknum(blocknum);
-        let temp = <MarketInfo<T>>::get(trading_pair, blocknum);
-        temp.unwrap().convert()
+    // Extrapolates price_cumulative of the accumulator to the given block using its last_price
+    fn price_cumulative_at(accumulator: &PriceAccumulator<T::BlockNumber>, block: T::BlockNumber) -> FixedU128 {
+        let elapsed: u128 = UniqueSaturatedInto::<u128>::unique_saturated_into(block.saturating_sub(accumulator.last_update));
+        accumulator.price_cumulative.saturating_add(accumulator.last_price.saturating_mul(FixedU128::from(elapsed)))
     }
 
-    pub fn u32_to_blocknum(input: u32) -> T::BlockNumber {
-        input.into()
+    // Returns the current accumulator, the most recent observation taken at or before `window` blocks ago
+    // and the observation that follows it if there is one
+    fn oracle_window(trading_pair: &T::Hash, window: T::BlockNumber)
+        -> Option<(PriceAccumulator<T::BlockNumber>, PriceObservation<T::BlockNumber>, Option<PriceObservation<T::BlockNumber>>)> {
+        let now = <frame_system::Module<T>>::block_number();
+        if window.is_zero() || now < window {
+            return None;
+        }
+        let accumulator = <PriceAccumulators<T>>::get(trading_pair)?;
+        let target = now - window;
+        let max_observations = T::OracleMaxObservations::get();
+        if max_observations == 0 {
+            return None;
+        }
+        // Observations are ordered oldest first starting right after observation_index, unless the
+        // ring buffer is not full yet in which case the oldest one is in slot 0.
+        let next_slot = (accumulator.observation_index + 1) % max_observations;
+        let (oldest_slot, count) = if <PriceObservations<T>>::contains_key(trading_pair, next_slot) {
+            (next_slot, max_observations)
+        } else {
+            (0, accumulator.observation_index + 1)
+        };
+        let slot_of = |position: u32| (oldest_slot + position) % max_observations;
+
+        // Binary search for the latest observation taken at or before target
+        let mut found: Option<PriceObservation<T::BlockNumber>> = None;
+        let (mut low, mut high) = (0u32, count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match <PriceObservations<T>>::get(trading_pair, slot_of(mid)) {
+                Some(observation) if observation.block <= target => {
+                    found = Some(observation);
+                    low = mid + 1;
+                }
+                _ => high = mid,
+            }
+        }
+        // low is now the position right after the observation found
+        let next = if low < count { <PriceObservations<T>>::get(trading_pair, slot_of(low)) } else { None };
+        Some((accumulator, found?, next))
     }
-}
 
-impl<T: Trait> Module<T> {
-    // Reserves TradingPairReservationFee (defined in configuration trait) balance of SpendingAssetCurrency
-    fn reserve_balance_registration(origin: &<T as frame_system::Trait>::AccountId) -> bool {
-        pallet_generic_asset::Module::<T>::reserve(
-            &pallet_generic_asset::SpendingAssetIdProvider::<T>::asset_id(),
-            origin, <T as Trait>::TradingPairReservationFee::get()).is_ok()
+    // Records an executed trade in the current ticker bucket of the TradingPair
+    fn update_ticker(trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) {
+        <LastTradePrice<T>>::insert(trading_pair, price);
+        let bucket_count = Self::ticker_bucket_count();
+        if bucket_count == 0 {
+            return;
+        }
+        let bucket = <frame_system::Module<T>>::block_number() / T::TickerBucketPeriod::get();
+        let slot = UniqueSaturatedInto::<u32>::unique_saturated_into(bucket % T::BlockNumber::from(bucket_count));
+        <TickerBuckets<T>>::mutate(trading_pair, slot, |ticker_bucket| {
+            match ticker_bucket {
+                Some(current_bucket) if current_bucket.bucket == bucket => current_bucket.add_trade(price, quantity, trade_amount),
+                // Either the slot is empty or it holds a bucket that has left the TickerWindow
+                _ => *ticker_bucket = Some(TickerBucket::new(bucket, price, quantity, trade_amount)),
+            }
+        });
     }
 
-    // Initializes a new Orderbook and stores it in the Orderbooks
-    fn create_order_book(quote_asset_id: T::AssetId, base_asset_id: T::AssetId, trading_pair_id: &T::Hash) {
-        let orderbook = Orderbook::new(base_asset_id, quote_asset_id, trading_pair_id.clone());
-        <Orderbooks<T>>::insert(trading_pair_id, orderbook);
-        <AsksLevels<T>>::insert(trading_pair_id, Vec::<FixedU128>::new());
-        <BidsLevels<T>>::insert(trading_pair_id, Vec::<FixedU128>::new());
+    // Aggregates the ticker buckets of the TradingPair that are inside the TickerWindow
+    fn compute_ticker(trading_pair: T::Hash) -> Ticker<T> {
+        let orderbook = <Orderbooks<T>>::get(&trading_pair);
+        let last_price = <LastTradePrice<T>>::get(&trading_pair);
+        let mut ticker = Ticker {
+            trading_pair,
+            last_price,
+            open_price: last_price,
+            high: FixedU128::from(0),
+            low: FixedU128::from(0),
+            base_volume: FixedU128::from(0),
+            quote_volume: FixedU128::from(0),
+            best_bid_price: orderbook.best_bid_price,
+            best_ask_price: orderbook.best_ask_price,
+            trade_count: 0,
+        };
+        let bucket_count = Self::ticker_bucket_count();
+        if bucket_count == 0 {
+            return ticker;
+        }
+        let current_bucket = <frame_system::Module<T>>::block_number() / T::TickerBucketPeriod::get();
+        let first_bucket = current_bucket.saturating_sub(T::BlockNumber::from(bucket_count - 1));
+        let mut ticker_buckets: Vec<TickerBucket<T::BlockNumber>> = <TickerBuckets<T>>::iter_prefix_values(&trading_pair)
+            .filter(|ticker_bucket| ticker_bucket.bucket >= first_bucket && ticker_bucket.bucket <= current_bucket)
+            .collect();
+        ticker_buckets.sort_by(|a, b| a.bucket.cmp(&b.bucket));
+
+        if let Some(oldest_bucket) = ticker_buckets.first() {
+            ticker.open_price = oldest_bucket.open;
+            ticker.low = oldest_bucket.low;
+        }
+        for ticker_bucket in ticker_buckets.iter() {
+            if ticker.high < ticker_bucket.high {
+                ticker.high = ticker_bucket.high
+            }
+            if ticker.low > ticker_bucket.low {
+                ticker.low = ticker_bucket.low
+            }
+            ticker.base_volume = ticker.base_volume.saturating_add(ticker_bucket.base_volume);
+            ticker.quote_volume = ticker.quote_volume.saturating_add(ticker_bucket.quote_volume);
+            ticker.trade_count = ticker.trade_count.saturating_add(ticker_bucket.trade_count);
+        }
+        ticker
     }
 
     // Creates a TradingPairID from both Asset IDs.
@@ -481,12 +1951,32 @@ impl<T: Trait> Module<T> {
         (quote_asset_id, base_asset_id).using_encoded(<T as frame_system::Trait>::Hashing::hash)
     }
 
-    // Submits an order for execution
+    // Id the next order submitted with these parameters is given
+    fn next_order_id(trader: &T::AccountId, order_type: &OrderType, trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128) -> T::Hash {
+        let nonce = Nonce::get(); // To get some kind non user controllable randomness to order id
+        (*trading_pair, trader.clone(), price, quantity, order_type.clone(), nonce)
+            .using_encoded(<T as frame_system::Trait>::Hashing::hash)
+    }
+
+    // Submits an order for execution, nothing of it is kept if a match is refused. Returns the amount
+    // of the asset bought that was paid to the trader right away by the pool and by market order fills,
+    // matches between limit orders are only credited when the block is settled.
+    #[transactional]
     fn execute_order(trader: T::AccountId,
                      order_type: OrderType,
                      trading_pair: T::Hash,
                      price: FixedU128,
-                     quantity: FixedU128) -> Result<(), Error<T>> {
+                     quantity: FixedU128) -> Result<FixedU128, Error<T>> {
+        Self::execute_order_without_transaction(trader, order_type, trading_pair, price, quantity)
+    }
+
+    // Body of execute_order without the storage transaction, for genesis where the externalities have no
+    // transaction support. A failed order leaves its partial writes behind, callers must not continue with them.
+    fn execute_order_without_transaction(trader: T::AccountId,
+                                         order_type: OrderType,
+                                         trading_pair: T::Hash,
+                                         price: FixedU128,
+                                         quantity: FixedU128) -> Result<FixedU128, Error<T>> {
         let mut current_order = Order {
             id: T::Hash::default(), // let's do the hashing after the checks.
             trading_pair,
@@ -494,51 +1984,70 @@ impl<T: Trait> Module<T> {
             price,
             quantity,
             order_type,
+            reserved: FixedU128::from(0),
         };
 
         match Self::basic_order_checks(&current_order) {
             Ok(mut orderbook) => {
-                let nonce = Nonce::get(); // To get some kind non user controllable randomness to order id
-                current_order.id = (trading_pair, current_order.trader.clone(), price, quantity, current_order.order_type.clone(), nonce)
-                    .using_encoded(<T as frame_system::Trait>::Hashing::hash);
-                Nonce::put(nonce + 1); // TODO: It might overflow after a long time.
+                // check_order has reserved the full cost of a limit order, track it on the order itself
+                let required_reserve = Self::required_reserve(&current_order).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
+                current_order.reserved = Self::round_to_asset_precision(&Self::reserved_asset_id(&current_order.order_type, &orderbook), required_reserve)
+                    .ok_or(<Error<T>>::InternalErrorU128Balance.into())?;
+                current_order.id = Self::next_order_id(&current_order.trader, &current_order.order_type, &trading_pair, price, quantity);
+                Nonce::mutate(|nonce| *nonce += 1); // TODO: It might overflow after a long time.
+
+                // Limit orders of off-chain matched pairs wait in the queue for the off-chain worker
+                if Self::is_offchain_matched(&trading_pair) &&
+                    (current_order.order_type == OrderType::BidLimit || current_order.order_type == OrderType::AskLimit) {
+                    <PendingOrders<T>>::mutate(&trading_pair, |pending| pending.push(current_order.clone()));
+                    Self::record_order(&current_order, price, quantity);
+                    Self::deposit_event(RawEvent::OrderQueued(current_order.id,
+                                                              current_order.trading_pair,
+                                                              current_order.order_type,
+                                                              current_order.price,
+                                                              current_order.quantity,
+                                                              current_order.trader));
+                    return Ok(FixedU128::from(0));
+                }
 
-                match current_order.order_type {
-                    OrderType::AskMarket if orderbook.best_bid_price != FixedU128::from(0) => {
-                        Self::consume_order(&mut current_order, &mut orderbook)?;
+                // The pool and the book fill the order level by level: before every level of the book the pool
+                // fills what it can sell cheaper than that level
+                let mut received = FixedU128::from(0);
+                loop {
+                    let pool_bound = Self::pool_price_bound(&current_order, &orderbook);
+                    received = received.saturating_add(Self::fill_from_pool(&mut current_order, &orderbook, pool_bound)?);
+                    if !Self::crosses_book(&current_order, &orderbook) {
+                        break;
                     }
-
-                    OrderType::BidMarket if orderbook.best_ask_price != FixedU128::from(0) => {
-                        Self::consume_order(&mut current_order, &mut orderbook)?;
+                    let remaining_before = (current_order.price, current_order.quantity);
+                    received = received.saturating_add(Self::consume_best_level(&mut current_order, &mut orderbook)?);
+                    if (current_order.price, current_order.quantity) == remaining_before {
+                        break;
                     }
+                }
 
-                    OrderType::AskLimit | OrderType::BidLimit => {
-                        // Check if current can consume orders present in the system
-                        if (current_order.order_type == OrderType::BidLimit &&
-                            current_order.price >= orderbook.best_ask_price &&
-                            orderbook.best_ask_price != FixedU128::from(0)) ||
-                            (current_order.order_type == OrderType::AskLimit &&
-                                current_order.price <= orderbook.best_bid_price &&
-                                orderbook.best_bid_price != FixedU128::from(0)) {
-
-                            // current_order can consume i.e. Market Taking order
-                            Self::consume_order(&mut current_order, &mut orderbook)?;
-
+                // What the book couldn't fill goes to the pool, limited only by the price of limit orders
+                let limit_bound = match current_order.order_type {
+                    OrderType::BidLimit | OrderType::AskLimit => Some(current_order.price),
+                    OrderType::BidMarket | OrderType::AskMarket => None,
+                };
+                received = received.saturating_add(Self::fill_from_pool(&mut current_order, &orderbook, limit_bound)?);
 
-                            // If current_order has quantity remaining to fulfil, insert it
-                            if current_order.quantity > FixedU128::from(0) {
-                                // Insert the remaining order in the order book
-                                Self::insert_order(&current_order, &mut orderbook)?;
-                            }
-                        } else {
-                            // Current Order cannot be consumed i.e. Market Making order
-                            // Insert the order in the order book
-                            Self::insert_order(&current_order, &mut orderbook)?;
-                        }
+                match current_order.order_type {
+                    // If current_order has quantity remaining to fulfil, insert it i.e. Market Making order
+                    OrderType::AskLimit | OrderType::BidLimit if current_order.quantity > FixedU128::from(0) => {
+                        Self::insert_order(&current_order, &mut orderbook)?;
                     }
                     _ => {}
                 }
+                // A filled limit order keeps nothing reserved, release what rounding may have left behind
+                if current_order.quantity == FixedU128::from(0) {
+                    let asset_id = Self::reserved_asset_id(&current_order.order_type, &orderbook);
+                    let leftover = current_order.reserved;
+                    Self::release_reserve(&mut current_order, asset_id, leftover)?;
+                }
                 <Orderbooks<T>>::insert(&current_order.trading_pair, orderbook);
+                Self::record_order(&current_order, price, quantity);
                 match current_order.order_type {
                     OrderType::BidLimit | OrderType::AskLimit if current_order.quantity > FixedU128::from(0) => {
                         Self::deposit_event(RawEvent::NewLimitOrder(current_order.id,
@@ -592,12 +2101,575 @@ impl<T: Trait> Module<T> {
                         // This branch will not execute
                     }
                 }
-                Ok(())
+                Ok(received)
             }
             Err(err_value) => Err(err_value),
         }
     }
 
+    // Whether the order can be matched against the best price of the other side of the book
+    fn crosses_book(order: &Order<T>, orderbook: &Orderbook<T>) -> bool {
+        match order.order_type {
+            OrderType::AskMarket => orderbook.best_bid_price != FixedU128::from(0) && order.quantity > FixedU128::from(0),
+            OrderType::BidMarket => orderbook.best_ask_price != FixedU128::from(0) && order.price > FixedU128::from(0),
+            OrderType::BidLimit => orderbook.best_ask_price != FixedU128::from(0) && order.quantity > FixedU128::from(0) &&
+                order.price >= orderbook.best_ask_price,
+            OrderType::AskLimit => orderbook.best_bid_price != FixedU128::from(0) && order.quantity > FixedU128::from(0) &&
+                order.price <= orderbook.best_bid_price,
+        }
+    }
+
+    // Matches the order against the best price level of the other side of the book only, the rest of
+    // the order is held back so consume_order never moves on to the next level
+    fn consume_best_level(order: &mut Order<T>, orderbook: &mut Orderbook<T>) -> Result<FixedU128, Error<T>> {
+        let level_price = match order.order_type {
+            OrderType::BidLimit | OrderType::BidMarket => orderbook.best_ask_price,
+            OrderType::AskLimit | OrderType::AskMarket => orderbook.best_bid_price,
+        };
+        // A BidMarket order spends price of the base asset, the others fill quantity of the quote asset
+        let depth = <PriceLevels<T>>::get(&order.trading_pair, level_price).orders.iter().fold(FixedU128::from(0), |total, counter_order| {
+            match order.order_type {
+                OrderType::BidMarket => total.saturating_add(level_price.saturating_mul(counter_order.quantity)),
+                _ => total.saturating_add(counter_order.quantity),
+            }
+        });
+        let received = if order.order_type == OrderType::BidMarket {
+            let held_back = order.price.saturating_sub(depth);
+            order.price = order.price.saturating_sub(held_back);
+            let received = Self::consume_order(order, orderbook)?;
+            order.price = order.price.saturating_add(held_back);
+            received
+        } else {
+            let held_back = order.quantity.saturating_sub(depth);
+            order.quantity = order.quantity.saturating_sub(held_back);
+            let received = Self::consume_order(order, orderbook)?;
+            order.quantity = order.quantity.saturating_add(held_back);
+            received
+        };
+        Ok(received)
+    }
+
+    // Executes the market order of every hop of the swap, all storage changes are reverted if any hop
+    // fails or the final amount is below min_amount_out
+    #[transactional]
+    fn execute_swap(trader: T::AccountId, asset_in: T::AssetId, path: Vec<T::Hash>, amount_in: FixedU128, min_amount_out: FixedU128) -> Result<(), Error<T>> {
+        ensure!(!path.is_empty() && path.len() <= MAX_SWAP_PATH_LENGTH, <Error<T>>::InvalidSwapPath);
+        let mut asset_id = asset_in;
+        let mut amount = amount_in;
+        for trading_pair in path.iter() {
+            ensure!(<Orderbooks<T>>::contains_key(trading_pair), <Error<T>>::InvalidTradingPair);
+            let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+            // Holding the quote asset means selling it, holding the base asset means spending it on the quote asset
+            let (order_type, asset_out, price, quantity) = if asset_id == orderbook.quote_asset_id {
+                (OrderType::AskMarket, orderbook.base_asset_id, FixedU128::from(0), Self::round_down_to_lot(amount, orderbook.lot_size))
+            } else if asset_id == orderbook.base_asset_id {
+                (OrderType::BidMarket, orderbook.quote_asset_id, amount, FixedU128::from(0))
+            } else {
+                return Err(<Error<T>>::InvalidSwapPath);
+            };
+            amount = Self::execute_order(trader.clone(), order_type, *trading_pair, price, quantity)?;
+            asset_id = asset_out;
+        }
+        ensure!(amount >= min_amount_out, <Error<T>>::SwapOutputTooLow);
+        Self::deposit_event(RawEvent::SwapExecuted(trader, asset_in, amount_in, asset_id, amount));
+        Ok(())
+    }
+
+    // Largest multiple of lot_size that is not above quantity
+    fn round_down_to_lot(quantity: FixedU128, lot_size: FixedU128) -> FixedU128 {
+        if lot_size == FixedU128::from(0) {
+            return quantity;
+        }
+        match quantity.checked_div(&lot_size) {
+            Some(lots) => lots.trunc().saturating_mul(lot_size),
+            None => FixedU128::from(0),
+        }
+    }
+
+    // Asset and amount received for selling amount of asset_id with a market order on the orderbook
+    // at its current depth, None if the asset isn't traded on it. Like execute_order the pool fills what
+    // it sells cheaper than every level of the book before the level, and whatever the book leaves.
+    fn quote_market_hop(orderbook: &Orderbook<T>, asset_id: &T::AssetId, amount: FixedU128) -> Option<(T::AssetId, FixedU128)> {
+        let mut reserves = Self::pool_reserves(orderbook);
+        let mut received = FixedU128::from(0);
+        if *asset_id == orderbook.quote_asset_id {
+            // AskMarket fills the bids starting from the highest price
+            let mut remaining = Self::round_down_to_lot(amount, orderbook.lot_size);
+            for price in <BidsLevels<T>>::get(&orderbook.trading_pair).iter().rev() {
+                received = received.checked_add(&Self::quote_pool_fill(orderbook, &OrderType::AskMarket, &mut FixedU128::from(0), &mut remaining, &mut reserves, Some(*price))?)?;
+                for order in <PriceLevels<T>>::get(&orderbook.trading_pair, price).orders.iter() {
+                    if remaining == FixedU128::from(0) {
+                        return Some((orderbook.base_asset_id, received));
+                    }
+                    let filled = remaining.min(order.quantity);
+                    received = received.checked_add(&price.checked_mul(&filled)?)?;
+                    remaining = remaining.saturating_sub(filled);
+                }
+            }
+            received = received.checked_add(&Self::quote_pool_fill(orderbook, &OrderType::AskMarket, &mut FixedU128::from(0), &mut remaining, &mut reserves, None)?)?;
+            Some((orderbook.base_asset_id, received))
+        } else if *asset_id == orderbook.base_asset_id {
+            // BidMarket spends the base asset on the asks starting from the lowest price
+            let mut remaining = amount;
+            for price in <AsksLevels<T>>::get(&orderbook.trading_pair).iter() {
+                received = received.checked_add(&Self::quote_pool_fill(orderbook, &OrderType::BidMarket, &mut remaining, &mut FixedU128::from(0), &mut reserves, Some(*price))?)?;
+                for order in <PriceLevels<T>>::get(&orderbook.trading_pair, price).orders.iter() {
+                    if remaining == FixedU128::from(0) {
+                        return Some((orderbook.quote_asset_id, received));
+                    }
+                    let quantity = remaining.checked_div(price)?;
+                    if quantity <= order.quantity {
+                        received = received.checked_add(&quantity)?;
+                        remaining = FixedU128::from(0);
+                    } else {
+                        received = received.checked_add(&order.quantity)?;
+                        remaining = remaining.saturating_sub(price.checked_mul(&order.quantity)?);
+                    }
+                }
+            }
+            received = received.checked_add(&Self::quote_pool_fill(orderbook, &OrderType::BidMarket, &mut remaining, &mut FixedU128::from(0), &mut reserves, None)?)?;
+            Some((orderbook.quote_asset_id, received))
+        } else {
+            None
+        }
+    }
+
+    // Amount the pool pays to the market order of a quote up to price_bound, taking what it fills from the
+    // order and moving the reserves the way fill_from_pool does. Referral fees are left out of the reserves.
+    fn quote_pool_fill(orderbook: &Orderbook<T>, order_type: &OrderType, price: &mut FixedU128, quantity: &mut FixedU128,
+                       reserves: &mut Option<(FixedU128, FixedU128)>, price_bound: Option<FixedU128>) -> Option<FixedU128> {
+        let (base_reserve, quote_reserve) = match *reserves {
+            Some(reserves) => reserves,
+            None => return Some(FixedU128::from(0)),
+        };
+        let (base_amount, quote_amount) = match Self::pool_fill_amounts(order_type, *price, *quantity, orderbook.lot_size, base_reserve, quote_reserve, price_bound).ok()? {
+            Some(amounts) => amounts,
+            None => return Some(FixedU128::from(0)),
+        };
+        match order_type {
+            OrderType::BidLimit | OrderType::BidMarket => {
+                *price = price.checked_sub(&base_amount)?;
+                *reserves = Some((base_reserve.checked_add(&base_amount)?, quote_reserve.checked_sub(&quote_amount)?));
+                Some(quote_amount)
+            }
+            OrderType::AskLimit | OrderType::AskMarket => {
+                *quantity = quantity.checked_sub(&quote_amount)?;
+                *reserves = Some((base_reserve.checked_sub(&base_amount)?, quote_reserve.checked_add(&quote_amount)?));
+                Some(base_amount)
+            }
+        }
+    }
+
+    // Path of at most MAX_SWAP_PATH_LENGTH TradingPairs that returns the most of asset_out for amount_in of asset_in
+    fn best_swap_path(asset_in: T::AssetId, asset_out: T::AssetId, amount_in: FixedU128) -> Option<(Vec<T::Hash>, FixedU128)> {
+        // The index only holds the asset ids, an Orderbook is read when a hop through it is quoted
+        let trading_pairs: Vec<(T::AssetId, T::AssetId, T::Hash)> = <TradingPairs<T>>::iter().collect();
+        let mut best: Option<(Vec<T::Hash>, FixedU128)> = None;
+        let mut candidates = MAX_SWAP_PATH_CANDIDATES;
+        Self::search_swap_paths(&trading_pairs, asset_in, &asset_out, amount_in, &mut Vec::new(), &mut best, &mut candidates);
+        best
+    }
+
+    // Depth first search over the TradingPairs that trade asset_id, quoting every hop against the current
+    // depth until the candidates are used up
+    fn search_swap_paths(trading_pairs: &[(T::AssetId, T::AssetId, T::Hash)], asset_id: T::AssetId, asset_out: &T::AssetId, amount: FixedU128,
+                         path: &mut Vec<T::Hash>, best: &mut Option<(Vec<T::Hash>, FixedU128)>, candidates: &mut usize) {
+        if path.len() >= MAX_SWAP_PATH_LENGTH {
+            return;
+        }
+        for (base_asset_id, quote_asset_id, trading_pair) in trading_pairs.iter() {
+            if (*base_asset_id != asset_id && *quote_asset_id != asset_id) || path.contains(trading_pair) {
+                continue;
+            }
+            if *candidates == 0 {
+                return;
+            }
+            *candidates -= 1;
+            let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+            match Self::quote_market_hop(&orderbook, &asset_id, amount) {
+                Some((next_asset_id, received)) if received > FixedU128::from(0) => {
+                    path.push(orderbook.trading_pair);
+                    if next_asset_id == *asset_out {
+                        if best.as_ref().map_or(true, |(_, best_amount)| received > *best_amount) {
+                            *best = Some((path.clone(), received));
+                        }
+                    } else {
+                        Self::search_swap_paths(trading_pairs, next_asset_id, asset_out, received, path, best, candidates);
+                    }
+                    path.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Account holding the reserves of the liquidity pool of the TradingPair
+    pub fn pool_account(trading_pair: &T::Hash) -> T::AccountId {
+        POOL_MODULE_ID.into_sub_account(trading_pair)
+    }
+
+    // Base and quote reserves of the liquidity pool of the orderbook, None if it has no pool or either reserve is empty
+    fn pool_reserves(orderbook: &Orderbook<T>) -> Option<(FixedU128, FixedU128)> {
+        Self::pool_share_asset(&orderbook.trading_pair)?;
+        let pool = Self::pool_account(&orderbook.trading_pair);
+        let base_reserve = Self::convert_balance_to_fixed_u128(&orderbook.base_asset_id,
+                                                               pallet_generic_asset::Module::<T>::free_balance(&orderbook.base_asset_id, &pool))?;
+        let quote_reserve = Self::convert_balance_to_fixed_u128(&orderbook.quote_asset_id,
+                                                                pallet_generic_asset::Module::<T>::free_balance(&orderbook.quote_asset_id, &pool))?;
+        if base_reserve == FixedU128::from(0) || quote_reserve == FixedU128::from(0) {
+            return None;
+        }
+        Some((base_reserve, quote_reserve))
+    }
+
+    // Total pool shares issued for the share asset
+    fn pool_total_shares(share_asset_id: &T::AssetId) -> Option<FixedU128> {
+        Self::convert_balance_to_fixed_u128(share_asset_id, pallet_generic_asset::Module::<T>::total_issuance(share_asset_id))
+    }
+
+    // Creates the share asset of a new liquidity pool, only the pool account can mint and burn it
+    fn create_pool_share_asset(trading_pair: &T::Hash, base_asset_id: &T::AssetId) -> Result<T::AssetId, Error<T>> {
+        let pool = Self::pool_account(trading_pair);
+        let share_asset_id = pallet_generic_asset::Module::<T>::next_asset_id();
+        let options = AssetOptions {
+            initial_issuance: Zero::zero(),
+            permissions: PermissionLatest {
+                update: Owner::Address(pool.clone()),
+                mint: Owner::Address(pool.clone()),
+                burn: Owner::Address(pool.clone()),
+            },
+        };
+        pallet_generic_asset::Module::<T>::create_asset(None, Some(pool), options).map_err(|_| <Error<T>>::PoolShareAssetError)?;
+        // Shares are minted one for one with the first base amount so they get the decimals of the base asset
+        <AssetDecimals<T>>::insert(&share_asset_id, Self::decimals_of(base_asset_id));
+        <PoolShareAssets<T>>::insert(trading_pair, share_asset_id);
+        Ok(share_asset_id)
+    }
+
+    #[transactional]
+    fn do_add_liquidity(provider: T::AccountId, trading_pair: T::Hash, base_amount: FixedU128, max_quote_amount: FixedU128, min_shares: FixedU128) -> Result<(), Error<T>> {
+        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+        ensure!(base_amount > FixedU128::from(0) && max_quote_amount > FixedU128::from(0), <Error<T>>::InvalidLiquidityAmount);
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        Self::ensure_tradable_asset(orderbook.base_asset_id)?;
+        Self::ensure_tradable_asset(orderbook.quote_asset_id)?;
+
+        let share_asset_id = match Self::pool_share_asset(&trading_pair) {
+            Some(share_asset_id) => share_asset_id,
+            None => Self::create_pool_share_asset(&trading_pair, &orderbook.base_asset_id)?,
+        };
+        let total_shares = Self::pool_total_shares(&share_asset_id).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        let (quote_amount, shares) = match Self::pool_reserves(&orderbook) {
+            Some((base_reserve, quote_reserve)) if total_shares > FixedU128::from(0) => {
+                // Liquidity is added at the pool's current ratio
+                let ratio = base_amount.checked_div(&base_reserve).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+                let quote_amount = quote_reserve.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                ensure!(quote_amount <= max_quote_amount, <Error<T>>::PoolRatioMismatch);
+                (quote_amount, total_shares.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?)
+            }
+            // An empty pool starts over at the price given by the provider
+            _ => (max_quote_amount, base_amount),
+        };
+        ensure!(shares > FixedU128::from(0) && shares >= min_shares, <Error<T>>::InsufficientPoolShares);
+
+        let pool = Self::pool_account(&trading_pair);
+        Self::transfer_asset_market(orderbook.base_asset_id, base_amount, &provider, &pool)?;
+        Self::transfer_asset_market(orderbook.quote_asset_id, quote_amount, &provider, &pool)?;
+        let shares_balance = Self::convert_fixed_u128_to_balance(&share_asset_id, shares).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        pallet_generic_asset::Module::<T>::mint_free(&share_asset_id, &pool, &provider, &shares_balance).map_err(|_| <Error<T>>::PoolShareAssetError)?;
+        Self::deposit_event(RawEvent::LiquidityAdded(trading_pair, provider, base_amount, quote_amount, shares));
+        Ok(())
+    }
+
+    #[transactional]
+    fn do_remove_liquidity(provider: T::AccountId, trading_pair: T::Hash, shares: FixedU128, min_base_amount: FixedU128, min_quote_amount: FixedU128) -> Result<(), Error<T>> {
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let share_asset_id = Self::pool_share_asset(&trading_pair).ok_or(<Error<T>>::NoLiquidityPool)?;
+        let (base_reserve, quote_reserve) = Self::pool_reserves(&orderbook).ok_or(<Error<T>>::NoLiquidityPool)?;
+        let total_shares = Self::pool_total_shares(&share_asset_id).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        ensure!(shares > FixedU128::from(0) && shares <= total_shares, <Error<T>>::InsufficientPoolShares);
+
+        let ratio = shares.checked_div(&total_shares).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+        let base_amount = base_reserve.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+        let quote_amount = quote_reserve.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+        ensure!(base_amount >= min_base_amount && quote_amount >= min_quote_amount, <Error<T>>::SwapOutputTooLow);
+
+        let pool = Self::pool_account(&trading_pair);
+        let shares_balance = Self::convert_fixed_u128_to_balance(&share_asset_id, shares).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        pallet_generic_asset::Module::<T>::burn_free(&share_asset_id, &pool, &provider, &shares_balance).map_err(|_| <Error<T>>::InsufficientPoolShares)?;
+        Self::transfer_asset_market(orderbook.base_asset_id, base_amount, &pool, &provider)?;
+        Self::transfer_asset_market(orderbook.quote_asset_id, quote_amount, &pool, &provider)?;
+        Self::deposit_event(RawEvent::LiquidityRemoved(trading_pair, provider, base_amount, quote_amount, shares));
+        Ok(())
+    }
+
+    // One minus PoolSwapFee, the part of an amount paid into the pool that is swapped
+    fn pool_fee_complement() -> FixedU128 {
+        FixedU128::from(1).saturating_sub(FixedU128::saturating_from_rational(T::PoolSwapFee::get().deconstruct(), 1_000_000u32))
+    }
+
+    // Amount the constant product pool pays out of reserve_out for amount_in paid into reserve_in
+    fn pool_amount_out(reserve_in: FixedU128, reserve_out: FixedU128, amount_in: FixedU128) -> Option<FixedU128> {
+        let amount_in_after_fee = amount_in.checked_mul(&Self::pool_fee_complement())?;
+        let denominator = reserve_in.checked_add(&amount_in_after_fee)?;
+        reserve_out.checked_div(&denominator)?.checked_mul(&amount_in_after_fee)
+    }
+
+    // Amount to pay into reserve_in so that the pool pays amount_out out of reserve_out
+    fn pool_amount_in(reserve_in: FixedU128, reserve_out: FixedU128, amount_out: FixedU128) -> Option<FixedU128> {
+        let remaining_out = reserve_out.checked_sub(&amount_out)?;
+        if remaining_out == FixedU128::from(0) {
+            return None;
+        }
+        let amount_in_after_fee = reserve_in.checked_div(&remaining_out)?.checked_mul(&amount_out)?;
+        amount_in_after_fee.checked_div(&Self::pool_fee_complement())
+    }
+
+    // Amount to pay into reserve_in until reserve_in / reserve_out reaches target_ratio, zero if it already did
+    fn pool_amount_in_to_ratio(reserve_in: FixedU128, reserve_out: FixedU128, target_ratio: FixedU128) -> Option<FixedU128> {
+        // The product of the reserves stays constant so the target reserve_in is sqrt(reserve_in * reserve_out * target_ratio)
+        let one = U256::from(FixedU128::accuracy());
+        let product = U256::from(reserve_in.into_inner()).checked_mul(U256::from(reserve_out.into_inner()))? / one;
+        let scaled = product.checked_mul(U256::from(target_ratio.into_inner()))? / one;
+        let target_reserve_in = scaled.checked_mul(one)?.integer_sqrt();
+        if target_reserve_in.bits() > 128 {
+            return None;
+        }
+        let target_reserve_in = FixedU128::from_inner(target_reserve_in.low_u128());
+        target_reserve_in.saturating_sub(reserve_in).checked_div(&Self::pool_fee_complement())
+    }
+
+    // Price the pool may move to before the book is cheaper: the best price of the other side of the book
+    // or the order's own limit price, whichever is reached first. None leaves the pool unbounded.
+    fn pool_price_bound(order: &Order<T>, orderbook: &Orderbook<T>) -> Option<FixedU128> {
+        match order.order_type {
+            OrderType::BidLimit if orderbook.best_ask_price != FixedU128::from(0) => Some(order.price.min(orderbook.best_ask_price)),
+            OrderType::AskLimit if orderbook.best_bid_price != FixedU128::from(0) => Some(order.price.max(orderbook.best_bid_price)),
+            OrderType::BidLimit | OrderType::AskLimit => Some(order.price),
+            OrderType::BidMarket if orderbook.best_ask_price != FixedU128::from(0) => Some(orderbook.best_ask_price),
+            OrderType::AskMarket if orderbook.best_bid_price != FixedU128::from(0) => Some(orderbook.best_bid_price),
+            OrderType::BidMarket | OrderType::AskMarket => None,
+        }
+    }
+
+    // Base and quote amounts the pool with these reserves exchanges with the order without moving its price
+    // base_reserve / quote_reserve beyond price_bound, None if it exchanges nothing
+    fn pool_fill_amounts(order_type: &OrderType, price: FixedU128, quantity: FixedU128, lot_size: FixedU128,
+                         base_reserve: FixedU128, quote_reserve: FixedU128, price_bound: Option<FixedU128>) -> Result<Option<(FixedU128, FixedU128)>, Error<T>> {
+        let pool_price = base_reserve.checked_div(&quote_reserve).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+        match order_type {
+            OrderType::BidLimit | OrderType::BidMarket => {
+                // The pool sells the quote asset and its price rises
+                let mut base_in = match price_bound {
+                    Some(bound) if bound <= pool_price => return Ok(None),
+                    Some(bound) => Self::pool_amount_in_to_ratio(base_reserve, quote_reserve, bound).ok_or(<Error<T>>::MulUnderflowOrOverflow)?,
+                    None => price,
+                };
+                if *order_type == OrderType::BidMarket {
+                    base_in = base_in.min(price);
+                }
+                let mut quote_out = Self::pool_amount_out(base_reserve, quote_reserve, base_in).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                if *order_type == OrderType::BidLimit {
+                    // A resting remainder must stay a multiple of the lot size
+                    let capped_quote_out = Self::round_down_to_lot(quote_out.min(quantity), lot_size);
+                    if capped_quote_out < quote_out {
+                        quote_out = capped_quote_out;
+                        base_in = Self::pool_amount_in(base_reserve, quote_reserve, quote_out).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                    }
+                }
+                if base_in == FixedU128::from(0) || quote_out == FixedU128::from(0) {
+                    return Ok(None);
+                }
+                Ok(Some((base_in, quote_out)))
+            }
+            OrderType::AskLimit | OrderType::AskMarket => {
+                // The pool buys the quote asset and its price falls
+                let quote_in = match price_bound {
+                    Some(bound) if bound >= pool_price || bound == FixedU128::from(0) => return Ok(None),
+                    Some(bound) => {
+                        let target_ratio = FixedU128::from(1).checked_div(&bound).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+                        Self::pool_amount_in_to_ratio(quote_reserve, base_reserve, target_ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?
+                    }
+                    None => quantity,
+                };
+                let quote_in = Self::round_down_to_lot(quote_in.min(quantity), lot_size);
+                let base_out = Self::pool_amount_out(quote_reserve, base_reserve, quote_in).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                if quote_in == FixedU128::from(0) || base_out == FixedU128::from(0) {
+                    return Ok(None);
+                }
+                Ok(Some((base_out, quote_in)))
+            }
+        }
+    }
+
+    // Fills as much of the order from the liquidity pool as possible without moving the pool's price
+    // base_reserve / quote_reserve beyond price_bound, returns the amount the pool paid to the trader
+    fn fill_from_pool(order: &mut Order<T>, orderbook: &Orderbook<T>, price_bound: Option<FixedU128>) -> Result<FixedU128, Error<T>> {
+        let (base_reserve, quote_reserve) = match Self::pool_reserves(orderbook) {
+            Some(reserves) => reserves,
+            None => return Ok(FixedU128::from(0)),
+        };
+        let (base_amount, quote_amount) = match Self::pool_fill_amounts(&order.order_type, order.price, order.quantity, orderbook.lot_size,
+                                                                        base_reserve, quote_reserve, price_bound)? {
+            Some(amounts) => amounts,
+            None => return Ok(FixedU128::from(0)),
+        };
+        let pool = Self::pool_account(&orderbook.trading_pair);
+        match order.order_type {
+            OrderType::BidLimit | OrderType::BidMarket => {
+                if order.order_type == OrderType::BidLimit {
+                    // The order reserved its limit price for the quantity, release what the pool didn't charge
+                    let reserved_for_fill = order.price.checked_mul(&quote_amount).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                    Self::transfer_reserved(order, orderbook.base_asset_id, base_amount, &pool)?;
+                    Self::release_reserve(order, orderbook.base_asset_id, reserved_for_fill.saturating_sub(base_amount))?;
+                    order.quantity = order.quantity.checked_sub(&quote_amount).ok_or(<Error<T>>::SubUnderflowOrOverflow)?;
+                } else {
+                    Self::transfer_asset_market(orderbook.base_asset_id, base_amount, &order.trader, &pool)?;
+                    order.price = order.price.checked_sub(&base_amount).ok_or(<Error<T>>::SubUnderflowOrOverflow)?;
+                }
+                Self::transfer_asset_market(orderbook.quote_asset_id, quote_amount, &pool, &order.trader)?;
+                Self::share_referral_fee(&order.trader, orderbook.base_asset_id, base_amount, &pool)?;
+            }
+            OrderType::AskLimit | OrderType::AskMarket => {
+                if order.order_type == OrderType::AskLimit {
+                    Self::transfer_reserved(order, orderbook.quote_asset_id, quote_amount, &pool)?;
+                } else {
+                    Self::transfer_asset_market(orderbook.quote_asset_id, quote_amount, &order.trader, &pool)?;
+                }
+                Self::transfer_asset_market(orderbook.base_asset_id, base_amount, &pool, &order.trader)?;
+                Self::share_referral_fee(&order.trader, orderbook.quote_asset_id, quote_amount, &pool)?;
+                order.quantity = order.quantity.checked_sub(&quote_amount).ok_or(<Error<T>>::SubUnderflowOrOverflow)?;
+            }
+        }
+        let price = base_amount.checked_div(&quote_amount).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+        Self::record_pool_trade(&orderbook.trading_pair, price, quote_amount, base_amount);
+        Self::deposit_event(RawEvent::PoolFill(order.id, orderbook.trading_pair, order.order_type.clone(), base_amount, quote_amount, order.trader.clone()));
+        match order.order_type {
+            OrderType::BidLimit | OrderType::BidMarket => Ok(quote_amount),
+            OrderType::AskLimit | OrderType::AskMarket => Ok(base_amount),
+        }
+    }
+
+    // Adds a trade with the liquidity pool to the MarketData of the current block, the ticker and the oracle
+    fn record_pool_trade(trading_pair: &T::Hash, price: FixedU128, quantity: FixedU128, trade_amount: FixedU128) {
+        let current_block_number: T::BlockNumber = <frame_system::Module<T>>::block_number();
+        Self::index_market_data(trading_pair, current_block_number);
+        <MarketInfo<T>>::mutate(trading_pair, current_block_number, |market_data| {
+            let mut merged = market_data.take().unwrap_or(MarketData {
+                low: FixedU128::from(0),
+                high: FixedU128::from(0),
+                volume: FixedU128::from(0),
+            });
+            merged.merge(&MarketData { low: price, high: price, volume: trade_amount });
+            *market_data = Some(merged);
+        });
+        Self::record_trade(trading_pair, price, quantity, trade_amount);
+    }
+
+    // Account the maker rewards are paid out of
+    pub fn maker_reward_account() -> T::AccountId {
+        MAKER_REWARD_MODULE_ID.into_account()
+    }
+
+    // Price levels of the book within spread of its mid price, the bids from the best price down and the
+    // asks from the best price up, with the distance from the mid price the spread allows. None without
+    // both sides of the book since there is no mid price to measure the distance from.
+    pub(crate) fn levels_near_mid_price(orderbook: &Orderbook<T>, spread: Permill) -> Option<(FixedU128, Vec<FixedU128>, Vec<FixedU128>)> {
+        if orderbook.best_bid_price == FixedU128::from(0) || orderbook.best_ask_price == FixedU128::from(0) {
+            return None;
+        }
+        let mid_price = orderbook.best_bid_price.checked_add(&orderbook.best_ask_price)?.checked_div(&FixedU128::from(2))?;
+        let distance = mid_price.saturating_mul(Self::permill_to_fixed(spread));
+        let lowest_price = mid_price.saturating_sub(distance);
+        let highest_price = mid_price.saturating_add(distance);
+        let bids_levels: Vec<FixedU128> = <BidsLevels<T>>::get(&orderbook.trading_pair).into_iter().rev()
+            .take_while(|price| *price >= lowest_price)
+            .collect();
+        let asks_levels: Vec<FixedU128> = <AsksLevels<T>>::get(&orderbook.trading_pair).into_iter()
+            .take_while(|price| *price <= highest_price)
+            .collect();
+        Some((distance, bids_levels, asks_levels))
+    }
+
+    // Pays the rewards of the last reward period once it is over and gives points to the orders resting near the mid price
+    fn accrue_maker_rewards() -> Weight {
+        let mut weight = T::DbWeight::get().reads(1);
+        let period = T::RewardPeriodIndex::get();
+        if period != Self::current_reward_period() {
+            weight = weight.saturating_add(Self::pay_maker_rewards()).saturating_add(Self::settle_market_makers());
+            CurrentRewardPeriod::put(period);
+            weight = weight.saturating_add(T::DbWeight::get().writes(1));
+        }
+        weight.saturating_add(Self::award_maker_points()).saturating_add(Self::measure_market_makers())
+    }
+
+    // Splits the reward of every TradingPair among its makers by their points and starts the next period from zero,
+    // the reward is capped by the balance left in the reward account
+    fn pay_maker_rewards() -> Weight {
+        let reward_account = Self::maker_reward_account();
+        let mut reads: Weight = 0;
+        let mut writes: Weight = 0;
+        for (trading_pair, (reward_asset_id, reward_per_period)) in <MakerRewards<T>>::iter() {
+            let total_points = <MakerRewardTotalPoints<T>>::take(&trading_pair);
+            let points: Vec<(T::AccountId, FixedU128)> = <MakerRewardPoints<T>>::drain_prefix(&trading_pair).collect();
+            reads = reads.saturating_add(3 + points.len() as Weight);
+            writes = writes.saturating_add(1 + points.len() as Weight);
+            if total_points == FixedU128::from(0) {
+                continue;
+            }
+            let available = Self::convert_balance_to_fixed_u128(&reward_asset_id,
+                                                                pallet_generic_asset::Module::<T>::free_balance(&reward_asset_id, &reward_account))
+                .unwrap_or_else(|| FixedU128::from(0));
+            let reward = reward_per_period.min(available);
+            for (trader, trader_points) in points {
+                let amount = trader_points.checked_div(&total_points)
+                    .and_then(|share| reward.checked_mul(&share))
+                    .and_then(|amount| Self::round_to_asset_precision(&reward_asset_id, amount))
+                    .unwrap_or_else(|| FixedU128::from(0));
+                if amount == FixedU128::from(0) {
+                    continue;
+                }
+                if Self::transfer_asset_market(reward_asset_id, amount, &reward_account, &trader).is_ok() {
+                    writes = writes.saturating_add(2);
+                    Self::deposit_event(RawEvent::MakerRewardPaid(trading_pair, trader, reward_asset_id, amount));
+                }
+            }
+        }
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+
+    // Gives every limit order resting within MakerRewardSpread of the mid price points equal to its quantity,
+    // called once per block so that points grow with both size and time on the book
+    fn award_maker_points() -> Weight {
+        let spread = T::MakerRewardSpread::get();
+        let mut reads: Weight = 0;
+        let mut writes: Weight = 0;
+        for (trading_pair, _) in <MakerRewards<T>>::iter() {
+            let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+            reads = reads.saturating_add(2);
+            let (_, bids_levels, asks_levels) = match Self::levels_near_mid_price(&orderbook, spread) {
+                Some(levels) => levels,
+                None => continue,
+            };
+            reads = reads.saturating_add(2);
+            let prices = bids_levels.into_iter().chain(asks_levels.into_iter());
+            let mut total_points = <MakerRewardTotalPoints<T>>::get(&trading_pair);
+            for price in prices {
+                let price_level: LinkedPriceLevel<T> = <PriceLevels<T>>::get(&trading_pair, price);
+                reads = reads.saturating_add(1);
+                for order in price_level.orders.iter() {
+                    <MakerRewardPoints<T>>::mutate(&trading_pair, &order.trader, |points| *points = points.saturating_add(order.quantity));
+                    total_points = total_points.saturating_add(order.quantity);
+                    reads = reads.saturating_add(1);
+                    writes = writes.saturating_add(1);
+                }
+            }
+            <MakerRewardTotalPoints<T>>::insert(&trading_pair, total_points);
+            writes = writes.saturating_add(1);
+        }
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+
     // Inserts the given order into orderbook
     fn insert_order(current_order: &Order<T>, orderbook: &mut Orderbook<T>) -> Result<(), Error<T>> {
         // TODO: bids_levels should be sorted in descending order  FIX-look
@@ -821,7 +2893,10 @@ impl<T: Trait> Module<T> {
         Ok(())
     }
 
-    fn consume_order(current_order: &mut Order<T>, orderbook: &mut Orderbook<T>) -> Result<(), Error<T>> {
+    // Matches the order against the other side of the book, returns the amount market order fills paid to
+    // the trader, limit order matches are credited when the block is settled
+    fn consume_order(current_order: &mut Order<T>, orderbook: &mut Orderbook<T>) -> Result<FixedU128, Error<T>> {
+        let mut received = FixedU128::from(0);
         let mut market_data: MarketData;
         // TODO: Not sure what will be the return value of get() given below for keys that doesn't exist.
         // TODO: Currently I am assuming it will be None and not Some("default value of MarketData")
@@ -944,11 +3019,11 @@ impl<T: Trait> Module<T> {
                 // We iterate until current_order is fulfilled or exhausts the Ask orders in the system.
                 while current_order.price > FixedU128::from(0) {
                     if let Some(mut counter_order) = linkedpricelevel.orders.pop_front() {
-                        Self::do_asset_exchange_market(current_order,
-                                                       &mut counter_order,
-                                                       &mut market_data,
-                                                       orderbook.base_asset_id,
-                                                       orderbook.quote_asset_id)?;
+                        received = received.saturating_add(Self::do_asset_exchange_market(current_order,
+                                                                                          &mut counter_order,
+                                                                                          &mut market_data,
+                                                                                          orderbook.base_asset_id,
+                                                                                          orderbook.quote_asset_id)?);
 
 
                         if counter_order.quantity > FixedU128::from(0) {
@@ -1105,11 +3180,11 @@ impl<T: Trait> Module<T> {
                 let mut linkedpricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::take(&current_order.trading_pair, &orderbook.best_bid_price);
                 while current_order.quantity > FixedU128::from(0) {
                     if let Some(mut counter_order) = linkedpricelevel.orders.pop_front() {
-                        Self::do_asset_exchange_market(current_order,
-                                                       &mut counter_order,
-                                                       &mut market_data,
-                                                       orderbook.base_asset_id,
-                                                       orderbook.quote_asset_id)?;
+                        received = received.saturating_add(Self::do_asset_exchange_market(current_order,
+                                                                                          &mut counter_order,
+                                                                                          &mut market_data,
+                                                                                          orderbook.base_asset_id,
+                                                                                          orderbook.quote_asset_id)?);
 
                         if counter_order.quantity > FixedU128::from(0) {
                             // Emit events
@@ -1173,11 +3248,13 @@ impl<T: Trait> Module<T> {
             }
         }
         // Write the market data back to storage
+        Self::index_market_data(&current_order.trading_pair, current_block_number);
         <MarketInfo<T>>::insert(&current_order.trading_pair, current_block_number, market_data);
-        Ok(())
+        Ok(received)
     }
 
-    fn do_asset_exchange_market(current_order: &mut Order<T>, counter_order: &mut Order<T>, market_data: &mut MarketData, base_assetid: T::AssetId, quote_assetid: T::AssetId) -> Result<(), Error<T>> {
+    // Fills the market order against the counter_order, returns the amount paid to the market order's trader
+    fn do_asset_exchange_market(current_order: &mut Order<T>, counter_order: &mut Order<T>, market_data: &mut MarketData, base_assetid: T::AssetId, quote_assetid: T::AssetId) -> Result<FixedU128, Error<T>> {
         if market_data.low == FixedU128::from(0) {
             market_data.low = counter_order.price
         }
@@ -1190,7 +3267,7 @@ impl<T: Trait> Module<T> {
         if market_data.low > counter_order.price {
             market_data.low = counter_order.price
         }
-        match current_order.order_type {
+        let received = match current_order.order_type {
             OrderType::BidMarket => {
                 let current_order_quantity = current_order.price.checked_div(&counter_order.price).ok_or(Error::<T>::DivUnderflowOrOverflow.into())?;
                 // 100/5 = 20.
@@ -1199,23 +3276,28 @@ impl<T: Trait> Module<T> {
                     // Transfer the base asset
                     Self::transfer_asset_market(base_assetid, current_order.price, &current_order.trader, &counter_order.trader)?;
                     // Transfer the quote asset
-                    Self::transfer_asset(quote_assetid, current_order_quantity, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved(counter_order, quote_assetid, current_order_quantity, &current_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&current_order.price).ok_or(Error::<T>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, current_order_quantity, current_order.price);
                     //Set current_order quantity to 0 and counter_order is reduced by fulfilled amount
                     counter_order.quantity = counter_order.quantity.checked_sub(&current_order_quantity).ok_or(Error::<T>::SubUnderflowOrOverflow.into())?;
                     current_order.price = FixedU128::from(0);
+                    current_order_quantity
                 } else {
                     let trade_amount = counter_order.price.checked_mul(&counter_order.quantity).ok_or(Error::<T>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
                     Self::transfer_asset_market(base_assetid, trade_amount, &current_order.trader, &counter_order.trader)?;
                     // Transfer the quote asset
-                    Self::transfer_asset(quote_assetid, counter_order.quantity, &counter_order.trader, &current_order.trader)?;
+                    let filled_quantity = counter_order.quantity;
+                    Self::transfer_reserved(counter_order, quote_assetid, filled_quantity, &current_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(Error::<T>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, counter_order.quantity, trade_amount);
                     // counter_order is set to 0 and current_order.price is reduced by fulfilled amount
                     counter_order.quantity = FixedU128::from(0);
                     current_order.price = current_order.price.checked_sub(&trade_amount).ok_or(Error::<T>::SubUnderflowOrOverflow.into())?;
+                    filled_quantity
                 }
             }
             OrderType::AskMarket => {
@@ -1223,39 +3305,46 @@ impl<T: Trait> Module<T> {
                     // We have enough quantity in the counter_order to fulfill current_order completely
                     let trade_amount = counter_order.price.checked_mul(&current_order.quantity).ok_or(Error::<T>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
-                    Self::transfer_asset(base_assetid, trade_amount, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved(counter_order, base_assetid, trade_amount, &current_order.trader)?;
                     // Transfer the quote asset
                     Self::transfer_asset_market(quote_assetid, current_order.quantity, &current_order.trader, &counter_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(Error::<T>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, current_order.quantity, trade_amount);
                     // current_order is set to 0 and counter_order is reduced by fulfilled amount
                     counter_order.quantity = counter_order.quantity.checked_sub(&current_order.quantity).ok_or(Error::<T>::SubUnderflowOrOverflow.into())?;
                     current_order.quantity = FixedU128::from(0);
+                    trade_amount
                 } else {
                     // We have enough quantity in the counter_order to fulfill current_order completely
                     let trade_amount = counter_order.price.checked_mul(&counter_order.quantity).ok_or(Error::<T>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
-                    Self::transfer_asset(base_assetid, trade_amount, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved(counter_order, base_assetid, trade_amount, &current_order.trader)?;
                     // Transfer the quote asset
                     Self::transfer_asset_market(quote_assetid, counter_order.quantity, &current_order.trader, &counter_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(Error::<T>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, counter_order.quantity, trade_amount);
                     // counter_order is set to 0 and current_order is reduced by fulfilled amount
                     current_order.quantity = current_order.quantity.checked_sub(&counter_order.quantity).ok_or(Error::<T>::SubUnderflowOrOverflow.into())?;
                     counter_order.quantity = FixedU128::from(0);
+                    trade_amount
                 }
             }
             _ => {
                 // It won't execute.
+                FixedU128::from(0)
             }
-        }
-        Ok(())
+        };
+        Self::release_filled_counter_order(counter_order, base_assetid, quote_assetid)?;
+        Ok(received)
     }
 
     // It checks the if the counter_order.quantity has enough to fulfill current_order then exchanges
     // it after collecting fees and else if counter_order.quantity is less than current_order.quantity
     // then exchange counter_order.quantity completely and return.
     // current_order.quantity is modified to new value.
+    // The exchanged balances are netted and settled in on_finalize.
     fn do_asset_exchange(current_order: &mut Order<T>, counter_order: &mut Order<T>, market_data: &mut MarketData, base_assetid: T::AssetId, quote_assetid: T::AssetId) -> Result<(), Error<T>> {
         if market_data.low == FixedU128::from(0) {
             market_data.low = counter_order.price
@@ -1276,28 +3365,37 @@ impl<T: Trait> Module<T> {
                 if current_order.quantity <= counter_order.quantity {
                     // We have enough quantity in the counter_order to fulfill current_order completely
                     // Calculate the total cost in base asset for buying required amount
-                    let trade_amount = current_order.price.checked_mul(&current_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
+                    let trade_amount = counter_order.price.checked_mul(&current_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
                     // AssetId, amount to send, from, to
-                    Self::transfer_asset(base_assetid, trade_amount, &current_order.trader, &counter_order.trader)?;
+                    Self::transfer_reserved_netted(current_order, base_assetid, trade_amount, &counter_order.trader)?;
+                    // Release what was reserved above the counter_order's price
+                    let improvement = Self::price_improvement(current_order, counter_order, current_order.quantity)?;
+                    Self::release_reserve(current_order, base_assetid, improvement)?;
                     // Transfer the quote asset
-                    Self::transfer_asset(quote_assetid, current_order.quantity, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved_netted(counter_order, quote_assetid, current_order.quantity, &current_order.trader)?;
                     // Add the executed volume
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(<Error<T>>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, current_order.quantity, trade_amount);
                     //Set Current order quantity to 0 and counter_order is subtracted.
                     counter_order.quantity = counter_order.quantity.checked_sub(&current_order.quantity).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
                     current_order.quantity = FixedU128::from(0);
                 } else {
                     // current_order is partially filled and counter_order is completely filled.
                     // Calculate the total cost in base asset for buying required amount
-                    let trade_amount = current_order.price.checked_mul(&counter_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
+                    let trade_amount = counter_order.price.checked_mul(&counter_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
                     // AssetId, amount to send, from, to
-                    Self::transfer_asset(base_assetid, trade_amount, &current_order.trader, &counter_order.trader)?;
+                    Self::transfer_reserved_netted(current_order, base_assetid, trade_amount, &counter_order.trader)?;
+                    // Release what was reserved above the counter_order's price
+                    let improvement = Self::price_improvement(current_order, counter_order, counter_order.quantity)?;
+                    Self::release_reserve(current_order, base_assetid, improvement)?;
                     // Transfer the quote asset from counter_order to current_order's trader.
-                    Self::transfer_asset(quote_assetid, counter_order.quantity, &counter_order.trader, &current_order.trader)?;
+                    let filled_quantity = counter_order.quantity;
+                    Self::transfer_reserved_netted(counter_order, quote_assetid, filled_quantity, &current_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, counter_order.quantity, trade_amount);
                     //Set counter_order quantity to 0 and current_order is subtracted.
                     current_order.quantity = current_order.quantity.checked_sub(&counter_order.quantity).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
                     counter_order.quantity = FixedU128::from(0);
@@ -1311,11 +3409,13 @@ impl<T: Trait> Module<T> {
                     let trade_amount = counter_order.price.checked_mul(&current_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
                     // AssetId, amount to send, from, to
-                    Self::transfer_asset(base_assetid, trade_amount, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved_netted(counter_order, base_assetid, trade_amount, &current_order.trader)?;
                     // Transfer the quote asset
-                    Self::transfer_asset(quote_assetid, current_order.quantity, &current_order.trader, &counter_order.trader)?;
+                    let filled_quantity = current_order.quantity;
+                    Self::transfer_reserved_netted(current_order, quote_assetid, filled_quantity, &counter_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(<Error<T>>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, current_order.quantity, trade_amount);
                     //Set Current order quantity to 0 and counter_order is subtracted.
                     counter_order.quantity = counter_order.quantity.checked_sub(&current_order.quantity).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
                     current_order.quantity = FixedU128::from(0);
@@ -1325,11 +3425,12 @@ impl<T: Trait> Module<T> {
                     let trade_amount = counter_order.price.checked_mul(&counter_order.quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())?;
                     // Transfer the base asset
                     // AssetId, amount to send, from, to
-                    Self::transfer_asset(base_assetid, trade_amount, &counter_order.trader, &current_order.trader)?;
+                    Self::transfer_reserved_netted(counter_order, base_assetid, trade_amount, &current_order.trader)?;
                     // Transfer the quote asset from counter_order to current_order's trader.
-                    Self::transfer_asset(quote_assetid, counter_order.quantity, &current_order.trader, &counter_order.trader)?;
+                    Self::transfer_reserved_netted(current_order, quote_assetid, counter_order.quantity, &counter_order.trader)?;
                     // Add the volume executed
                     market_data.volume = market_data.volume.checked_add(&trade_amount).ok_or(<Error<T>>::AddUnderflowOrOverflow.into())?;
+                    Self::record_trade(&current_order.trading_pair, counter_order.price, counter_order.quantity, trade_amount);
                     //Set counter_order quantity to 0 and current_order is subtracted.
                     current_order.quantity = current_order.quantity.checked_sub(&counter_order.quantity).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
                     counter_order.quantity = FixedU128::from(0);
@@ -1338,28 +3439,73 @@ impl<T: Trait> Module<T> {
 
             _ => {}
         }
+        Self::release_filled_counter_order(counter_order, base_assetid, quote_assetid)?;
         Ok(())
     }
 
     // Transfers the balance of traders
     fn transfer_asset(asset_id: T::AssetId, amount: FixedU128, from: &T::AccountId, to: &T::AccountId) -> Result<(), Error<T>> {
-        let amount_balance = Self::convert_fixed_u128_to_balance(amount).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
-        // Initially the balance was reserved so now it is unreserved and then transfer is made
-        pallet_generic_asset::Module::<T>::unreserve(&asset_id, from, amount_balance);
-        match pallet_generic_asset::Module::<T>::make_transfer(&asset_id, from, to,
-                                                               amount_balance) {
-            Ok(_) => Ok(()),
-            _ => Err(<Error<T>>::ErrorWhileTransferingAsset.into()),
-        }
+        // Initially the balance was reserved so it is paid out of the reserved balance
+        Self::transfer_tradable(asset_id, amount, from, to, true)
     }
 
     // Transfers the balance of traders
     fn transfer_asset_market(asset_id: T::AssetId, amount: FixedU128, from: &T::AccountId, to: &T::AccountId) -> Result<(), Error<T>> {
-        let amount_balance = Self::convert_fixed_u128_to_balance(amount).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
-        match pallet_generic_asset::Module::<T>::make_transfer(&asset_id, from, to,
-                                                               amount_balance) {
-            Ok(_) => Ok(()),
-            _ => Err(<Error<T>>::ErrorWhileTransferingAsset.into()),
+        Self::transfer_tradable(asset_id, amount, from, to, false)
+    }
+
+    // Transfers out of the balance reserved for the order and keeps track of what is left reserved
+    fn transfer_reserved(order: &mut Order<T>, asset_id: T::AssetId, amount: FixedU128, to: &T::AccountId) -> Result<(), Error<T>> {
+        // Only whole balance units are unreserved so only those are deducted, the dust is released once the order is done
+        let amount = Self::round_to_asset_precision(&asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        Self::transfer_asset(asset_id, amount, &order.trader, to)?;
+        order.reserved = order.reserved.saturating_sub(amount);
+        Ok(())
+    }
+
+    // Unreserves the given amount of the order's reserved balance back to its trader
+    fn release_reserve(order: &mut Order<T>, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        // Never release more than the order holds, the rest may belong to other orders of the trader
+        let amount = Self::round_to_asset_precision(&asset_id, amount.min(order.reserved)).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        if amount == FixedU128::from(0) {
+            return Ok(());
+        }
+        Self::unreserve_tradable(&asset_id, &order.trader, amount)?;
+        order.reserved = order.reserved.saturating_sub(amount);
+        Self::deposit_event(RawEvent::ReservationReleased(order.id, order.trader.clone(), asset_id, amount));
+        Ok(())
+    }
+
+    // Releases whatever is left reserved for a counter_order that got completely filled
+    fn release_filled_counter_order(counter_order: &mut Order<T>, base_assetid: T::AssetId, quote_assetid: T::AssetId) -> Result<(), Error<T>> {
+        if counter_order.quantity > FixedU128::from(0) {
+            return Ok(());
+        }
+        let asset_id = if counter_order.order_type == OrderType::AskLimit { quote_assetid } else { base_assetid };
+        let leftover = counter_order.reserved;
+        Self::release_reserve(counter_order, asset_id, leftover)
+    }
+
+    // Base asset a BidLimit reserved above the counter_order's price for the filled quantity
+    fn price_improvement(current_order: &Order<T>, counter_order: &Order<T>, quantity: FixedU128) -> Result<FixedU128, Error<T>> {
+        let price_difference = current_order.price.checked_sub(&counter_order.price).ok_or(<Error<T>>::SubUnderflowOrOverflow.into())?;
+        price_difference.checked_mul(&quantity).ok_or(<Error<T>>::MulUnderflowOrOverflow.into())
+    }
+
+    // Amount check_order reserves for the order, market orders are paid from the free balance
+    fn required_reserve(order: &Order<T>) -> Option<FixedU128> {
+        match order.order_type {
+            OrderType::BidLimit => order.price.checked_mul(&order.quantity),
+            OrderType::AskLimit => Some(order.quantity),
+            OrderType::BidMarket | OrderType::AskMarket => Some(FixedU128::from(0)),
+        }
+    }
+
+    // Asset the order's balance is reserved in
+    fn reserved_asset_id(order_type: &OrderType, orderbook: &Orderbook<T>) -> T::AssetId {
+        match order_type {
+            OrderType::AskLimit | OrderType::AskMarket => orderbook.quote_asset_id,
+            OrderType::BidLimit | OrderType::BidMarket => orderbook.base_asset_id,
         }
     }
 
@@ -1375,12 +3521,22 @@ impl<T: Trait> Module<T> {
     }
     fn check_order(order: &Order<T>) -> Result<Orderbook<T>, Error<T>> {
         let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&order.trading_pair);
-        let balance: <T>::Balance = match order.order_type {
-            OrderType::BidLimit | OrderType::BidMarket => pallet_generic_asset::Module::<T>::free_balance(&orderbook.base_asset_id, &order.trader),
-            OrderType::AskMarket | OrderType::AskLimit => pallet_generic_asset::Module::<T>::free_balance(&orderbook.quote_asset_id, &order.trader),
-        };
-
-        match Self::convert_balance_to_fixed_u128(balance) {
+        // Orders are not accepted once either asset is removed from the whitelist
+        ensure!(Self::is_whitelisted_asset(&orderbook.base_asset_id) && Self::is_whitelisted_asset(&orderbook.quote_asset_id),
+                <Error<T>>::AssetNotWhitelisted);
+        // BidMarket orders carry the amount to spend in price so only limit prices are checked against the tick size
+        match order.order_type {
+            OrderType::BidLimit | OrderType::AskLimit => {
+                ensure!(Orderbook::<T>::is_multiple_of(order.price, orderbook.tick_size), <Error<T>>::InvalidTickSize);
+                ensure!(Orderbook::<T>::is_multiple_of(order.quantity, orderbook.lot_size), <Error<T>>::InvalidLotSize);
+            }
+            OrderType::AskMarket => {
+                ensure!(Orderbook::<T>::is_multiple_of(order.quantity, orderbook.lot_size), <Error<T>>::InvalidLotSize);
+            }
+            OrderType::BidMarket => {}
+        }
+        let asset_id = Self::reserved_asset_id(&order.order_type, &orderbook);
+        match Self::tradable_balance(&asset_id, &order.trader) {
             Some(converted_balance) if order.order_type == OrderType::BidLimit => Self::compare_balance(converted_balance, order, orderbook),
             Some(converted_balance) if order.order_type == OrderType::BidMarket && converted_balance < order.price => Err(<Error<T>>::InsufficientAssetBalance.into()),
             Some(converted_balance) if (order.order_type == OrderType::AskLimit || order.order_type == OrderType::AskMarket) && converted_balance < order.quantity => Err(<Error<T>>::InsufficientAssetBalance.into()),
@@ -1403,40 +3559,37 @@ impl<T: Trait> Module<T> {
         // TODO: Based on BidLimit or AskLimit we need to change between orderbook.base_asset_id & orderbook.quote_asset_id respectively
         let asset_id = if order.order_type == OrderType::AskLimit { &orderbook.quote_asset_id } else { &orderbook.base_asset_id };
 
-        match Self::convert_fixed_u128_to_balance(amount) {
-            Some(balance) => {
-                match pallet_generic_asset::Module::<T>::reserve(
-                    asset_id, &order.trader,
-                    balance) {
-                    Ok(_) => Ok(orderbook),
-                    _ => Err(<Error<T>>::ReserveAmountFailed.into()),
-                }
-            }
+        Self::reserve_tradable(asset_id, &order.trader, amount)?;
+        Ok(orderbook)
+    }
 
-            None => Err(<Error<T>>::InternalErrorU128Balance.into()),
-        }
+    // Decimals of the asset's balance
+    pub fn decimals_of(asset_id: &T::AssetId) -> u8 {
+        Self::asset_decimals(asset_id).unwrap_or(DEFAULT_ASSET_DECIMALS)
     }
 
-    // Converts Balance to FixedU128 representation
-    pub fn convert_balance_to_fixed_u128(x: T::Balance) -> Option<FixedU128> {
-        if let Some(y) = TryInto::<u128>::try_into(x).ok() {
-            FixedU128::from(y).checked_div(&FixedU128::from(1_000_000_000_000))
-        } else {
-            None
-        }
+    // Converts Balance of the asset to FixedU128 representation
+    pub fn convert_balance_to_fixed_u128(asset_id: &T::AssetId, x: T::Balance) -> Option<FixedU128> {
+        let balance_in_u128 = TryInto::<u128>::try_into(x).ok()?;
+        let scale = decimals_scale(Self::decimals_of(asset_id))?;
+        balance_in_u128.checked_mul(scale).map(FixedU128::from_inner)
     }
 
-    // Converts FixedU128 to Balance representation
-    pub fn convert_fixed_u128_to_balance(x: FixedU128) -> Option<T::Balance> {
-        if let Some(balance_in_fixed_u128) = x.checked_div(&FixedU128::from(1000000)) {
-            let balance_in_u128 = balance_in_fixed_u128.into_inner();
-            Some(UniqueSaturatedFrom::<u128>::unique_saturated_from(balance_in_u128))
-        } else {
-            None
-        }
+    // Truncates the digits of x beyond the decimals of the asset
+    fn round_to_asset_precision(asset_id: &T::AssetId, x: FixedU128) -> Option<FixedU128> {
+        let scale = decimals_scale(Self::decimals_of(asset_id))?;
+        Some(FixedU128::from_inner(x.into_inner() / scale * scale))
+    }
+
+    // Converts FixedU128 to Balance representation of the asset, digits beyond its decimals are truncated
+    pub fn convert_fixed_u128_to_balance(asset_id: &T::AssetId, x: FixedU128) -> Option<T::Balance> {
+        let scale = decimals_scale(Self::decimals_of(asset_id))?;
+        let balance_in_u128 = x.into_inner() / scale;
+        Some(UniqueSaturatedFrom::<u128>::unique_saturated_from(balance_in_u128))
     }
 
     pub fn emit_partial_fill(order: &Order<T>, filled_amount: FixedU128) {
+        Self::update_order_record(order);
         Self::deposit_event(RawEvent::PartialFillLimitOrder(order.id,
                                                             order.trading_pair,
                                                             order.order_type.clone(),
@@ -1446,6 +3599,7 @@ impl<T: Trait> Module<T> {
     }
 
     pub fn emit_complete_fill(order: &Order<T>, filled_amount: FixedU128) {
+        Self::update_order_record(order);
         Self::deposit_event(RawEvent::FulfilledLimitOrder(order.id,
                                                           order.trading_pair,
                                                           order.order_type.clone(),
@@ -1456,43 +3610,35 @@ impl<T: Trait> Module<T> {
 
     // Cancels an existing active order
     pub fn cancel_order_from_orderbook(trader: T::AccountId, order_id: T::Hash, trading_pair: T::Hash, price: FixedU128) -> Result<(), Error<T>> {
+        // Orders still waiting for the off-chain worker are not in the price levels yet
+        if Self::cancel_pending_order(&trader, &order_id, &trading_pair)? {
+            return Ok(());
+        }
         // There are two situations we get the LinkedPriceLevel delete the order from that FIFO
         // FIFO can be empty after this operation so we delete the LinkedPriceLevel and modify the
         // next and prev of LinkedPriceLevels previous and next to this one.
         // Also delete the price from AsksLevels or BidsLevels as per the current_order.
-        let mut current_linkedpricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::take(trading_pair, price);
-        let mut index = 0;
-        let mut match_flag = false;
-        // TODO: Can we define removed_order like in the comments?
-        // let removed_order: Order<T>;
-        let mut removed_order: Order<T> = Order {
-            id: Default::default(),
-            trading_pair: Default::default(),
-            trader: Default::default(),
-            price: Default::default(),
-            quantity: Default::default(),
-            order_type: OrderType::BidLimit,
-        };
-        // TODO: Can we optimize this iteration? or even completely remove it?
-        for order in current_linkedpricelevel.orders.iter() {
-            if order.id == order_id {
-                removed_order = current_linkedpricelevel.orders.remove(index).unwrap();
-                match_flag = true;
-                break;
-            }
-            index = index + 1;
-        }
-        ensure!(match_flag, <Error<T>>::InvalidOrderID);
-        ensure!(removed_order.trader == trader,<Error<T>>::InvalidOrigin);
-        ensure!(removed_order.trading_pair == trading_pair,<Error<T>>::TradingPairMismatch);
-        ensure!(removed_order.price == price,<Error<T>>::CancelPriceDoesntMatch);
-
+        // The price level is only changed in storage once every check passed, a failed cancel leaves it as it was.
+        let mut current_linkedpricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::get(trading_pair, price);
+        let index = current_linkedpricelevel.orders.iter().position(|order| order.id == order_id).ok_or(<Error<T>>::InvalidOrderID)?;
+        let order = &current_linkedpricelevel.orders[index];
+        ensure!(order.trader == trader,<Error<T>>::InvalidOrigin);
+        ensure!(order.trading_pair == trading_pair,<Error<T>>::TradingPairMismatch);
+        ensure!(order.price == price,<Error<T>>::CancelPriceDoesntMatch);
+        let mut removed_order: Order<T> = current_linkedpricelevel.orders.remove(index).ok_or(<Error<T>>::InvalidOrderID)?;
+
+        // The order won't trade anymore so everything still reserved for it goes back to the trader
+        let asset_id = Self::reserved_asset_id(&removed_order.order_type, &<Orderbooks<T>>::get(trading_pair));
+        let leftover = removed_order.reserved;
+        Self::release_reserve(&mut removed_order, asset_id, leftover)?;
+        Self::close_order_record(&order_id, OrderStatus::Cancelled);
 
         if !current_linkedpricelevel.orders.is_empty() {
             // Current LinkedPriceLevel contains other orders so write it back to storage and exit
             <PriceLevels<T>>::insert(trading_pair, price, current_linkedpricelevel);
             return Ok(());
         }
+        <PriceLevels<T>>::remove(trading_pair, price);
         // There are no more orders in the current linkedPricelevel struct so we need to remove it also
         // make sure the linkedlist is not broken when this linked item was removed so modify the next and prev members accordingly.
         // Also check if the it is the best_bid_price or best_ask_price if so modify that too.
@@ -1526,20 +3672,32 @@ impl<T: Trait> Module<T> {
 
             // Write it back
             <PriceLevels<T>>::insert(trading_pair, current_linkedpricelevel.next.unwrap(), next_linkedpricelevel);
+        }
 
-            // Update the orderbook
-            let mut orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
-            // Update the best_bid_price if applicable
-            if removed_order.order_type == OrderType::BidLimit && price == orderbook.best_bid_price {
-                orderbook.best_bid_price = current_linkedpricelevel.next.unwrap();
+        // The price level doesn't exist anymore so remove it from the level vector and update the orderbook's
+        // best price of that side, which also covers the level being the only one on its side.
+        let mut orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+        match removed_order.order_type {
+            OrderType::AskLimit => {
+                let mut asks_levels: Vec<FixedU128> = <AsksLevels<T>>::get(trading_pair);
+                if let Ok(index) = asks_levels.binary_search(&price) {
+                    asks_levels.remove(index);
+                }
+                orderbook.best_ask_price = asks_levels.first().copied().unwrap_or(FixedU128::from(0));
+                <AsksLevels<T>>::insert(trading_pair, asks_levels);
             }
-            // Update the best_ask_price if applicable
-            if removed_order.order_type == OrderType::AskLimit && price == orderbook.best_ask_price {
-                orderbook.best_ask_price = current_linkedpricelevel.next.unwrap();
+            OrderType::BidLimit => {
+                let mut bids_levels: Vec<FixedU128> = <BidsLevels<T>>::get(trading_pair);
+                if let Ok(index) = bids_levels.binary_search(&price) {
+                    bids_levels.remove(index);
+                }
+                orderbook.best_bid_price = bids_levels.last().copied().unwrap_or(FixedU128::from(0));
+                <BidsLevels<T>>::insert(trading_pair, bids_levels);
             }
-            // Write orderbook back to storage
-            <Orderbooks<T>>::insert(trading_pair, orderbook);
+            _ => {}
         }
+        // Write orderbook back to storage
+        <Orderbooks<T>>::insert(trading_pair, orderbook);
         Ok(())
     }
 
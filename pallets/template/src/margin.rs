@@ -0,0 +1,432 @@
+//! Isolated margin trading with borrowable lending pools.
+//!
+//! Every trader has a margin account per TradingPair, a sub account of MARGIN_MODULE_ID that holds
+//! the collateral and everything bought with borrowed funds, so a position on one TradingPair never
+//! affects another one. Loans are taken from the lending pool of an asset whose lenders earn the
+//! interest paid by the borrowers. Orders of a margin account go through execute_order like any other
+//! order. Holdings and debt are valued in the base asset at the time weighted average price of the
+//! TradingPair over the last MarginPriceWindow blocks.
+
+use codec::{Decode, Encode};
+use frame_support::{ensure, transactional};
+use frame_support::storage::{StorageDoubleMap, StorageMap};
+use frame_support::traits::Get;
+use sp_arithmetic::{FixedPointNumber, FixedU128};
+use sp_arithmetic::traits::{Bounded, CheckedAdd, CheckedDiv, CheckedMul, Saturating, UniqueSaturatedInto};
+use sp_runtime::Permill;
+use sp_runtime::traits::{AccountIdConversion, Hash};
+use sp_std::vec::Vec;
+
+use crate::{AsksLevels, BidsLevels, DexPriceOracle, Error, LENDING_MODULE_ID, LendingPools, LendingShares, MARGIN_MODULE_ID, MAX_MARGIN_OPEN_ORDERS,
+            MarginAccounts, MarginOpenOrders, Module, Orderbook, Orderbooks, OrderType, PriceLevels, RawEvent, Trait};
+
+/// Lending pool of an asset, its cash is the free balance of the lending account of the asset
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, Default)]
+pub struct LendingPool<BlockNumber> {
+    /// Shares of the lenders in the cash plus total_borrowed of the pool
+    pub total_shares: FixedU128,
+    /// Outstanding loans including the interest accrued so far
+    pub total_borrowed: FixedU128,
+    /// Grows with the interest charged, the debt of a loan is its scaled debt times the index
+    pub borrow_index: FixedU128,
+    /// Block up to which the interest is accrued
+    pub last_accrual: BlockNumber,
+}
+
+/// Loans of an isolated margin account, scaled by the borrow index of the lending pool they were taken from
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MarginAccount {
+    pub base_debt: FixedU128,
+    pub quote_debt: FixedU128,
+}
+
+impl<T: Trait> Module<T> {
+    /// Account holding the collateral and the positions of the margin account of the trader on the TradingPair
+    pub fn margin_account_id(trading_pair: &T::Hash, trader: &T::AccountId) -> T::AccountId {
+        // Only the first bytes of a sub account seed are kept so the TradingPair and trader are hashed into one
+        MARGIN_MODULE_ID.into_sub_account(T::Hashing::hash_of(&(trading_pair, trader)))
+    }
+
+    /// Account holding the cash of the lending pool of the asset
+    pub fn lending_account(asset_id: &T::AssetId) -> T::AccountId {
+        LENDING_MODULE_ID.into_sub_account(asset_id)
+    }
+
+    /// Health factor of the margin account: the value of its holdings times LiquidationLoanToValue over
+    /// the value of its debt. The account can be liquidated below one, None if it has no debt.
+    pub fn margin_health(trading_pair: &T::Hash, trader: &T::AccountId) -> Option<FixedU128> {
+        let (holdings, debt) = Self::margin_values(trading_pair, trader).ok()?;
+        if debt == FixedU128::from(0) {
+            return None;
+        }
+        holdings.saturating_mul(Self::permill_to_fixed(T::LiquidationLoanToValue::get())).checked_div(&debt)
+    }
+
+    #[transactional]
+    pub(crate) fn do_deposit_lending(lender: T::AccountId, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        ensure!(amount > FixedU128::from(0), <Error<T>>::InvalidLendingAmount);
+        Self::ensure_tradable_asset(asset_id)?;
+        let mut pool = Self::accrued_lending_pool(&asset_id).unwrap_or_else(|| LendingPool {
+            total_shares: FixedU128::from(0),
+            total_borrowed: FixedU128::from(0),
+            borrow_index: FixedU128::from(1),
+            last_accrual: <frame_system::Module<T>>::block_number(),
+        });
+        let pool_value = Self::lending_cash(&asset_id)?.checked_add(&pool.total_borrowed).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        let shares = if pool.total_shares == FixedU128::from(0) || pool_value == FixedU128::from(0) {
+            amount
+        } else {
+            let ratio = amount.checked_div(&pool_value).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+            pool.total_shares.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?
+        };
+        ensure!(shares > FixedU128::from(0), <Error<T>>::InvalidLendingAmount);
+
+        Self::transfer_asset_market(asset_id, amount, &lender, &Self::lending_account(&asset_id))?;
+        pool.total_shares = pool.total_shares.checked_add(&shares).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        <LendingShares<T>>::mutate(&asset_id, &lender, |lender_shares| *lender_shares = lender_shares.saturating_add(shares));
+        <LendingPools<T>>::insert(&asset_id, pool);
+        Self::deposit_event(RawEvent::LendingDeposited(asset_id, lender, amount, shares));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_withdraw_lending(lender: T::AccountId, asset_id: T::AssetId, shares: FixedU128) -> Result<(), Error<T>> {
+        let mut pool = Self::accrued_lending_pool(&asset_id).ok_or(<Error<T>>::NoLendingPool)?;
+        let lender_shares = <LendingShares<T>>::get(&asset_id, &lender);
+        ensure!(shares > FixedU128::from(0) && shares <= lender_shares, <Error<T>>::InsufficientLendingShares);
+
+        let cash = Self::lending_cash(&asset_id)?;
+        let pool_value = cash.checked_add(&pool.total_borrowed).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        let ratio = shares.checked_div(&pool.total_shares).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+        let amount = pool_value.checked_mul(&ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+        // Lent out funds come back only when they are repaid
+        ensure!(amount <= cash, <Error<T>>::InsufficientLendingLiquidity);
+
+        Self::transfer_asset_market(asset_id, amount, &Self::lending_account(&asset_id), &lender)?;
+        pool.total_shares = pool.total_shares.saturating_sub(shares);
+        <LendingShares<T>>::insert(&asset_id, &lender, lender_shares.saturating_sub(shares));
+        <LendingPools<T>>::insert(&asset_id, pool);
+        Self::deposit_event(RawEvent::LendingWithdrawn(asset_id, lender, amount, shares));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_deposit_margin(trader: T::AccountId, trading_pair: T::Hash, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+        ensure!(amount > FixedU128::from(0), <Error<T>>::InvalidMarginAmount);
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        ensure!(asset_id == orderbook.base_asset_id || asset_id == orderbook.quote_asset_id, <Error<T>>::AssetNotInTradingPair);
+
+        Self::transfer_asset_market(asset_id, amount, &trader, &Self::margin_account_id(&trading_pair, &trader))?;
+        if !<MarginAccounts<T>>::contains_key(&trading_pair, &trader) {
+            <MarginAccounts<T>>::insert(&trading_pair, &trader, MarginAccount::default());
+        }
+        Self::deposit_event(RawEvent::MarginDeposited(trading_pair, trader, asset_id, amount));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_withdraw_margin(trader: T::AccountId, trading_pair: T::Hash, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        ensure!(<MarginAccounts<T>>::contains_key(&trading_pair, &trader), <Error<T>>::NoMarginAccount);
+        ensure!(amount > FixedU128::from(0), <Error<T>>::InvalidMarginAmount);
+        // Any asset can be withdrawn, e.g. maker rewards paid to the orders of the margin account.
+        // What limit order matches of the block pay to the margin account is only withdrawable once
+        // the block is settled, while the health check already counts it.
+        Self::transfer_asset_market(asset_id, amount, &Self::margin_account_id(&trading_pair, &trader), &trader)?;
+        ensure!(Self::margin_within(&trading_pair, &trader, T::MaxLoanToValue::get())?, <Error<T>>::MarginAccountUnhealthy);
+        Self::deposit_event(RawEvent::MarginWithdrawn(trading_pair, trader, asset_id, amount));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_borrow(trader: T::AccountId, trading_pair: T::Hash, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        let mut account = Self::margin_account(&trading_pair, &trader).ok_or(<Error<T>>::NoMarginAccount)?;
+        ensure!(amount > FixedU128::from(0), <Error<T>>::InvalidMarginAmount);
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let scaled_debt = Self::margin_debt_mut(&mut account, &orderbook, &asset_id)?;
+        let mut pool = Self::accrued_lending_pool(&asset_id).ok_or(<Error<T>>::NoLendingPool)?;
+        ensure!(amount <= Self::lending_cash(&asset_id)?, <Error<T>>::InsufficientLendingLiquidity);
+
+        let scaled_amount = amount.checked_div(&pool.borrow_index).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+        *scaled_debt = scaled_debt.checked_add(&scaled_amount).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        Self::transfer_asset_market(asset_id, amount, &Self::lending_account(&asset_id), &Self::margin_account_id(&trading_pair, &trader))?;
+        pool.total_borrowed = pool.total_borrowed.checked_add(&amount).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        <LendingPools<T>>::insert(&asset_id, pool);
+        <MarginAccounts<T>>::insert(&trading_pair, &trader, account);
+
+        ensure!(Self::margin_within(&trading_pair, &trader, T::MaxLoanToValue::get())?, <Error<T>>::MarginAccountUnhealthy);
+        Self::deposit_event(RawEvent::MarginBorrowed(trading_pair, trader, asset_id, amount));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_repay(trader: T::AccountId, trading_pair: T::Hash, asset_id: T::AssetId, amount: FixedU128) -> Result<(), Error<T>> {
+        let mut account = Self::margin_account(&trading_pair, &trader).ok_or(<Error<T>>::NoMarginAccount)?;
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let repaid = Self::repay_margin_debt(&Self::margin_account_id(&trading_pair, &trader), &mut account, &orderbook, &asset_id, amount)?;
+        <MarginAccounts<T>>::insert(&trading_pair, &trader, account);
+        Self::deposit_event(RawEvent::MarginRepaid(trading_pair, trader, asset_id, repaid));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_submit_margin_order(trader: T::AccountId, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128) -> Result<(), Error<T>> {
+        ensure!(<MarginAccounts<T>>::contains_key(&trading_pair, &trader), <Error<T>>::NoMarginAccount);
+        let margin_account_id = Self::margin_account_id(&trading_pair, &trader);
+        let order_id = Self::next_order_id(&margin_account_id, &order_type, &trading_pair, price, quantity);
+        Self::execute_order(margin_account_id, order_type, trading_pair, price, quantity)?;
+        ensure!(Self::margin_within(&trading_pair, &trader, T::MaxLoanToValue::get())?, <Error<T>>::MarginAccountUnhealthy);
+
+        // Orders filled or cancelled since are dropped, a liquidation cancels the ones that are left
+        let mut open_orders: Vec<(T::Hash, FixedU128)> = Self::margin_open_orders(&trading_pair, &trader).into_iter()
+            .filter(|(order_id, _)| Self::is_open_order(order_id))
+            .collect();
+        if Self::is_open_order(&order_id) {
+            ensure!(open_orders.len() < MAX_MARGIN_OPEN_ORDERS, <Error<T>>::TooManyMarginOrders);
+            open_orders.push((order_id, price));
+        }
+        <MarginOpenOrders<T>>::insert(&trading_pair, &trader, open_orders);
+        Ok(())
+    }
+
+    // Cancels the open orders of the margin account, repays its debt out of its holdings and sells
+    // the other asset at market for any debt that is left. Limit order matches of the block are only
+    // paid to the margin account once the block is settled, the debt they cover is repaid by a later
+    // liquidation, as is the debt the market orders can't cover within MaxLiquidationSlippage of the
+    // mark price. The liquidator is paid LiquidationReward of the value of the debt repaid.
+    #[transactional]
+    pub(crate) fn do_liquidate_margin_account(liquidator: T::AccountId, trading_pair: T::Hash, trader: T::AccountId) -> Result<(), Error<T>> {
+        let mut account = Self::margin_account(&trading_pair, &trader).ok_or(<Error<T>>::NoMarginAccount)?;
+        let health = Self::margin_health(&trading_pair, &trader);
+        ensure!(health.map_or(false, |health| health < FixedU128::from(1)), <Error<T>>::MarginAccountHealthy);
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let mark_price = Self::mark_price(&trading_pair)?;
+        let margin_account_id = Self::margin_account_id(&trading_pair, &trader);
+
+        for (order_id, price) in <MarginOpenOrders<T>>::take(&trading_pair, &trader) {
+            if Self::is_open_order(&order_id) {
+                Self::cancel_order_from_orderbook(margin_account_id.clone(), order_id, trading_pair, price)?;
+            }
+        }
+
+        let all = FixedU128::max_value();
+        let mut base_repaid = Self::repay_margin_debt(&margin_account_id, &mut account, &orderbook, &orderbook.base_asset_id, all)?;
+        let mut quote_repaid = Self::repay_margin_debt(&margin_account_id, &mut account, &orderbook, &orderbook.quote_asset_id, all)?;
+        if account.base_debt > FixedU128::from(0) {
+            let quote_held = Self::free_fixed(&orderbook.quote_asset_id, &margin_account_id)?;
+            let quantity = Self::round_down_to_lot(Self::liquidation_order_size(&trading_pair, OrderType::AskMarket, mark_price, quote_held)?, orderbook.lot_size);
+            if quantity > FixedU128::from(0) {
+                Self::execute_order(margin_account_id.clone(), OrderType::AskMarket, trading_pair, FixedU128::from(0), quantity)?;
+            }
+            base_repaid = base_repaid.saturating_add(Self::repay_margin_debt(&margin_account_id, &mut account, &orderbook, &orderbook.base_asset_id, all)?);
+        }
+        if account.quote_debt > FixedU128::from(0) {
+            let base_held = Self::free_fixed(&orderbook.base_asset_id, &margin_account_id)?;
+            let amount = Self::liquidation_order_size(&trading_pair, OrderType::BidMarket, mark_price, base_held)?;
+            if amount > FixedU128::from(0) {
+                Self::execute_order(margin_account_id.clone(), OrderType::BidMarket, trading_pair, amount, FixedU128::from(0))?;
+            }
+            quote_repaid = quote_repaid.saturating_add(Self::repay_margin_debt(&margin_account_id, &mut account, &orderbook, &orderbook.quote_asset_id, all)?);
+        }
+        <MarginAccounts<T>>::insert(&trading_pair, &trader, account);
+
+        let repaid_value = base_repaid.saturating_add(quote_repaid.saturating_mul(mark_price));
+        Self::pay_liquidation_reward(&orderbook, &margin_account_id, &liquidator, repaid_value, mark_price)?;
+        Self::deposit_event(RawEvent::MarginLiquidated(trading_pair, trader, liquidator));
+        Ok(())
+    }
+
+    // Part of `held` a market order of a liquidation can spend without filling beyond MaxLiquidationSlippage
+    // of the mark price: quote asset to sell for an AskMarket and base asset to spend for a BidMarket order.
+    // The pool fills the order up to the best price of the book first, then the book within the bound.
+    fn liquidation_order_size(trading_pair: &T::Hash, order_type: OrderType, mark_price: FixedU128, held: FixedU128) -> Result<FixedU128, Error<T>> {
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+        let slippage = mark_price.saturating_mul(Self::permill_to_fixed(T::MaxLiquidationSlippage::get()));
+        let reserves = Self::pool_reserves(&orderbook);
+        let mut size = FixedU128::from(0);
+        if order_type == OrderType::AskMarket {
+            let floor = mark_price.saturating_sub(slippage);
+            let pool_floor = orderbook.best_bid_price.max(floor);
+            if let Some((base_reserve, quote_reserve)) = reserves {
+                let pool_price = base_reserve.checked_div(&quote_reserve).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+                if pool_floor == FixedU128::from(0) {
+                    return Ok(held);
+                }
+                if pool_price > pool_floor {
+                    let target_ratio = FixedU128::from(1).checked_div(&pool_floor).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+                    size = Self::pool_amount_in_to_ratio(quote_reserve, base_reserve, target_ratio).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                }
+            }
+            for price in <BidsLevels<T>>::get(trading_pair).into_iter().rev() {
+                if size >= held || price < floor {
+                    break;
+                }
+                let quantity = <PriceLevels<T>>::get(trading_pair, price).orders.iter()
+                    .fold(FixedU128::from(0), |total, order| total.saturating_add(order.quantity));
+                size = size.saturating_add(quantity);
+            }
+        } else {
+            let ceiling = mark_price.saturating_add(slippage);
+            let pool_ceiling = if orderbook.best_ask_price == FixedU128::from(0) { ceiling } else { orderbook.best_ask_price.min(ceiling) };
+            if let Some((base_reserve, quote_reserve)) = reserves {
+                let pool_price = base_reserve.checked_div(&quote_reserve).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+                if pool_price < pool_ceiling {
+                    size = Self::pool_amount_in_to_ratio(base_reserve, quote_reserve, pool_ceiling).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+                }
+            }
+            for price in <AsksLevels<T>>::get(trading_pair) {
+                if size >= held || price > ceiling {
+                    break;
+                }
+                let amount = <PriceLevels<T>>::get(trading_pair, price).orders.iter()
+                    .fold(FixedU128::from(0), |total, order| total.saturating_add(price.saturating_mul(order.quantity)));
+                size = size.saturating_add(amount);
+            }
+        }
+        Ok(size.min(held))
+    }
+
+    // Pays the liquidator its reward worth `value` of the base asset out of the base asset left in the
+    // margin account, and out of its quote asset at the mark price for what the base asset can't cover
+    fn pay_liquidation_reward(orderbook: &Orderbook<T>, margin_account_id: &T::AccountId, liquidator: &T::AccountId, value: FixedU128, mark_price: FixedU128) -> Result<(), Error<T>> {
+        let mut reward = value.saturating_mul(Self::permill_to_fixed(T::LiquidationReward::get()));
+        for (asset_id, price) in [(orderbook.base_asset_id, FixedU128::from(1)), (orderbook.quote_asset_id, mark_price)].iter() {
+            if reward == FixedU128::from(0) {
+                break;
+            }
+            let held = Self::free_fixed(asset_id, margin_account_id)?;
+            let amount = reward.checked_div(price).ok_or(<Error<T>>::DivUnderflowOrOverflow)?.min(held);
+            let amount = Self::round_to_asset_precision(asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+            if amount == FixedU128::from(0) {
+                continue;
+            }
+            Self::transfer_asset_market(*asset_id, amount, margin_account_id, liquidator)?;
+            reward = reward.saturating_sub(amount.saturating_mul(*price));
+            Self::deposit_event(RawEvent::LiquidationRewardPaid(orderbook.trading_pair, liquidator.clone(), *asset_id, amount));
+        }
+        Ok(())
+    }
+
+    // Repays up to amount of the margin account's debt in the asset out of its free balance, returns the amount repaid
+    fn repay_margin_debt(margin_account_id: &T::AccountId, account: &mut MarginAccount, orderbook: &Orderbook<T>, asset_id: &T::AssetId, amount: FixedU128) -> Result<FixedU128, Error<T>> {
+        let scaled_debt = Self::margin_debt_mut(account, orderbook, asset_id)?;
+        if *scaled_debt == FixedU128::from(0) {
+            return Ok(FixedU128::from(0));
+        }
+        let mut pool = Self::accrued_lending_pool(asset_id).ok_or(<Error<T>>::NoLendingPool)?;
+        let debt = scaled_debt.checked_mul(&pool.borrow_index).ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+        let held = Self::free_fixed(asset_id, margin_account_id)?;
+        let repaid = Self::round_to_asset_precision(asset_id, amount.min(debt).min(held)).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        if repaid == FixedU128::from(0) {
+            return Ok(repaid);
+        }
+
+        Self::transfer_asset_market(*asset_id, repaid, margin_account_id, &Self::lending_account(asset_id))?;
+        // Debt below the precision of the asset can't be paid so it is cleared with the rest
+        if Self::round_to_asset_precision(asset_id, debt) == Some(repaid) {
+            *scaled_debt = FixedU128::from(0);
+        } else {
+            let scaled_repaid = repaid.checked_div(&pool.borrow_index).ok_or(<Error<T>>::DivUnderflowOrOverflow)?;
+            *scaled_debt = scaled_debt.saturating_sub(scaled_repaid);
+        }
+        pool.total_borrowed = pool.total_borrowed.saturating_sub(repaid);
+        <LendingPools<T>>::insert(asset_id, pool);
+        Ok(repaid)
+    }
+
+    // Scaled debt of the margin account in the asset, which must be the base or quote asset of the orderbook
+    fn margin_debt_mut<'a>(account: &'a mut MarginAccount, orderbook: &Orderbook<T>, asset_id: &T::AssetId) -> Result<&'a mut FixedU128, Error<T>> {
+        if *asset_id == orderbook.base_asset_id {
+            Ok(&mut account.base_debt)
+        } else if *asset_id == orderbook.quote_asset_id {
+            Ok(&mut account.quote_debt)
+        } else {
+            Err(<Error<T>>::AssetNotInTradingPair)
+        }
+    }
+
+    // Whether the debt of the margin account is at most the given part of the value of its holdings
+    fn margin_within(trading_pair: &T::Hash, trader: &T::AccountId, loan_to_value: Permill) -> Result<bool, Error<T>> {
+        let (holdings, debt) = Self::margin_values(trading_pair, trader)?;
+        Ok(debt <= holdings.saturating_mul(Self::permill_to_fixed(loan_to_value)))
+    }
+
+    // Value of the holdings and of the debt of the margin account in the base asset. Balances reserved
+    // for open orders are part of the holdings.
+    fn margin_values(trading_pair: &T::Hash, trader: &T::AccountId) -> Result<(FixedU128, FixedU128), Error<T>> {
+        let account = Self::margin_account(trading_pair, trader).ok_or(<Error<T>>::NoMarginAccount)?;
+        if account.base_debt == FixedU128::from(0) && account.quote_debt == FixedU128::from(0) {
+            return Ok((FixedU128::from(0), FixedU128::from(0)));
+        }
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(trading_pair);
+        let price = Self::mark_price(trading_pair)?;
+
+        let margin_account_id = Self::margin_account_id(trading_pair, trader);
+        let base_held = Self::convert_balance_to_fixed_u128(&orderbook.base_asset_id,
+                                                            pallet_generic_asset::Module::<T>::total_balance(&orderbook.base_asset_id, &margin_account_id))
+            .ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        let quote_held = Self::convert_balance_to_fixed_u128(&orderbook.quote_asset_id,
+                                                             pallet_generic_asset::Module::<T>::total_balance(&orderbook.quote_asset_id, &margin_account_id))
+            .ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        // Limit order matches of the current block are not settled yet
+        let base_pending = Self::pending_settlement(&margin_account_id, &orderbook.base_asset_id);
+        let quote_pending = Self::pending_settlement(&margin_account_id, &orderbook.quote_asset_id);
+        let base_held = base_held.saturating_add(base_pending.credit).saturating_sub(base_pending.reserved_debit);
+        let quote_held = quote_held.saturating_add(quote_pending.credit).saturating_sub(quote_pending.reserved_debit);
+        let base_debt = account.base_debt.saturating_mul(Self::current_borrow_index(&orderbook.base_asset_id));
+        let quote_debt = account.quote_debt.saturating_mul(Self::current_borrow_index(&orderbook.quote_asset_id));
+
+        let holdings = base_held.saturating_add(quote_held.saturating_mul(price));
+        let debt = base_debt.saturating_add(quote_debt.saturating_mul(price));
+        Ok((holdings, debt))
+    }
+
+    // Price margin accounts are valued at. A time weighted average can't be moved by a single trade the
+    // way the last trade price can.
+    fn mark_price(trading_pair: &T::Hash) -> Result<FixedU128, Error<T>> {
+        <Self as DexPriceOracle<T::Hash, T::BlockNumber>>::twap(*trading_pair, T::MarginPriceWindow::get())
+            .filter(|price| *price > FixedU128::from(0))
+            .ok_or(<Error<T>>::NoMarkPrice)
+    }
+
+    // Borrow index of the lending pool of the asset including the interest not accrued yet
+    fn current_borrow_index(asset_id: &T::AssetId) -> FixedU128 {
+        Self::accrued_lending_pool(asset_id).map_or(FixedU128::from(1), |pool| pool.borrow_index)
+    }
+
+    // Lending pool of the asset with the interest accrued up to the current block. The yearly rate is
+    // BaseBorrowRate plus BorrowRateSlope times the part of the pool that is borrowed.
+    fn accrued_lending_pool(asset_id: &T::AssetId) -> Option<LendingPool<T::BlockNumber>> {
+        let mut pool = Self::lending_pool(asset_id)?;
+        let now = <frame_system::Module<T>>::block_number();
+        let blocks: u128 = now.saturating_sub(pool.last_accrual).unique_saturated_into();
+        pool.last_accrual = now;
+        if blocks == 0 || pool.total_borrowed == FixedU128::from(0) {
+            return Some(pool);
+        }
+        let pool_value = Self::lending_cash(asset_id).ok()?.checked_add(&pool.total_borrowed)?;
+        let utilisation = pool.total_borrowed.checked_div(&pool_value)?;
+        let yearly_rate = Self::permill_to_fixed(T::BaseBorrowRate::get())
+            .saturating_add(Self::permill_to_fixed(T::BorrowRateSlope::get()).saturating_mul(utilisation));
+        let blocks_per_year: u128 = T::BlocksPerYear::get().unique_saturated_into();
+        let interest = yearly_rate.checked_mul(&FixedU128::saturating_from_rational(blocks, blocks_per_year.max(1)))?;
+        let growth = FixedU128::from(1).checked_add(&interest)?;
+        pool.borrow_index = pool.borrow_index.checked_mul(&growth)?;
+        pool.total_borrowed = pool.total_borrowed.checked_mul(&growth)?;
+        Some(pool)
+    }
+
+    // Funds of the lending pool of the asset that are not lent out
+    fn lending_cash(asset_id: &T::AssetId) -> Result<FixedU128, Error<T>> {
+        Self::free_fixed(asset_id, &Self::lending_account(asset_id))
+    }
+
+    fn free_fixed(asset_id: &T::AssetId, who: &T::AccountId) -> Result<FixedU128, Error<T>> {
+        Self::convert_balance_to_fixed_u128(asset_id, pallet_generic_asset::Module::<T>::free_balance(asset_id, who))
+            .ok_or(<Error<T>>::InternalErrorU128Balance)
+    }
+
+    pub(crate) fn permill_to_fixed(part: Permill) -> FixedU128 {
+        FixedU128::saturating_from_rational(part.deconstruct(), 1_000_000u32)
+    }
+}
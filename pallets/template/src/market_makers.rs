@@ -0,0 +1,170 @@
+//! Designated market makers of a TradingPair and the quoting obligations they are measured against.
+//!
+//! Every block, a registered maker is counted as quoting if it has a resting bid and a resting ask of
+//! at least min_quantity each, both within max_spread of the mid price and at most max_spread of the
+//! mid price apart. When the reward period changes, makers that quoted for at least min_uptime of the
+//! blocks measured since they were registered are paid the reward of the TradingPair out of the maker
+//! reward account, the others lose their status and must be registered again.
+
+use codec::{Decode, Encode};
+use frame_support::ensure;
+use frame_support::storage::{IterableStorageDoubleMap, IterableStorageMap, StorageDoubleMap, StorageMap};
+use frame_support::traits::Get;
+use frame_support::weights::Weight;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::Saturating;
+use sp_runtime::Permill;
+use sp_std::collections::btree_map::BTreeMap;
+use sp_std::vec::Vec;
+
+use crate::{Error, LinkedPriceLevel, MarketMakerMeasuredBlocks, MarketMakerObligations, MarketMakerRegisteredAt, MarketMakers,
+            Module, Orderbook, Orderbooks, PriceLevels, RawEvent, Trait};
+
+/// Quoting obligations of the designated market makers of a TradingPair and their reward
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct MarketMakerObligation<AssetId> {
+    /// Widest distance between a maker's bid and ask, and from either of them to the mid price, as a part of the mid price
+    pub max_spread: Permill,
+    /// Smallest quantity of the quoting bid and ask
+    pub min_quantity: FixedU128,
+    /// Smallest part of the measured blocks of a reward period in which a maker must quote
+    pub min_uptime: Permill,
+    pub reward_asset_id: AssetId,
+    /// Paid to every maker that meets the obligations, as long as the maker reward account can afford it
+    pub reward_per_period: FixedU128,
+}
+
+impl<T: Trait> Module<T> {
+    pub(crate) fn do_set_market_maker_obligation(trading_pair: T::Hash, obligation: MarketMakerObligation<T::AssetId>) -> Result<(), Error<T>> {
+        ensure!(<Orderbooks<T>>::contains_key(&trading_pair), <Error<T>>::InvalidTradingPair);
+        ensure!(Self::asset_exists(&obligation.reward_asset_id), <Error<T>>::AssetDoesNotExist);
+        <MarketMakerObligations<T>>::insert(&trading_pair, obligation);
+        Self::deposit_event(RawEvent::MarketMakerObligationSet(trading_pair));
+        Ok(())
+    }
+
+    pub(crate) fn do_end_market_maker_program(trading_pair: T::Hash) -> Result<(), Error<T>> {
+        ensure!(<MarketMakerObligations<T>>::contains_key(&trading_pair), <Error<T>>::NoMarketMakerObligation);
+        <MarketMakerObligations<T>>::remove(&trading_pair);
+        <MarketMakers<T>>::remove_prefix(&trading_pair);
+        <MarketMakerRegisteredAt<T>>::remove_prefix(&trading_pair);
+        <MarketMakerMeasuredBlocks<T>>::remove(&trading_pair);
+        Self::deposit_event(RawEvent::MarketMakerProgramEnded(trading_pair));
+        Ok(())
+    }
+
+    pub(crate) fn do_register_market_maker(trading_pair: T::Hash, maker: T::AccountId) -> Result<(), Error<T>> {
+        ensure!(<MarketMakerObligations<T>>::contains_key(&trading_pair), <Error<T>>::NoMarketMakerObligation);
+        ensure!(!<MarketMakers<T>>::contains_key(&trading_pair, &maker), <Error<T>>::AlreadyMarketMaker);
+        <MarketMakers<T>>::insert(&trading_pair, &maker, 0);
+        // The blocks of the period measured before the registration don't count against the maker
+        <MarketMakerRegisteredAt<T>>::insert(&trading_pair, &maker, <MarketMakerMeasuredBlocks<T>>::get(&trading_pair));
+        Self::deposit_event(RawEvent::MarketMakerRegistered(trading_pair, maker));
+        Ok(())
+    }
+
+    pub(crate) fn do_remove_market_maker(trading_pair: T::Hash, maker: T::AccountId) -> Result<(), Error<T>> {
+        ensure!(<MarketMakers<T>>::contains_key(&trading_pair, &maker), <Error<T>>::NotMarketMaker);
+        <MarketMakers<T>>::remove(&trading_pair, &maker);
+        <MarketMakerRegisteredAt<T>>::remove(&trading_pair, &maker);
+        Self::deposit_event(RawEvent::MarketMakerRemoved(trading_pair, maker));
+        Ok(())
+    }
+
+    // Rewards the makers that met the obligations of the ending reward period, the others lose their
+    // status, and starts measuring the next period from zero
+    pub(crate) fn settle_market_makers() -> Weight {
+        let reward_account = Self::maker_reward_account();
+        let mut reads: Weight = 0;
+        let mut writes: Weight = 0;
+        for (trading_pair, obligation) in <MarketMakerObligations<T>>::iter() {
+            let measured_blocks = <MarketMakerMeasuredBlocks<T>>::take(&trading_pair);
+            let makers: Vec<(T::AccountId, u32)> = <MarketMakers<T>>::iter_prefix(&trading_pair).collect();
+            reads = reads.saturating_add(2 + makers.len() as Weight);
+            writes = writes.saturating_add(1);
+            // Nothing can be measured without both sides of the book, that isn't held against the makers
+            if measured_blocks == 0 {
+                continue;
+            }
+            for (maker, quoted_blocks) in makers {
+                let registered_at = <MarketMakerRegisteredAt<T>>::take(&trading_pair, &maker);
+                let measured_since = measured_blocks.saturating_sub(registered_at);
+                reads = reads.saturating_add(1);
+                writes = writes.saturating_add(2);
+                if measured_since == 0 {
+                    // Registered after the last measured block, the maker is judged in the next period
+                    <MarketMakers<T>>::insert(&trading_pair, &maker, 0);
+                    continue;
+                }
+                if Permill::from_rational_approximation(quoted_blocks, measured_since) < obligation.min_uptime {
+                    <MarketMakers<T>>::remove(&trading_pair, &maker);
+                    Self::deposit_event(RawEvent::MarketMakerStatusLost(trading_pair, maker));
+                    continue;
+                }
+                <MarketMakers<T>>::insert(&trading_pair, &maker, 0);
+                let available = Self::convert_balance_to_fixed_u128(&obligation.reward_asset_id,
+                                                                    pallet_generic_asset::Module::<T>::free_balance(&obligation.reward_asset_id, &reward_account))
+                    .unwrap_or_else(|| FixedU128::from(0));
+                let amount = obligation.reward_per_period.min(available);
+                reads = reads.saturating_add(1);
+                if amount == FixedU128::from(0) {
+                    continue;
+                }
+                if Self::transfer_asset_market(obligation.reward_asset_id, amount, &reward_account, &maker).is_ok() {
+                    writes = writes.saturating_add(2);
+                    Self::deposit_event(RawEvent::MarketMakerRewarded(trading_pair, maker, obligation.reward_asset_id, amount));
+                }
+            }
+        }
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+
+    // Counts the block for every TradingPair with both sides of the book and for every maker quoting in it
+    pub(crate) fn measure_market_makers() -> Weight {
+        let mut reads: Weight = 0;
+        let mut writes: Weight = 0;
+        for (trading_pair, obligation) in <MarketMakerObligations<T>>::iter() {
+            let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+            reads = reads.saturating_add(2);
+            let (distance, bids_levels, asks_levels) = match Self::levels_near_mid_price(&orderbook, obligation.max_spread) {
+                Some(levels) => levels,
+                None => continue,
+            };
+            <MarketMakerMeasuredBlocks<T>>::mutate(&trading_pair, |blocks| *blocks = blocks.saturating_add(1));
+            writes = writes.saturating_add(1);
+
+            // Best qualifying bid and ask of every maker within the distance of the mid price
+            let mut bids: BTreeMap<T::AccountId, FixedU128> = BTreeMap::new();
+            let mut asks: BTreeMap<T::AccountId, FixedU128> = BTreeMap::new();
+            for price in bids_levels {
+                let price_level: LinkedPriceLevel<T> = <PriceLevels<T>>::get(&trading_pair, price);
+                reads = reads.saturating_add(1);
+                for order in price_level.orders.iter().filter(|order| order.quantity >= obligation.min_quantity) {
+                    bids.entry(order.trader.clone()).or_insert(price);
+                }
+            }
+            for price in asks_levels {
+                let price_level: LinkedPriceLevel<T> = <PriceLevels<T>>::get(&trading_pair, price);
+                reads = reads.saturating_add(1);
+                for order in price_level.orders.iter().filter(|order| order.quantity >= obligation.min_quantity) {
+                    asks.entry(order.trader.clone()).or_insert(price);
+                }
+            }
+            reads = reads.saturating_add(2);
+
+            for (maker, bid_price) in bids {
+                let quoting = asks.get(&maker).map_or(false, |ask_price| ask_price.saturating_sub(bid_price) <= distance);
+                if quoting && <MarketMakers<T>>::contains_key(&trading_pair, &maker) {
+                    <MarketMakers<T>>::mutate(&trading_pair, &maker, |quoted_blocks| {
+                        if let Some(quoted_blocks) = quoted_blocks {
+                            *quoted_blocks = quoted_blocks.saturating_add(1);
+                        }
+                    });
+                    reads = reads.saturating_add(1);
+                    writes = writes.saturating_add(1);
+                }
+            }
+        }
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+}
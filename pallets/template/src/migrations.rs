@@ -0,0 +1,267 @@
+//! Storage migrations of the DEX pallet.
+//!
+//! Every change to the encoding of a stored struct bumps `Releases` and adds a module here that
+//! translates the storage of the previous release. `migrate` runs the pending migrations in order
+//! from `on_runtime_upgrade`, so a chain can skip several releases in one upgrade.
+
+use frame_support::weights::Weight;
+use frame_support::storage::{IterableStorageDoubleMap, IterableStorageMap, StorageValue};
+use frame_support::traits::Get;
+
+use crate::{Releases, StorageVersion, Trait};
+
+/// Storage version written at genesis and reached after all migrations have run.
+pub const LATEST_RELEASE: Releases = Releases::V8_0_0;
+
+/// Runs all the migrations needed to bring the storage from its current version to LATEST_RELEASE.
+pub fn migrate<T: Trait>() -> Weight {
+    let mut weight: Weight = T::DbWeight::get().reads(1);
+    let mut version = StorageVersion::get();
+
+    if version == Releases::V1_0_0 {
+        weight = weight.saturating_add(v2::migrate::<T>());
+        version = Releases::V2_0_0;
+    }
+    if version == Releases::V2_0_0 {
+        weight = weight.saturating_add(v3::migrate::<T>());
+        version = Releases::V3_0_0;
+    }
+    if version == Releases::V3_0_0 {
+        weight = weight.saturating_add(v4::migrate::<T>());
+        version = Releases::V4_0_0;
+    }
+    if version == Releases::V4_0_0 {
+        weight = weight.saturating_add(v5::migrate::<T>());
+        version = Releases::V5_0_0;
+    }
+    if version == Releases::V5_0_0 {
+        weight = weight.saturating_add(v6::migrate::<T>());
+        version = Releases::V6_0_0;
+    }
+    if version == Releases::V6_0_0 {
+        weight = weight.saturating_add(v7::migrate::<T>());
+        version = Releases::V7_0_0;
+    }
+    if version == Releases::V7_0_0 {
+        weight = weight.saturating_add(v8::migrate::<T>());
+        version = Releases::V8_0_0;
+    }
+
+    if version != StorageVersion::get() {
+        StorageVersion::put(version);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    weight
+}
+
+/// V1_0_0 -> V2_0_0: adds tick_size and lot_size to Orderbook, existing books accept any price and quantity.
+pub mod v2 {
+    use codec::{Decode, Encode};
+    use sp_arithmetic::FixedU128;
+    use sp_std::cell::Cell;
+
+    use super::*;
+    use crate::{Orderbook, Orderbooks};
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct OldOrderbook<T> where T: Trait {
+        pub trading_pair: T::Hash,
+        pub base_asset_id: T::AssetId,
+        pub quote_asset_id: T::AssetId,
+        pub best_bid_price: FixedU128,
+        pub best_ask_price: FixedU128,
+    }
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let translated: Cell<Weight> = Cell::new(0);
+        <Orderbooks<T>>::translate::<OldOrderbook<T>, _>(|_trading_pair, old| {
+            translated.set(translated.get() + 1);
+            Some(Orderbook {
+                trading_pair: old.trading_pair,
+                base_asset_id: old.base_asset_id,
+                quote_asset_id: old.quote_asset_id,
+                best_bid_price: old.best_bid_price,
+                best_ask_price: old.best_ask_price,
+                tick_size: FixedU128::from(0),
+                lot_size: FixedU128::from(0),
+            })
+        });
+        T::DbWeight::get().reads_writes(translated.get(), translated.get())
+    }
+}
+
+/// V2_0_0 -> V3_0_0: adds reserved to Order, resting orders are assumed to hold their full remaining cost.
+pub mod v3 {
+    use codec::{Decode, Encode};
+    use sp_arithmetic::FixedU128;
+    use sp_std::cell::Cell;
+    use sp_std::collections::vec_deque::VecDeque;
+
+    use super::*;
+    use crate::{LinkedPriceLevel, Order, OrderType, PriceLevels};
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct OldOrder<T> where T: Trait {
+        pub id: T::Hash,
+        pub trading_pair: T::Hash,
+        pub trader: T::AccountId,
+        pub price: FixedU128,
+        pub quantity: FixedU128,
+        pub order_type: OrderType,
+    }
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct OldLinkedPriceLevel<T> where T: Trait {
+        pub next: Option<FixedU128>,
+        pub prev: Option<FixedU128>,
+        pub orders: VecDeque<OldOrder<T>>,
+    }
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let translated: Cell<Weight> = Cell::new(0);
+        <PriceLevels<T>>::translate::<OldLinkedPriceLevel<T>, _>(|_trading_pair, _price, old| {
+            translated.set(translated.get() + 1);
+            let orders = old.orders.into_iter().map(|order| {
+                let reserved = match order.order_type {
+                    OrderType::AskLimit => order.quantity,
+                    _ => order.price.saturating_mul(order.quantity),
+                };
+                Order {
+                    id: order.id,
+                    trading_pair: order.trading_pair,
+                    trader: order.trader,
+                    price: order.price,
+                    quantity: order.quantity,
+                    order_type: order.order_type,
+                    reserved,
+                }
+            }).collect();
+            Some(LinkedPriceLevel {
+                next: old.next,
+                prev: old.prev,
+                orders,
+            })
+        });
+        T::DbWeight::get().reads_writes(translated.get(), translated.get())
+    }
+}
+
+/// V3_0_0 -> V4_0_0: fills the TradingPairs index from the registered Orderbooks. Two Orderbooks of the same
+/// assets with quote and base swapped were allowed before, the index keeps the first one and reports the other.
+pub mod v4 {
+    use frame_support::debug;
+    use frame_support::storage::StorageDoubleMap;
+
+    use super::*;
+    use crate::{Module, Orderbooks, RawEvent, TradingPairs};
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let (mut read, mut indexed): (Weight, Weight) = (0, 0);
+        for (trading_pair, orderbook) in <Orderbooks<T>>::iter() {
+            read += 2;
+            if let Some(reversed_pair) = <TradingPairs<T>>::get(orderbook.quote_asset_id, orderbook.base_asset_id) {
+                debug::error!("TradingPair {:?} is registered with quote and base swapped as {:?}, it is not indexed", trading_pair, reversed_pair);
+                Module::<T>::deposit_event(RawEvent::ReversedTradingPairNotIndexed(reversed_pair, trading_pair));
+                continue;
+            }
+            <TradingPairs<T>>::insert(orderbook.base_asset_id, orderbook.quote_asset_id, trading_pair);
+            indexed += 1;
+        }
+        T::DbWeight::get().reads_writes(read, indexed)
+    }
+}
+
+/// V4_0_0 -> V5_0_0: whitelists the assets of the Orderbooks registered before orders were only accepted on
+/// pairs of whitelisted assets. An asset that has decimals was whitelisted before and may have been removed
+/// from the whitelist since, so it is left as is; the others keep the default decimals their balances were
+/// converted with so far.
+pub mod v5 {
+    use frame_support::storage::StorageMap;
+
+    use super::*;
+    use crate::{AssetDecimals, Module, Orderbooks, WhitelistedAssets};
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let (mut read, mut whitelisted): (Weight, Weight) = (0, 0);
+        for (_trading_pair, orderbook) in <Orderbooks<T>>::iter() {
+            for asset_id in [orderbook.base_asset_id, orderbook.quote_asset_id].iter() {
+                read += 1;
+                if <AssetDecimals<T>>::contains_key(asset_id) {
+                    continue;
+                }
+                <AssetDecimals<T>>::insert(asset_id, Module::<T>::decimals_of(asset_id));
+                <WhitelistedAssets<T>>::insert(asset_id, true);
+                whitelisted += 1;
+            }
+        }
+        T::DbWeight::get().reads_writes(read, whitelisted.saturating_mul(2))
+    }
+}
+
+/// V5_0_0 -> V6_0_0: fills the MarketInfoPairs index from the MarketInfo not pruned yet, pruning only visits
+/// the trading pairs indexed for a block.
+pub mod v6 {
+    use frame_support::storage::StorageMap;
+
+    use super::*;
+    use crate::{MarketInfo, MarketInfoPairs};
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let mut indexed: Weight = 0;
+        for (trading_pair, block_number, _market_data) in <MarketInfo<T>>::iter() {
+            <MarketInfoPairs<T>>::mutate(block_number, |trading_pairs| trading_pairs.push(trading_pair));
+            indexed += 1;
+        }
+        T::DbWeight::get().reads_writes(indexed.saturating_mul(2), indexed)
+    }
+}
+
+/// V6_0_0 -> V7_0_0: records the orders resting in PriceLevels and queued in PendingOrders since before
+/// OrderRecords was added. What they were submitted with is unknown, their remaining quantity is used instead.
+pub mod v7 {
+    use frame_support::storage::StorageMap;
+
+    use super::*;
+    use crate::{Order, OrderRecord, OrderRecords, OrderStatus, PendingOrders, PriceLevels};
+
+    pub fn migrate<T: Trait>() -> Weight {
+        let (mut read, mut recorded): (Weight, Weight) = (0, 0);
+        let mut record = |order: &Order<T>| {
+            read += 1;
+            if <OrderRecords<T>>::contains_key(&order.id) {
+                return;
+            }
+            <OrderRecords<T>>::insert(&order.id, OrderRecord {
+                trading_pair: order.trading_pair,
+                trader: order.trader.clone(),
+                order_type: order.order_type.clone(),
+                price: order.price,
+                quantity: order.quantity,
+                remaining: order.quantity,
+                status: OrderStatus::Open,
+                closed_at: None,
+            });
+            recorded += 1;
+        };
+        for (_trading_pair, _price, linked_pricelevel) in <PriceLevels<T>>::iter() {
+            linked_pricelevel.orders.iter().for_each(&mut record);
+        }
+        for (_trading_pair, pending) in <PendingOrders<T>>::iter() {
+            pending.iter().for_each(&mut record);
+        }
+        T::DbWeight::get().reads_writes(read, recorded)
+    }
+}
+
+/// V7_0_0 -> V8_0_0: starts CurrentRewardPeriod at the current RewardPeriodIndex. It was zero until the
+/// first period change, so the first block after the upgrade would have paid out a reward period early.
+pub mod v8 {
+    use super::*;
+    use crate::CurrentRewardPeriod;
+
+    pub fn migrate<T: Trait>() -> Weight {
+        CurrentRewardPeriod::put(T::RewardPeriodIndex::get());
+        T::DbWeight::get().writes(1)
+    }
+}
@@ -1,11 +1,13 @@
-use frame_support::{impl_outer_origin, parameter_types, weights::Weight};
+use std::cell::RefCell;
+
+use frame_support::{impl_outer_origin, parameter_types, traits::Get, weights::Weight};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
-    Perbill, testing::Header, traits::{BlakeTwo256, IdentityLookup},
+    Perbill, Permill, testing::{Header, TestSignature, TestXt, UintAuthorityId}, traits::{BlakeTwo256, IdentityLookup}, transaction_validity::TransactionPriority,
 };
 
-use crate::{Module, Trait};
+use crate::{Call, IntegrityIssue, Module, Trait};
 
 impl_outer_origin! {
 	pub enum Origin for Test {}
@@ -13,7 +15,7 @@ impl_outer_origin! {
 
 // Configure a mock runtime to test the pallet.
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Test;
 parameter_types! {
 	pub const BlockHashCount: u64 = 250;
@@ -52,11 +54,95 @@ impl system::Trait for Test {
 
 parameter_types! {
 pub const TradingPairReservationFee: u128 = 1_000_000_000_000;
+pub const MarketDataRetention: u64 = 100;
+pub const MarketDataRollupPeriod: u64 = 10;
+pub const MaxMarketDataPrunedPerBlock: u32 = 5;
+pub const TickerWindow: u64 = 100;
+pub const TickerBucketPeriod: u64 = 10;
+pub const OracleObservationPeriod: u64 = 10;
+pub const OracleMaxObservations: u32 = 10;
+pub const PoolSwapFee: Permill = Permill::from_perthousand(3);
+pub const MakerRewardSpread: Permill = Permill::from_percent(5);
+pub const BlocksPerYear: u64 = 1000;
+pub const BaseBorrowRate: Permill = Permill::from_percent(2);
+pub const BorrowRateSlope: Permill = Permill::from_percent(20);
+pub const MaxLoanToValue: Permill = Permill::from_percent(75);
+pub const LiquidationLoanToValue: Permill = Permill::from_percent(80);
+pub const MarginPriceWindow: u64 = 10;
+pub const MaxLiquidationSlippage: Permill = Permill::from_percent(10);
+pub const LiquidationReward: Permill = Permill::from_percent(5);
+pub const MaxOffchainBatchOrders: u32 = 10;
+pub const OffchainBatchPriority: TransactionPriority = 1 << 20;
+pub const MaxSignedOrdersPerBatch: u32 = 10;
+pub const RevealWindow: u64 = 3;
+pub const CommitmentDeposit: u128 = 1_000_000_000_000;
+pub const MaxRevealsPerBlock: u32 = 2;
+pub const ReferralFeeShare: Permill = Permill::from_percent(20);
+pub const OrderHistoryRetention: u64 = 10;
+pub const MaxSettlementsPerBlock: u32 = 20;
+}
+
+thread_local! {
+    static REWARD_PERIOD: RefCell<u32> = RefCell::new(0);
+}
+
+// Reward period index the tests can move forward, stands in for the session index of the runtime
+pub struct RewardPeriodIndex;
+
+impl RewardPeriodIndex {
+    pub fn set(period: u32) {
+        REWARD_PERIOD.with(|v| *v.borrow_mut() = period);
+    }
+}
+
+impl Get<u32> for RewardPeriodIndex {
+    fn get() -> u32 {
+        REWARD_PERIOD.with(|v| *v.borrow())
+    }
 }
 
 impl Trait for Test {
     type Event = ();
     type TradingPairReservationFee = TradingPairReservationFee;
+    type MarketDataRetention = MarketDataRetention;
+    type MarketDataRollupPeriod = MarketDataRollupPeriod;
+    type MaxMarketDataPrunedPerBlock = MaxMarketDataPrunedPerBlock;
+    type TickerWindow = TickerWindow;
+    type TickerBucketPeriod = TickerBucketPeriod;
+    type OracleObservationPeriod = OracleObservationPeriod;
+    type OracleMaxObservations = OracleMaxObservations;
+    type RegisterPairOrigin = system::EnsureRoot<u64>;
+    type RepairOrigin = system::EnsureRoot<u64>;
+    type PoolSwapFee = PoolSwapFee;
+    type RewardPeriodIndex = RewardPeriodIndex;
+    type MakerRewardSpread = MakerRewardSpread;
+    type BlocksPerYear = BlocksPerYear;
+    type BaseBorrowRate = BaseBorrowRate;
+    type BorrowRateSlope = BorrowRateSlope;
+    type MaxLoanToValue = MaxLoanToValue;
+    type LiquidationLoanToValue = LiquidationLoanToValue;
+    type MarginPriceWindow = MarginPriceWindow;
+    type MaxLiquidationSlippage = MaxLiquidationSlippage;
+    type LiquidationReward = LiquidationReward;
+    type MaxOffchainBatchOrders = MaxOffchainBatchOrders;
+    type OffchainBatchPriority = OffchainBatchPriority;
+    type OrderSignature = TestSignature;
+    type OrderSigner = UintAuthorityId;
+    type MaxSignedOrdersPerBatch = MaxSignedOrdersPerBatch;
+    type RevealWindow = RevealWindow;
+    type CommitmentDeposit = CommitmentDeposit;
+    type MaxRevealsPerBlock = MaxRevealsPerBlock;
+    type Randomness = ();
+    type ReferralFeeShare = ReferralFeeShare;
+    type OrderHistoryRetention = OrderHistoryRetention;
+    type MaxSettlementsPerBlock = MaxSettlementsPerBlock;
+}
+
+pub type Extrinsic = TestXt<Call<Test>, ()>;
+
+impl<C> system::offchain::SendTransactionTypes<C> for Test where Call<Test>: From<C> {
+    type Extrinsic = Extrinsic;
+    type OverarchingCall = Call<Test>;
 }
 
 impl pallet_generic_asset::Trait for Test {
@@ -67,7 +153,31 @@ impl pallet_generic_asset::Trait for Test {
 
 pub type DEXModule = Module<Test>;
 
+// Balance representation of one unit of an asset
+pub const UNIT: u128 = 1_000_000_000_000;
+
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-    system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+    test_storage().into()
+}
+
+// Genesis storage shared by every test, other pallets' genesis can be added to it before it is used
+pub fn test_storage() -> sp_runtime::Storage {
+    let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+    // Assets 0, 1 and 2 with every test account endowed with 1000 units of each
+    pallet_generic_asset::GenesisConfig::<Test> {
+        assets: vec![0, 1, 2],
+        initial_balance: 1_000 * UNIT,
+        endowed_accounts: vec![1, 2, 3],
+        next_asset_id: 3,
+        staking_asset_id: 0,
+        spending_asset_id: 0,
+    }.assimilate_storage(&mut storage).unwrap();
+    storage
+}
+
+// Panics with the list of issues if the orderbook of the TradingPair is inconsistent.
+pub fn assert_orderbook_integrity(trading_pair: H256) {
+    let issues: Vec<IntegrityIssue> = DEXModule::check_orderbook_integrity(trading_pair);
+    assert!(issues.is_empty(), "Orderbook integrity check failed: {:?}", issues);
 }
@@ -0,0 +1,270 @@
+//! Off-chain matching of the orders of busy TradingPairs.
+//!
+//! Limit orders of a TradingPair with off-chain matching enabled are queued in PendingOrders instead
+//! of being matched in the block. The off-chain worker matches the queue against the book in memory
+//! and submits the resulting fills as an unsigned batch. The runtime settles the batch only if every
+//! fill is against the oldest order of the best opposite price, has the quantity the matching engine
+//! would have used and every queued order is matched for as long as it crosses the book, so the
+//! outcome is the one of on-chain matching. Market orders still match on chain against the book.
+//!
+//! Every fill of a batch is still executed and checked on chain, what moves off chain is only finding
+//! the maker price-time priority picks for it. The weight of settle_offchain_batch grows with its
+//! orders and fills accordingly. Queued limit orders are only matched against the book, never against
+//! the liquidity pool of the TradingPair, since a batch carries no pool fills.
+
+use codec::{Decode, Encode};
+use frame_support::{debug, ensure, transactional};
+use frame_support::storage::{IterableStorageMap, StorageDoubleMap, StorageMap};
+use frame_support::traits::Get;
+use frame_system::offchain::SubmitTransaction;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::CheckedSub;
+use sp_runtime::transaction_validity::{InvalidTransaction, TransactionSource, TransactionValidity, ValidTransaction};
+use sp_std::collections::btree_map::BTreeMap;
+use sp_std::collections::vec_deque::VecDeque;
+use sp_std::vec::Vec;
+
+use crate::{AsksLevels, BidsLevels, Call, Error, LinkedPriceLevel, MarketData, MarketInfo, Module, OffchainMatching, Order, Orderbook,
+            Orderbooks, OrderStatus, OrderType, PendingOrders, PriceLevels, RawEvent, Trait};
+
+/// Fill of a queued order against the oldest order of the best opposite price level
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct OffchainFill<Hash> {
+    /// Id of the resting order that is filled
+    pub maker: Hash,
+    pub quantity: FixedU128,
+}
+
+/// Fills of the first `orders` queued orders of a TradingPair, in the order they are executed
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct OffchainBatch<Hash> {
+    pub trading_pair: Hash,
+    pub orders: u32,
+    pub fills: Vec<OffchainFill<Hash>>,
+}
+
+// One side of the book as the off-chain worker sees it while matching, levels are loaded when first needed
+struct SimulatedSide<Hash> {
+    // Ascending like AsksLevels and BidsLevels
+    prices: Vec<FixedU128>,
+    levels: BTreeMap<FixedU128, VecDeque<(Hash, FixedU128)>>,
+}
+
+impl<T: Trait> Module<T> {
+    /// Matches the queued orders of the TradingPair against its book without changing storage and
+    /// returns the batch for the runtime to settle, None if nothing is queued
+    pub fn compute_offchain_batch(trading_pair: T::Hash) -> Option<OffchainBatch<T::Hash>> {
+        let pending: Vec<Order<T>> = <PendingOrders<T>>::get(&trading_pair);
+        if pending.is_empty() {
+            return None;
+        }
+        let mut asks = SimulatedSide { prices: <AsksLevels<T>>::get(&trading_pair), levels: BTreeMap::new() };
+        let mut bids = SimulatedSide { prices: <BidsLevels<T>>::get(&trading_pair), levels: BTreeMap::new() };
+        let mut fills: Vec<OffchainFill<T::Hash>> = Vec::new();
+        let mut orders: u32 = 0;
+
+        for taker in pending.iter().take(T::MaxOffchainBatchOrders::get() as usize) {
+            let (own_side, counter_side) = match taker.order_type {
+                OrderType::BidLimit => (&mut bids, &mut asks),
+                OrderType::AskLimit => (&mut asks, &mut bids),
+                _ => return None,
+            };
+            let mut quantity = taker.quantity;
+            while quantity > FixedU128::from(0) {
+                let best_price = match taker.order_type {
+                    OrderType::BidLimit => counter_side.prices.first().copied().filter(|price| taker.price >= *price),
+                    _ => counter_side.prices.last().copied().filter(|price| taker.price <= *price),
+                };
+                let best_price = match best_price {
+                    Some(best_price) => best_price,
+                    None => break,
+                };
+                let level = Self::simulated_level(&trading_pair, counter_side, best_price);
+                let (maker, maker_quantity) = level.front_mut()?;
+                let filled = quantity.min(*maker_quantity);
+                fills.push(OffchainFill { maker: *maker, quantity: filled });
+                quantity = quantity.checked_sub(&filled)?;
+                *maker_quantity = maker_quantity.checked_sub(&filled)?;
+                if *maker_quantity == FixedU128::from(0) {
+                    level.pop_front();
+                }
+                if level.is_empty() {
+                    counter_side.levels.remove(&best_price);
+                    counter_side.prices.retain(|price| *price != best_price);
+                }
+            }
+            if quantity > FixedU128::from(0) {
+                // The rest of the order rests on the book behind the orders already at its price
+                Self::simulated_level(&trading_pair, own_side, taker.price).push_back((taker.id, quantity));
+                if let Err(index) = own_side.prices.binary_search(&taker.price) {
+                    own_side.prices.insert(index, taker.price);
+                }
+            }
+            orders += 1;
+        }
+        Some(OffchainBatch { trading_pair, orders, fills })
+    }
+
+    // Submits the batches of all TradingPairs with off-chain matching that have queued orders
+    pub(crate) fn match_pending_orders_offchain() {
+        for (trading_pair, enabled) in <OffchainMatching<T>>::iter() {
+            if !enabled {
+                continue;
+            }
+            if let Some(batch) = Self::compute_offchain_batch(trading_pair) {
+                let call = Call::settle_offchain_batch(batch);
+                if SubmitTransaction::<T, Call<T>>::submit_unsigned_transaction(call.into()).is_err() {
+                    debug::warn!("Failed to submit the off-chain matched batch of a TradingPair");
+                }
+            }
+        }
+    }
+
+    // Settles the batch if it is exactly what matching the queued orders on chain would do
+    #[transactional]
+    pub(crate) fn do_settle_offchain_batch(batch: OffchainBatch<T::Hash>) -> Result<(), Error<T>> {
+        let trading_pair = batch.trading_pair;
+        ensure!(Self::is_offchain_matched(&trading_pair), <Error<T>>::NotOffchainMatched);
+        let mut pending: Vec<Order<T>> = <PendingOrders<T>>::get(&trading_pair);
+        let orders = batch.orders as usize;
+        ensure!(orders > 0 && orders <= pending.len() && batch.orders <= T::MaxOffchainBatchOrders::get(), <Error<T>>::InvalidOffchainBatch);
+        let still_pending = pending.split_off(orders);
+        let fill_count = batch.fills.len() as u32;
+
+        let mut orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let current_block_number = <frame_system::Module<T>>::block_number();
+        let mut market_data = <MarketInfo<T>>::get(&trading_pair, current_block_number).unwrap_or(MarketData {
+            low: FixedU128::from(0),
+            high: FixedU128::from(0),
+            volume: FixedU128::from(0),
+        });
+        let mut fills = batch.fills.into_iter();
+        for mut taker in pending {
+            // A queued order must be matched for as long as it crosses the book, no more and no less
+            while taker.quantity > FixedU128::from(0) && Self::crosses_book(&taker, &orderbook) {
+                let fill = fills.next().ok_or(<Error<T>>::InvalidOffchainBatch)?;
+                Self::settle_offchain_fill(&mut taker, &fill, &mut orderbook, &mut market_data)?;
+            }
+            Self::update_order_record(&taker);
+            if taker.quantity > FixedU128::from(0) {
+                Self::insert_order(&taker, &mut orderbook)?;
+                Self::deposit_event(RawEvent::NewLimitOrder(taker.id, taker.trading_pair, taker.order_type, taker.price, taker.quantity, taker.trader));
+            } else {
+                let asset_id = Self::reserved_asset_id(&taker.order_type, &orderbook);
+                let leftover = taker.reserved;
+                Self::release_reserve(&mut taker, asset_id, leftover)?;
+                Self::deposit_event(RawEvent::FulfilledLimitOrder(taker.id, taker.trading_pair, taker.order_type, taker.price, taker.quantity, taker.trader));
+            }
+        }
+        ensure!(fills.next().is_none(), <Error<T>>::InvalidOffchainBatch);
+
+        if fill_count > 0 {
+            Self::index_market_data(&trading_pair, current_block_number);
+            <MarketInfo<T>>::insert(&trading_pair, current_block_number, market_data);
+        }
+        <Orderbooks<T>>::insert(&trading_pair, orderbook);
+        <PendingOrders<T>>::insert(&trading_pair, still_pending);
+        Self::deposit_event(RawEvent::OffchainBatchSettled(trading_pair, batch.orders, fill_count));
+        Ok(())
+    }
+
+    // Removes a queued order of the trader and releases its reserved balance, false if the order isn't queued
+    pub(crate) fn cancel_pending_order(trader: &T::AccountId, order_id: &T::Hash, trading_pair: &T::Hash) -> Result<bool, Error<T>> {
+        let mut pending: Vec<Order<T>> = <PendingOrders<T>>::get(trading_pair);
+        let index = match pending.iter().position(|order| order.id == *order_id) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        ensure!(pending[index].trader == *trader, <Error<T>>::InvalidOrigin);
+        let mut removed_order = pending.remove(index);
+        let asset_id = Self::reserved_asset_id(&removed_order.order_type, &<Orderbooks<T>>::get(trading_pair));
+        let leftover = removed_order.reserved;
+        Self::release_reserve(&mut removed_order, asset_id, leftover)?;
+        <PendingOrders<T>>::insert(trading_pair, pending);
+        Self::close_order_record(order_id, OrderStatus::Cancelled);
+        Ok(true)
+    }
+
+    // Fills the taker against the oldest order of the best opposite price, which must be the fill's maker
+    fn settle_offchain_fill(taker: &mut Order<T>, fill: &OffchainFill<T::Hash>, orderbook: &mut Orderbook<T>, market_data: &mut MarketData) -> Result<(), Error<T>> {
+        let best_price = match taker.order_type {
+            OrderType::BidLimit => orderbook.best_ask_price,
+            _ => orderbook.best_bid_price,
+        };
+        let mut linkedpricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::get(&taker.trading_pair, &best_price);
+        let mut maker = linkedpricelevel.orders.pop_front().ok_or(<Error<T>>::NoElementFound)?;
+        ensure!(maker.id == fill.maker, <Error<T>>::InvalidOffchainBatch);
+        ensure!(fill.quantity == taker.quantity.min(maker.quantity), <Error<T>>::InvalidOffchainBatch);
+
+        Self::do_asset_exchange(taker, &mut maker, market_data, orderbook.base_asset_id, orderbook.quote_asset_id)?;
+        if maker.quantity > FixedU128::from(0) {
+            Self::emit_partial_fill(&maker, taker.quantity);
+            linkedpricelevel.orders.push_front(maker);
+        } else {
+            Self::emit_complete_fill(&maker, taker.quantity);
+        }
+
+        if !linkedpricelevel.orders.is_empty() {
+            <PriceLevels<T>>::insert(&taker.trading_pair, &best_price, linkedpricelevel);
+            return Ok(());
+        }
+        // The best level is used up, the next one becomes the best and has no better level before it
+        <PriceLevels<T>>::remove(&taker.trading_pair, &best_price);
+        if let Some(next_price) = linkedpricelevel.next {
+            <PriceLevels<T>>::mutate(&taker.trading_pair, &next_price, |next_linkedpricelevel| next_linkedpricelevel.prev = None);
+        }
+        match taker.order_type {
+            OrderType::BidLimit => {
+                let mut asks_levels: Vec<FixedU128> = <AsksLevels<T>>::get(&taker.trading_pair);
+                asks_levels.retain(|price| *price != best_price);
+                orderbook.best_ask_price = asks_levels.first().copied().unwrap_or(FixedU128::from(0));
+                <AsksLevels<T>>::insert(&taker.trading_pair, asks_levels);
+            }
+            _ => {
+                let mut bids_levels: Vec<FixedU128> = <BidsLevels<T>>::get(&taker.trading_pair);
+                bids_levels.retain(|price| *price != best_price);
+                orderbook.best_bid_price = bids_levels.last().copied().unwrap_or(FixedU128::from(0));
+                <BidsLevels<T>>::insert(&taker.trading_pair, bids_levels);
+            }
+        }
+        Ok(())
+    }
+
+    // Orders of the simulated level at the price, loaded from PriceLevels the first time
+    fn simulated_level<'a>(trading_pair: &T::Hash, side: &'a mut SimulatedSide<T::Hash>, price: FixedU128) -> &'a mut VecDeque<(T::Hash, FixedU128)> {
+        let prices = &side.prices;
+        side.levels.entry(price).or_insert_with(|| {
+            if prices.contains(&price) {
+                let linkedpricelevel: LinkedPriceLevel<T> = <PriceLevels<T>>::get(trading_pair, &price);
+                linkedpricelevel.orders.iter().map(|order| (order.id, order.quantity)).collect()
+            } else {
+                VecDeque::new()
+            }
+        })
+    }
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+    type Call = Call<T>;
+
+    fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+        if let Call::settle_offchain_batch(batch) = call {
+            if !Self::is_offchain_matched(&batch.trading_pair) {
+                return InvalidTransaction::Call.into();
+            }
+            let pending: Vec<Order<T>> = <PendingOrders<T>>::get(&batch.trading_pair);
+            if batch.orders == 0 || batch.orders as usize > pending.len() || batch.orders > T::MaxOffchainBatchOrders::get() {
+                return InvalidTransaction::Stale.into();
+            }
+            // One batch per state of the queue, the oldest queued order identifies it
+            ValidTransaction::with_tag_prefix("DexOffchainBatch")
+                .priority(T::OffchainBatchPriority::get())
+                .and_provides((batch.trading_pair, pending[0].id))
+                .longevity(3)
+                .propagate(true)
+                .build()
+        } else {
+            InvalidTransaction::Call.into()
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Current state of every order by its id, kept for OrderHistoryRetention blocks once it is closed.
+//!
+//! An order is recorded when it is submitted and its record follows every fill of the order until
+//! it is filled or cancelled. A market order that can't be filled completely is recorded as cancelled
+//! with the part that wasn't filled as its remaining quantity. Orders resting since before order
+//! history was added are recorded by the V7_0_0 migration with their remaining quantity as the quantity
+//! they were submitted with.
+
+use codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use frame_support::storage::StorageMap;
+use frame_support::traits::Get;
+use frame_support::weights::Weight;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{Saturating, Zero};
+
+use crate::{ClosedOrderExpiries, Module, Order, OrderRecords, OrderType, Trait};
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    Cancelled,
+}
+
+/// State of an order, quantities of a BidMarket order are the amount of the base asset it spends
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct OrderRecord<AccountId, Hash, BlockNumber> {
+    pub trading_pair: Hash,
+    pub trader: AccountId,
+    pub order_type: OrderType,
+    pub price: FixedU128,
+    /// Quantity the order was submitted with
+    pub quantity: FixedU128,
+    /// Quantity not filled yet
+    pub remaining: FixedU128,
+    pub status: OrderStatus,
+    /// Block in which the order was filled or cancelled
+    pub closed_at: Option<BlockNumber>,
+}
+
+impl<T: Trait> Module<T> {
+    // Records the order once its submission is executed, price and quantity are the ones it was submitted with
+    pub(crate) fn record_order(order: &Order<T>, price: FixedU128, quantity: FixedU128) {
+        let (quantity, remaining) = match order.order_type {
+            OrderType::BidMarket => (price, order.price),
+            _ => (quantity, order.quantity),
+        };
+        let status = match order.order_type {
+            _ if remaining == FixedU128::from(0) => OrderStatus::Filled,
+            OrderType::BidLimit | OrderType::AskLimit => OrderStatus::Open,
+            OrderType::BidMarket | OrderType::AskMarket => OrderStatus::Cancelled,
+        };
+        let now = <frame_system::Module<T>>::block_number();
+        let closed_at = if status == OrderStatus::Open { None } else { Some(now) };
+        <OrderRecords<T>>::insert(&order.id, OrderRecord {
+            trading_pair: order.trading_pair,
+            trader: order.trader.clone(),
+            order_type: order.order_type.clone(),
+            price,
+            quantity,
+            remaining,
+            status,
+            closed_at,
+        });
+        if closed_at.is_some() {
+            Self::expire_order_record(&order.id, now);
+        }
+    }
+
+    // Updates the remaining quantity of a resting or queued limit order after a fill
+    pub(crate) fn update_order_record(order: &Order<T>) {
+        <OrderRecords<T>>::mutate(&order.id, |record| {
+            if let Some(record) = record {
+                record.remaining = order.quantity;
+            }
+        });
+        if order.quantity == FixedU128::from(0) {
+            Self::close_order_record(&order.id, OrderStatus::Filled);
+        }
+    }
+
+    // Whether the order was recorded and is neither filled nor cancelled yet
+    pub(crate) fn is_open_order(order_id: &T::Hash) -> bool {
+        Self::order_record(order_id).map_or(false, |record| record.status == OrderStatus::Open)
+    }
+
+    pub(crate) fn close_order_record(order_id: &T::Hash, status: OrderStatus) {
+        if !<OrderRecords<T>>::contains_key(order_id) {
+            return;
+        }
+        let now = <frame_system::Module<T>>::block_number();
+        <OrderRecords<T>>::mutate(order_id, |record| {
+            if let Some(record) = record {
+                record.status = status;
+                record.closed_at = Some(now);
+            }
+        });
+        Self::expire_order_record(order_id, now);
+    }
+
+    // Schedules the record of an order closed in the block for pruning, zero retention keeps it forever
+    fn expire_order_record(order_id: &T::Hash, now: T::BlockNumber) {
+        let retention = T::OrderHistoryRetention::get();
+        if !retention.is_zero() {
+            <ClosedOrderExpiries<T>>::mutate(now.saturating_add(retention), |expiring| expiring.push(*order_id));
+        }
+    }
+
+    // Removes the records of the orders closed OrderHistoryRetention blocks ago
+    pub(crate) fn prune_order_history(now: T::BlockNumber) -> Weight {
+        let expired = <ClosedOrderExpiries<T>>::take(now);
+        for order_id in expired.iter() {
+            <OrderRecords<T>>::remove(order_id);
+        }
+        T::DbWeight::get().reads_writes(1, 1 + expired.len() as Weight)
+    }
+}
@@ -0,0 +1,63 @@
+//! Referrers of traders and their share of the trading fees of the traders they referred.
+//!
+//! An account registers its referrer once and can't change it afterwards. The trading fee of the DEX
+//! is the PoolSwapFee kept by a liquidity pool out of every amount paid into it, ReferralFeeShare of
+//! the fee paid by a referred trader is moved out of the pool into the referral account and accrues
+//! to its referrer in the asset the fee was paid in. A referrer claims everything it accrued at once.
+
+use frame_support::{ensure, transactional};
+use frame_support::storage::{IterableStorageDoubleMap, StorageDoubleMap, StorageMap};
+use frame_support::traits::Get;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{CheckedMul, Saturating};
+use sp_runtime::traits::AccountIdConversion;
+use sp_std::vec::Vec;
+
+use crate::{Error, Module, RawEvent, REFERRAL_MODULE_ID, ReferralRewards, Referrers, Trait};
+
+impl<T: Trait> Module<T> {
+    /// Account holding the referral rewards accrued and not claimed yet
+    pub fn referral_account() -> T::AccountId {
+        REFERRAL_MODULE_ID.into_account()
+    }
+
+    pub(crate) fn do_register_referrer(referee: T::AccountId, referrer: T::AccountId) -> Result<(), Error<T>> {
+        ensure!(referee != referrer, <Error<T>>::InvalidReferrer);
+        ensure!(!<Referrers<T>>::contains_key(&referee), <Error<T>>::ReferrerAlreadyRegistered);
+        <Referrers<T>>::insert(&referee, &referrer);
+        Self::deposit_event(RawEvent::ReferrerRegistered(referee, referrer));
+        Ok(())
+    }
+
+    #[transactional]
+    pub(crate) fn do_claim_referral_rewards(referrer: T::AccountId) -> Result<(), Error<T>> {
+        let rewards: Vec<(T::AssetId, FixedU128)> = <ReferralRewards<T>>::drain_prefix(&referrer).collect();
+        ensure!(!rewards.is_empty(), <Error<T>>::NoReferralRewards);
+        let referral_account = Self::referral_account();
+        for (asset_id, amount) in rewards {
+            Self::transfer_asset_market(asset_id, amount, &referral_account, &referrer)?;
+            Self::deposit_event(RawEvent::ReferralRewardsClaimed(referrer.clone(), asset_id, amount));
+        }
+        Ok(())
+    }
+
+    // Moves the referrer's share of the pool fee on amount_in, which the trader just paid into the pool,
+    // to the referral account
+    pub(crate) fn share_referral_fee(trader: &T::AccountId, asset_id: T::AssetId, amount_in: FixedU128, pool: &T::AccountId) -> Result<(), Error<T>> {
+        let referrer = match <Referrers<T>>::get(trader) {
+            Some(referrer) => referrer,
+            None => return Ok(()),
+        };
+        let share = amount_in.checked_mul(&Self::permill_to_fixed(T::PoolSwapFee::get()))
+            .and_then(|fee| fee.checked_mul(&Self::permill_to_fixed(T::ReferralFeeShare::get())))
+            .and_then(|share| Self::round_to_asset_precision(&asset_id, share))
+            .ok_or(<Error<T>>::MulUnderflowOrOverflow)?;
+        if share == FixedU128::from(0) {
+            return Ok(());
+        }
+        Self::transfer_asset_market(asset_id, share, pool, &Self::referral_account())?;
+        <ReferralRewards<T>>::mutate(&referrer, &asset_id, |reward| *reward = reward.saturating_add(share));
+        Self::deposit_event(RawEvent::ReferralRewardAccrued(referrer, trader.clone(), asset_id, share));
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Orders signed off chain by traders and submitted on their behalf by relayers.
+//!
+//! A trader signs the signed_order_payload of a SignedOrder, its SCALE encoding prefixed with
+//! SIGNED_ORDER_DOMAIN and the genesis hash of the chain, with the key of its account. Any account
+//! can relay a batch of signed orders and pays the transaction fee, the orders are executed for the
+//! traders who signed them. The salt of every relayed order is recorded for its trader so the same
+//! signed order can't be executed twice, and an order is rejected once its expiry block has passed.
+//! The salt is pruned after the expiry block since the order can't be executed any more anyway.
+
+use codec::{Decode, Encode};
+use frame_support::{ensure, transactional};
+use frame_support::storage::{StorageDoubleMap, StorageMap};
+use frame_support::traits::Get;
+use frame_support::weights::Weight;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{One, Saturating, Zero};
+use sp_runtime::traits::Verify;
+use sp_std::vec::Vec;
+
+use crate::{Error, Module, OrderType, RawEvent, SIGNED_ORDER_DOMAIN, SignedOrderSaltExpiries, SignedOrderSalts, Trait};
+
+/// Order message a trader signs off chain for a relayer to submit
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct SignedOrder<AccountId, Hash, BlockNumber> {
+    /// Account that signed the order and trades it
+    pub trader: AccountId,
+    pub trading_pair: Hash,
+    pub order_type: OrderType,
+    pub price: FixedU128,
+    pub quantity: FixedU128,
+    /// Last block in which the order can be executed
+    pub expiry: BlockNumber,
+    /// Chosen by the trader, every order of a trader needs a different salt
+    pub salt: u64,
+}
+
+impl<T: Trait> Module<T> {
+    /// Bytes a trader signs for the order, they only verify on this chain and only as a SignedOrder
+    pub fn signed_order_payload(order: &SignedOrder<T::AccountId, T::Hash, T::BlockNumber>) -> Vec<u8> {
+        let genesis_hash = <frame_system::Module<T>>::block_hash(T::BlockNumber::zero());
+        (SIGNED_ORDER_DOMAIN, genesis_hash, order).encode()
+    }
+
+    // Executes the signed orders in the given order, nothing is executed if one of them is invalid or fails
+    #[transactional]
+    pub(crate) fn do_submit_signed_orders(relayer: T::AccountId,
+                                          orders: Vec<(SignedOrder<T::AccountId, T::Hash, T::BlockNumber>, T::OrderSignature)>) -> Result<(), Error<T>> {
+        ensure!(!orders.is_empty(), <Error<T>>::NoSignedOrders);
+        ensure!(orders.len() <= T::MaxSignedOrdersPerBatch::get() as usize, <Error<T>>::TooManySignedOrders);
+        let now = <frame_system::Module<T>>::block_number();
+        let count = orders.len() as u32;
+        for (order, signature) in orders {
+            ensure!(signature.verify(&Self::signed_order_payload(&order)[..], &order.trader), <Error<T>>::InvalidOrderSignature);
+            ensure!(now <= order.expiry, <Error<T>>::SignedOrderExpired);
+            ensure!(!<SignedOrderSalts<T>>::get(&order.trader, order.salt), <Error<T>>::SignedOrderReplayed);
+            <SignedOrderSalts<T>>::insert(&order.trader, order.salt, true);
+            <SignedOrderSaltExpiries<T>>::mutate(order.expiry, |expiring| expiring.push((order.trader.clone(), order.salt)));
+            Self::execute_order(order.trader, order.order_type, order.trading_pair, order.price, order.quantity)?;
+        }
+        Self::deposit_event(RawEvent::SignedOrdersRelayed(relayer, count));
+        Ok(())
+    }
+
+    // Forgets the salts of the signed orders that expired with the previous block
+    pub(crate) fn prune_signed_order_salts(now: T::BlockNumber) -> Weight {
+        let expired = <SignedOrderSaltExpiries<T>>::take(now.saturating_sub(One::one()));
+        for (trader, salt) in expired.iter() {
+            <SignedOrderSalts<T>>::remove(trader, salt);
+        }
+        T::DbWeight::get().reads_writes(1, 1 + expired.len() as Weight)
+    }
+}
@@ -0,0 +1,138 @@
+//! Netting of the balances exchanged by matched limit orders.
+//!
+//! do_asset_exchange doesn't transfer anything when two limit orders match. It records what each
+//! trader pays out of the balance reserved for its order and what it receives in PendingSettlements,
+//! and the net amounts of every trader and asset are settled once in on_finalize through the
+//! settlement account, which holds its balances in the trading ledger. A trader crossing many orders
+//! in a block makes at most one transfer per asset. What a trader receives from a limit order match
+//! can be spent from the next block on. A match is refused if the reserved balance of the trader
+//! can't pay for it. Traders are settled one by one, so a trader whose reserved balance still can't
+//! pay at the end of the block only drops its own delta and never holds up the others.
+
+use codec::{Decode, Encode};
+use frame_support::{debug, ensure};
+use frame_support::traits::Get;
+use frame_support::weights::Weight;
+use frame_support::storage::{with_transaction, IterableStorageDoubleMap, StorageDoubleMap, StorageValue, TransactionOutcome};
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{CheckedAdd, Saturating};
+use sp_runtime::traits::AccountIdConversion;
+use sp_std::collections::btree_map::BTreeMap;
+use sp_std::vec::Vec;
+
+use crate::{Error, Module, Order, PendingSettlementCount, PendingSettlements, RawEvent, SETTLEMENT_MODULE_ID, Trait};
+
+/// What a trader receives and pays out of its reserved balance in one asset during the block
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SettlementDelta {
+    pub credit: FixedU128,
+    pub reserved_debit: FixedU128,
+}
+
+impl<T: Trait> Module<T> {
+    /// Account the net amounts of the block are settled through
+    pub fn settlement_account() -> T::AccountId {
+        SETTLEMENT_MODULE_ID.into_account()
+    }
+
+    // Pays the amount out of the balance reserved for the order to `to` when the block is finalized
+    pub(crate) fn transfer_reserved_netted(order: &mut Order<T>, asset_id: T::AssetId, amount: FixedU128, to: &T::AccountId) -> Result<(), Error<T>> {
+        // Only whole balance units are unreserved so only those are deducted, the dust is released once the order is done
+        let amount = Self::round_to_asset_precision(&asset_id, amount).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        // Everything the trader pays in the block is taken from its reserved balance in on_finalize
+        let debit = Self::pending_settlement(&order.trader, &asset_id).reserved_debit.checked_add(&amount).ok_or(<Error<T>>::AddUnderflowOrOverflow)?;
+        let reserved = Self::reserved_tradable_balance(&asset_id, &order.trader).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        ensure!(debit <= reserved, <Error<T>>::InsufficientReservedBalance);
+        Self::count_pending_settlement(&order.trader, &asset_id)?;
+        <PendingSettlements<T>>::mutate(&order.trader, &asset_id, |delta| delta.reserved_debit = debit);
+        Self::count_pending_settlement(to, &asset_id)?;
+        <PendingSettlements<T>>::mutate(to, &asset_id, |delta| delta.credit = delta.credit.saturating_add(amount));
+        order.reserved = order.reserved.saturating_sub(amount);
+        Ok(())
+    }
+
+    // Counts a PendingSettlements entry the trader doesn't have yet, refused once the block has MaxSettlementsPerBlock
+    fn count_pending_settlement(trader: &T::AccountId, asset_id: &T::AssetId) -> Result<(), Error<T>> {
+        if !<PendingSettlements<T>>::contains_key(trader, asset_id) {
+            let count = PendingSettlementCount::get();
+            ensure!(count < T::MaxSettlementsPerBlock::get(), <Error<T>>::TooManySettlements);
+            PendingSettlementCount::put(count + 1);
+        }
+        Ok(())
+    }
+
+    // Weight of settling the most PendingSettlements a block can have, reserved in on_initialize as
+    // on_finalize can't report its weight. Settling a trader reads and writes both ledger balances.
+    pub(crate) fn settlement_weight() -> Weight {
+        T::DbWeight::get().reads_writes(6, 5).saturating_mul(T::MaxSettlementsPerBlock::get() as Weight)
+    }
+
+    // Settles the net amount of every trader and asset of the block, the traders paying out first so that
+    // the settlement account holds what the ones after them receive. A trader that can't be settled is
+    // dropped and reported, the credits its debit should have paid for are paid as far as the other
+    // debits go and the rest is reported as a shortfall. Nothing stays pending for a later block.
+    pub(crate) fn settle_pending_transfers() {
+        PendingSettlementCount::kill();
+        let mut assets: BTreeMap<T::AssetId, Vec<(T::AccountId, SettlementDelta)>> = BTreeMap::new();
+        for (trader, asset_id, delta) in <PendingSettlements<T>>::drain() {
+            assets.entry(asset_id).or_insert_with(Vec::new).push((trader, delta));
+        }
+        let settlement_account = Self::settlement_account();
+        for (asset_id, mut deltas) in assets.into_iter() {
+            // Held in the trading ledger so that matches between ledger traders stay in the ledger
+            Self::open_trading_balance(&settlement_account, &asset_id);
+            let balance_before = Self::tradable_balance(&asset_id, &settlement_account);
+            deltas.sort_by_key(|(_, delta)| delta.credit > delta.reserved_debit);
+            // Paid in by the traders settled so far and not paid out yet
+            let mut held = FixedU128::from(0);
+            for (trader, delta) in deltas.iter() {
+                let settled = with_transaction(|| match Self::settle_trader(&asset_id, trader, delta, &settlement_account, held) {
+                    Ok(settled) => TransactionOutcome::Commit(Some(settled)),
+                    Err(_) => TransactionOutcome::Rollback(None),
+                });
+                match settled {
+                    Some((debit, credit, paid)) => {
+                        held = held.saturating_add(debit).saturating_sub(paid);
+                        if paid < credit {
+                            debug::error!("Settlement of asset {:?} is short of {:?} for {:?}", asset_id, credit.saturating_sub(paid), trader);
+                            Self::deposit_event(RawEvent::SettlementShortfall(trader.clone(), asset_id, credit.saturating_sub(paid)));
+                        }
+                    }
+                    None => {
+                        debug::error!("Settlement of asset {:?} for {:?} failed and is dropped", asset_id, trader);
+                        Self::deposit_event(RawEvent::SettlementFailed(trader.clone(), asset_id));
+                    }
+                }
+            }
+            // Whatever was paid in was paid out again, the settlement account is back where it started
+            let balance_after = Self::tradable_balance(&asset_id, &settlement_account);
+            if balance_after != balance_before {
+                debug::error!("Settlement of asset {:?} is not conserved, balance {:?} before and {:?} after", asset_id, balance_before, balance_after);
+                Self::deposit_event(RawEvent::SettlementNotConserved(asset_id));
+            }
+        }
+    }
+
+    // Collects the net debit of the trader into the settlement account and pays its net credit out of what
+    // the traders before it paid in. Returns the debit collected, the credit owed and the credit paid.
+    fn settle_trader(asset_id: &T::AssetId, trader: &T::AccountId, delta: &SettlementDelta, settlement_account: &T::AccountId, held: FixedU128)
+        -> Result<(FixedU128, FixedU128, FixedU128), Error<T>> {
+        let reserved = Self::reserved_tradable_balance(asset_id, trader).ok_or(<Error<T>>::InternalErrorU128Balance)?;
+        ensure!(delta.reserved_debit <= reserved, <Error<T>>::InsufficientReservedBalance);
+        // What the trader gets back in the same asset never leaves its account
+        let netted = delta.credit.min(delta.reserved_debit);
+        let debit = delta.reserved_debit.saturating_sub(netted);
+        let credit = delta.credit.saturating_sub(netted);
+        if netted > FixedU128::from(0) {
+            Self::unreserve_tradable(asset_id, trader, netted)?;
+        }
+        if debit > FixedU128::from(0) {
+            Self::transfer_tradable(*asset_id, debit, trader, settlement_account, true)?;
+        }
+        let paid = credit.min(held);
+        if paid > FixedU128::from(0) {
+            Self::transfer_tradable(*asset_id, paid, settlement_account, trader, false)?;
+        }
+        Ok((debit, credit, paid))
+    }
+}
@@ -0,0 +1,149 @@
+//! Dry run of an order against the current state, so wallets can show its price impact before it is signed.
+//!
+//! The order is executed exactly like submit_order executes it, inside a storage transaction that is
+//! rolled back, so a simulation fails with the same Error as the real order. Fills against the book
+//! are found by comparing the resting orders of the other side before and after the execution, what
+//! else the trader paid and received was exchanged with the liquidity pool.
+
+use codec::Decode;
+use frame_support::storage::{with_transaction, StorageDoubleMap, StorageMap, TransactionOutcome};
+use frame_support::traits::Get;
+use sp_arithmetic::FixedU128;
+use sp_arithmetic::traits::{CheckedDiv, Saturating};
+use sp_std::vec::Vec;
+
+use crate::{AsksLevels, BidsLevels, Error, Module, Order, Orderbook, Orderbooks, OrderStatus, OrderType, PriceLevels,
+            SimulatedFillRpc, SimulatedOrderRpc, Trait};
+
+impl<T: Trait> Module<T> {
+    pub fn simulate_order(trader: [u8; 32], order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128) -> SimulatedOrderRpc {
+        let trader = match T::AccountId::decode(&mut &trader[..]) {
+            Ok(trader) => trader,
+            Err(_) => return Self::simulation_error(<Error<T>>::InvalidOrigin),
+        };
+        with_transaction(|| TransactionOutcome::Rollback(Self::dry_run_order(trader, order_type, trading_pair, price, quantity)))
+    }
+
+    fn dry_run_order(trader: T::AccountId, order_type: OrderType, trading_pair: T::Hash, price: FixedU128, quantity: FixedU128) -> SimulatedOrderRpc {
+        let orderbook: Orderbook<T> = <Orderbooks<T>>::get(&trading_pair);
+        let is_bid = order_type == OrderType::BidLimit || order_type == OrderType::BidMarket;
+        let book_before = Self::simulated_counter_levels(&orderbook, &order_type, price, quantity);
+        let pool_before = Self::pool_reserves(&orderbook);
+        let holdings_before = Self::simulated_holdings(&orderbook, &trader);
+        let order_id = Self::next_order_id(&trader, &order_type, &trading_pair, price, quantity);
+        if let Err(error) = Self::execute_order(trader.clone(), order_type.clone(), trading_pair, price, quantity) {
+            return Self::simulation_error(error);
+        }
+        let ((base_before, quote_before), (base_after, quote_after)) = match (holdings_before, Self::simulated_holdings(&orderbook, &trader)) {
+            (Some(before), Some(after)) => (before, after),
+            _ => return Self::simulation_error(<Error<T>>::InternalErrorU128Balance),
+        };
+        // Base and quote asset the trader exchanged in total
+        let (base_total, quote_total) = if is_bid {
+            (base_before.saturating_sub(base_after), quote_after.saturating_sub(quote_before))
+        } else {
+            (base_after.saturating_sub(base_before), quote_before.saturating_sub(quote_after))
+        };
+
+        let mut fills: Vec<(FixedU128, FixedU128, bool)> = Vec::new();
+        let (mut book_base, mut book_quote) = (FixedU128::from(0), FixedU128::from(0));
+        for (level_price, orders) in book_before {
+            let orders_after = <PriceLevels<T>>::get(&trading_pair, level_price).orders;
+            let filled = orders.iter().fold(FixedU128::from(0), |total, (order_id, quantity_before)| {
+                let quantity_after = orders_after.iter().find(|order| order.id == *order_id).map_or(FixedU128::from(0), |order| order.quantity);
+                total.saturating_add(quantity_before.saturating_sub(quantity_after))
+            });
+            if filled == FixedU128::from(0) {
+                continue;
+            }
+            book_base = book_base.saturating_add(level_price.saturating_mul(filled));
+            book_quote = book_quote.saturating_add(filled);
+            fills.push((level_price, filled, false));
+        }
+
+        // The pool pays out exactly what it sold, what the trader paid into it is the rest of its total
+        let mut fee = FixedU128::from(0);
+        if let (Some((base_reserve, quote_reserve)), Some((base_reserve_after, quote_reserve_after))) = (pool_before, Self::pool_reserves(&orderbook)) {
+            let (pool_base, pool_quote) = if is_bid {
+                (base_total.saturating_sub(book_base), quote_reserve.saturating_sub(quote_reserve_after))
+            } else {
+                (base_reserve.saturating_sub(base_reserve_after), quote_total.saturating_sub(book_quote))
+            };
+            if pool_quote > FixedU128::from(0) {
+                let amount_in = if is_bid { pool_base } else { pool_quote };
+                fee = amount_in.saturating_mul(Self::permill_to_fixed(T::PoolSwapFee::get()));
+                fills.push((pool_base.checked_div(&pool_quote).unwrap_or_else(|| FixedU128::from(0)), pool_quote, true));
+            }
+        }
+
+        let record = match Self::order_record(&order_id) {
+            Some(record) => record,
+            None => return Self::simulation_error(<Error<T>>::InvalidOrderID),
+        };
+        let (base_decimals, quote_decimals) = Self::pair_decimals(&trading_pair);
+        let fee_decimals = if is_bid { base_decimals } else { quote_decimals };
+        let remaining_decimals = if order_type == OrderType::BidMarket { base_decimals } else { quote_decimals };
+        SimulatedOrderRpc {
+            fills: fills.into_iter().map(|(fill_price, fill_quantity, from_pool)| SimulatedFillRpc {
+                price: Order::<T>::convert_fixed_u128_to_balance(fill_price, base_decimals).unwrap_or_default(),
+                quantity: Order::<T>::convert_fixed_u128_to_balance(fill_quantity, quote_decimals).unwrap_or_default(),
+                from_pool,
+            }).collect(),
+            filled_quantity: Order::<T>::convert_fixed_u128_to_balance(quote_total, quote_decimals).unwrap_or_default(),
+            average_price: base_total.checked_div(&quote_total)
+                .and_then(|average_price| Order::<T>::convert_fixed_u128_to_balance(average_price, base_decimals))
+                .unwrap_or_default(),
+            fee: Order::<T>::convert_fixed_u128_to_balance(fee, fee_decimals).unwrap_or_default(),
+            remaining_quantity: Order::<T>::convert_fixed_u128_to_balance(record.remaining, remaining_decimals).unwrap_or_default(),
+            resting: record.status == OrderStatus::Open,
+            error: None,
+        }
+    }
+
+    // Resting orders of the other side of the book the order can reach, best price first
+    fn simulated_counter_levels(orderbook: &Orderbook<T>, order_type: &OrderType, price: FixedU128, quantity: FixedU128) -> Vec<(FixedU128, Vec<(T::Hash, FixedU128)>)> {
+        let prices: Vec<FixedU128> = match order_type {
+            OrderType::BidLimit | OrderType::BidMarket => <AsksLevels<T>>::get(&orderbook.trading_pair),
+            OrderType::AskLimit | OrderType::AskMarket => <BidsLevels<T>>::get(&orderbook.trading_pair).into_iter().rev().collect(),
+        };
+        // A BidMarket order spends price of the base asset, the others fill quantity of the quote asset
+        let needed = if *order_type == OrderType::BidMarket { price } else { quantity };
+        let mut covered = FixedU128::from(0);
+        let mut levels = Vec::new();
+        for level_price in prices {
+            let within_limit = match order_type {
+                OrderType::BidLimit => level_price <= price,
+                OrderType::AskLimit => level_price >= price,
+                OrderType::BidMarket | OrderType::AskMarket => true,
+            };
+            if !within_limit || covered >= needed {
+                break;
+            }
+            let orders: Vec<(T::Hash, FixedU128)> = <PriceLevels<T>>::get(&orderbook.trading_pair, level_price).orders.iter()
+                .map(|order| (order.id, order.quantity))
+                .collect();
+            let level_quantity = orders.iter().fold(FixedU128::from(0), |total, (_, quantity)| total.saturating_add(*quantity));
+            covered = covered.saturating_add(if *order_type == OrderType::BidMarket { level_price.saturating_mul(level_quantity) } else { level_quantity });
+            levels.push((level_price, orders));
+        }
+        levels
+    }
+
+    // Free and reserved tradable balance of the trader in the base and quote asset, including what limit
+    // order matches of the block pay to and take from it once the block is settled
+    fn simulated_holdings(orderbook: &Orderbook<T>, trader: &T::AccountId) -> Option<(FixedU128, FixedU128)> {
+        let holding = |asset_id: &T::AssetId| -> Option<FixedU128> {
+            let pending = Self::pending_settlement(trader, asset_id);
+            Some(Self::tradable_balance(asset_id, trader)?
+                .saturating_add(Self::reserved_tradable_balance(asset_id, trader)?)
+                .saturating_add(pending.credit)
+                .saturating_sub(pending.reserved_debit))
+        };
+        Some((holding(&orderbook.base_asset_id)?, holding(&orderbook.quote_asset_id)?))
+    }
+
+    fn simulation_error(error: Error<T>) -> SimulatedOrderRpc {
+        let name: &'static str = error.into();
+        SimulatedOrderRpc { error: Some(name.as_bytes().to_vec()), ..Default::default() }
+    }
+}
@@ -1,5 +1,5 @@
 // use crate::{mock::*};
-// use frame_support::assert_ok;
+// use frame_support::{assert_noop, assert_ok};
 // use frame_system::ensure_signed;
 //
 
@@ -24,3 +24,1306 @@
 // // 		);
 // // 	});
 // // }
+
+use codec::Encode;
+use frame_support::{assert_noop, assert_ok};
+use frame_support::storage::{StorageMap, StorageValue, unhashed};
+use frame_support::traits::{OnFinalize, OnInitialize};
+use sp_arithmetic::{FixedPointNumber, FixedU128};
+use sp_core::H256;
+use sp_runtime::Permill;
+use sp_runtime::testing::TestSignature;
+
+use crate::{mock::*, migrations, AsksLevels, DexPriceOracle, Error, IntegrityIssue, LinkedPriceLevel, MarginAccount, MarketDataRpc, OffchainFill, Order, Orderbook, Orderbooks, OrderStatus, OrderType, PriceLevels, Releases, SignedOrder, StorageVersion, TradingBalance};
+
+// Writes an Orderbook with the encoding used before V2_0_0
+fn put_v1_orderbook(trading_pair: H256) {
+    let old = migrations::v2::OldOrderbook::<Test> {
+        trading_pair,
+        base_asset_id: 0,
+        quote_asset_id: 1,
+        best_bid_price: FixedU128::from(2),
+        best_ask_price: FixedU128::from(3),
+    };
+    unhashed::put(&<Orderbooks<Test>>::hashed_key_for(trading_pair), &old);
+}
+
+#[test]
+fn migration_to_v2_adds_tick_and_lot_size() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(1);
+        put_v1_orderbook(trading_pair);
+        assert_eq!(DEXModule::storage_version(), Releases::V1_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        let orderbook = DEXModule::get_orderbooks(trading_pair);
+        assert_eq!(orderbook.trading_pair, trading_pair);
+        assert_eq!(orderbook.base_asset_id, 0);
+        assert_eq!(orderbook.quote_asset_id, 1);
+        assert_eq!(orderbook.best_bid_price, FixedU128::from(2));
+        assert_eq!(orderbook.best_ask_price, FixedU128::from(3));
+        assert_eq!(orderbook.tick_size, FixedU128::from(0));
+        assert_eq!(orderbook.lot_size, FixedU128::from(0));
+    });
+}
+
+#[test]
+fn migration_is_noop_on_latest_release() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(2);
+        StorageVersion::put(migrations::LATEST_RELEASE);
+        let orderbook: Orderbook<Test> = Orderbook::new(0, 1, trading_pair, FixedU128::from(1), FixedU128::from(1));
+        <Orderbooks<Test>>::insert(trading_pair, orderbook.clone());
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        assert_eq!(DEXModule::get_orderbooks(trading_pair), orderbook);
+    });
+}
+
+#[test]
+fn migration_to_v3_tracks_reserved_balance_of_orders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(5);
+        let price = FixedU128::from(2);
+        let old_order = |trader: u64, order_type: OrderType| migrations::v3::OldOrder::<Test> {
+            id: H256::repeat_byte(trader as u8),
+            trading_pair,
+            trader,
+            price,
+            quantity: FixedU128::from(3),
+            order_type,
+        };
+        let old = migrations::v3::OldLinkedPriceLevel::<Test> {
+            next: None,
+            prev: Some(FixedU128::from(1)),
+            orders: vec![old_order(1, OrderType::BidLimit), old_order(2, OrderType::AskLimit)].into(),
+        };
+        unhashed::put(&<PriceLevels<Test>>::hashed_key_for(trading_pair, price), &old);
+        StorageVersion::put(Releases::V2_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        let linked_pricelevel = DEXModule::get_pricelevels(trading_pair, price);
+        assert_eq!(linked_pricelevel.prev, Some(FixedU128::from(1)));
+        assert_eq!(linked_pricelevel.orders[0].reserved, FixedU128::from(6));
+        assert_eq!(linked_pricelevel.orders[1].reserved, FixedU128::from(3));
+    });
+}
+
+// Whitelists both mock assets and registers the 1/0 orderbook without tick or lot size
+fn setup_orderbook() -> H256 {
+    assert_ok!(DEXModule::whitelist_asset(Origin::root(), 0, 12));
+    assert_ok!(DEXModule::whitelist_asset(Origin::root(), 1, 12));
+    assert_ok!(DEXModule::register_new_orderbook(Origin::root(), 1, 0, FixedU128::from(0), FixedU128::from(0)));
+    DEXModule::create_trading_pair_id(&1, &0)
+}
+
+#[test]
+fn cancel_order_releases_reserved_balance() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 6 * UNIT);
+
+        let order_id = DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].id;
+        assert_ok!(DEXModule::cancel_order(Origin::signed(1), order_id, trading_pair, FixedU128::from(2), None));
+
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 1000 * UNIT);
+    });
+}
+
+#[test]
+fn failed_cancel_leaves_the_price_level_unchanged() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        let order_id = DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].id;
+
+        assert_noop!(DEXModule::cancel_order(Origin::signed(2), order_id, trading_pair, FixedU128::from(2), None), Error::<Test>::InvalidOrigin);
+        assert_noop!(DEXModule::cancel_order(Origin::signed(1), H256::repeat_byte(9), trading_pair, FixedU128::from(2), None), Error::<Test>::InvalidOrderID);
+
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].id, order_id);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 6 * UNIT);
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn bid_limit_releases_price_improvement() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+        // Bids 3 for one unit which fills at the ask price of 2
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(3), FixedU128::from(1), None));
+        // The payment stays reserved until the block is finalized
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 2 * UNIT);
+        DEXModule::on_finalize(1);
+
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 998 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1002 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), UNIT);
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].reserved, FixedU128::from(1));
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn crosses_of_a_trader_settle_as_one_net_transfer() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+
+        // Account 1 paid 6 and received 4 of asset 0 in two crosses, only the difference leaves its account
+        let delta = DEXModule::pending_settlement(1, 0);
+        assert_eq!((delta.credit, delta.reserved_debit), (FixedU128::from(4), FixedU128::from(6)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 994 * UNIT);
+        DEXModule::on_finalize(1);
+
+        assert_eq!(DEXModule::pending_settlement(1, 0), Default::default());
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 998 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1002 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1001 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 999 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), 0);
+        assert_eq!(DEXModule::tradable_balance(&0, &DEXModule::settlement_account()), Some(FixedU128::from(0)));
+    });
+}
+
+#[test]
+fn match_proceeds_are_spendable_once_the_block_is_finalized() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+
+        // The 3 of asset 1 bought are not paid to account 1 yet
+        assert_noop!(DEXModule::submit_order(Origin::signed(1), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(1003), None),
+                     Error::<Test>::InsufficientAssetBalance);
+        DEXModule::on_finalize(1);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(1003), None));
+    });
+}
+
+#[test]
+fn unpaid_settlement_is_dropped_without_holding_up_other_traders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        // A match is refused when the reserved balance of the order can't pay for it
+        pallet_generic_asset::Module::<Test>::unreserve(&1, &2, 3 * UNIT);
+        assert_noop!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(1), None),
+                     Error::<Test>::InsufficientReservedBalance);
+        assert_ok!(pallet_generic_asset::Module::<Test>::reserve(&1, &2, 3 * UNIT));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(1), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+
+        // Account 1 spends what it reserved for the match before the block is settled
+        pallet_generic_asset::Module::<Test>::unreserve(&0, &1, 2 * UNIT);
+        DEXModule::on_finalize(1);
+
+        // Only account 1 is dropped, account 3 pays and account 2 is paid what account 3 paid in
+        assert_eq!(DEXModule::pending_settlement(1, 0), Default::default());
+        assert_eq!(DEXModule::pending_settlement(2, 0), Default::default());
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 1000 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &3), 996 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &3), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1004 * UNIT);
+        assert_eq!(DEXModule::tradable_balance(&0, &DEXModule::settlement_account()), Some(FixedU128::from(0)));
+        // Asset 1 is settled for everyone
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1001 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &3), 1002 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 997 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), 0);
+    });
+}
+
+#[test]
+fn conversions_respect_asset_decimals() {
+    new_test_ext().execute_with(|| {
+        // Assets without decimals keep the precision of the native asset
+        assert_eq!(DEXModule::convert_fixed_u128_to_balance(&0, FixedU128::from(1)), Some(UNIT));
+
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 0, 6));
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 1, 18));
+
+        assert_eq!(DEXModule::convert_fixed_u128_to_balance(&0, FixedU128::from(1)), Some(1_000_000));
+        assert_eq!(DEXModule::convert_fixed_u128_to_balance(&1, FixedU128::from(1)), Some(1_000_000_000_000_000_000));
+        assert_eq!(DEXModule::convert_balance_to_fixed_u128(&0, 1_500_000), Some(FixedU128::saturating_from_rational(3, 2)));
+        assert_eq!(DEXModule::convert_balance_to_fixed_u128(&1, 1), Some(FixedU128::from_inner(1)));
+        // Digits beyond the asset's decimals are truncated
+        assert_eq!(DEXModule::convert_fixed_u128_to_balance(&0, FixedU128::from_inner(1_999_999_999_999)), Some(1));
+    });
+}
+
+#[test]
+fn whitelist_asset_rejects_invalid_or_changed_decimals() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(DEXModule::whitelist_asset(Origin::root(), 0, 19), Error::<Test>::InvalidAssetDecimals);
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 0, 6));
+        assert_ok!(DEXModule::remove_whitelisted_asset(Origin::root(), 0));
+        assert_noop!(DEXModule::whitelist_asset(Origin::root(), 0, 8), Error::<Test>::AssetDecimalsMismatch);
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 0, 6));
+    });
+}
+
+#[test]
+fn trade_settles_in_decimals_of_each_asset() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 0, 6));
+        assert_ok!(DEXModule::whitelist_asset(Origin::root(), 1, 18));
+        assert_ok!(DEXModule::register_new_orderbook(Origin::root(), 1, 0, FixedU128::from(0), FixedU128::from(0)));
+        let trading_pair = DEXModule::create_trading_pair_id(&1, &0);
+        let quantity = FixedU128::saturating_from_rational(1, 10_000);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), quantity, None));
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), 100_000_000_000_000);
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), quantity, None));
+        DEXModule::on_finalize(1);
+
+        // 0.0001 of the 18 decimals asset for 0.0002 of the 6 decimals asset
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1000 * UNIT + 100_000_000_000_000);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 1000 * UNIT - 200);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1000 * UNIT + 200);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), 0);
+    });
+}
+
+#[test]
+fn reversed_trading_pair_is_rejected() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_eq!(DEXModule::get_trading_pair(0, 1), Some(trading_pair));
+        assert_eq!(DEXModule::get_trading_pair(1, 0), None);
+
+        assert_noop!(DEXModule::register_new_orderbook(Origin::root(), 0, 1, FixedU128::from(0), FixedU128::from(0)),
+                     Error::<Test>::ReversedTradingPairExists);
+    });
+}
+
+#[test]
+fn trading_pair_is_found_by_symbol() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::set_asset_symbol(Origin::root(), 0, b"USDT".to_vec()));
+        assert_ok!(DEXModule::set_asset_symbol(Origin::root(), 1, b"BTC".to_vec()));
+        assert_eq!(DEXModule::get_trading_pair_by_symbol(b"USDT".to_vec(), b"BTC".to_vec()), Some(trading_pair));
+        assert_eq!(DEXModule::get_trading_pair_by_symbol(b"BTC".to_vec(), b"USDT".to_vec()), None);
+
+        assert_noop!(DEXModule::set_asset_symbol(Origin::root(), 0, b"BTC".to_vec()), Error::<Test>::AssetSymbolTaken);
+        assert_noop!(DEXModule::set_asset_symbol(Origin::root(), 0, Vec::new()), Error::<Test>::InvalidAssetSymbol);
+
+        // Renaming frees the old symbol
+        assert_ok!(DEXModule::set_asset_symbol(Origin::root(), 1, b"XBT".to_vec()));
+        assert_eq!(DEXModule::get_trading_pair_by_symbol(b"USDT".to_vec(), b"BTC".to_vec()), None);
+        assert_eq!(DEXModule::get_trading_pair_by_symbol(b"USDT".to_vec(), b"XBT".to_vec()), Some(trading_pair));
+    });
+}
+
+#[test]
+fn migration_to_v4_indexes_trading_pairs() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(6);
+        <Orderbooks<Test>>::insert(trading_pair, Orderbook::new(0, 1, trading_pair, FixedU128::from(0), FixedU128::from(0)));
+        StorageVersion::put(Releases::V3_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        assert_eq!(DEXModule::get_trading_pair(0, 1), Some(trading_pair));
+    });
+}
+
+#[test]
+fn migration_to_v4_indexes_one_of_two_reversed_trading_pairs() {
+    new_test_ext().execute_with(|| {
+        let (first_pair, second_pair) = (H256::repeat_byte(6), H256::repeat_byte(7));
+        <Orderbooks<Test>>::insert(first_pair, Orderbook::new(0, 1, first_pair, FixedU128::from(0), FixedU128::from(0)));
+        <Orderbooks<Test>>::insert(second_pair, Orderbook::new(1, 0, second_pair, FixedU128::from(0), FixedU128::from(0)));
+        StorageVersion::put(Releases::V3_0_0);
+
+        migrations::migrate::<Test>();
+
+        let indexed = (DEXModule::get_trading_pair(0, 1), DEXModule::get_trading_pair(1, 0));
+        assert!(indexed == (Some(first_pair), None) || indexed == (None, Some(second_pair)));
+    });
+}
+
+#[test]
+fn migration_to_v5_whitelists_assets_of_existing_orderbooks() {
+    new_test_ext().execute_with(|| {
+        // Book registered before assets had to be whitelisted
+        let trading_pair = DEXModule::create_trading_pair_id(&1, &0);
+        <Orderbooks<Test>>::insert(trading_pair, Orderbook::new(0, 1, trading_pair, FixedU128::from(0), FixedU128::from(0)));
+        assert_noop!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None),
+                     Error::<Test>::AssetNotWhitelisted);
+        StorageVersion::put(Releases::V4_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        assert!(DEXModule::is_whitelisted_asset(0));
+        assert!(DEXModule::is_whitelisted_asset(1));
+        assert_eq!(DEXModule::asset_decimals(0), Some(12));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+    });
+}
+
+// Test externalities with assets 0 and 1 whitelisted and the Orderbook and orders given at genesis
+fn genesis_ext(orderbooks: Vec<(u32, u32, FixedU128, FixedU128)>, orders: Vec<(u64, OrderType, u32, u32, FixedU128, FixedU128)>) -> sp_io::TestExternalities {
+    let mut storage = test_storage();
+    crate::GenesisConfig::<Test> {
+        whitelisted_assets: vec![(0, 12), (1, 12)],
+        asset_symbols: vec![],
+        orderbooks,
+        orders,
+    }.assimilate_storage(&mut storage).unwrap();
+    storage.into()
+}
+
+// Genesis storage is built without storage transactions, seed orders must not open one
+#[test]
+fn genesis_registers_orderbooks_and_places_orders() {
+    let trading_pair = DEXModule::create_trading_pair_id(&1, &0);
+    genesis_ext(vec![(1, 0, FixedU128::saturating_from_rational(1, 2), FixedU128::from(1))],
+                vec![(2, OrderType::AskLimit, 1, 0, FixedU128::saturating_from_rational(5, 2), FixedU128::from(3))]).execute_with(|| {
+        let orderbook = DEXModule::get_orderbooks(trading_pair);
+        assert_eq!(orderbook.best_ask_price, FixedU128::saturating_from_rational(5, 2));
+        assert_eq!(DEXModule::get_trading_pair(0, 1), Some(trading_pair));
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &2), 3 * UNIT);
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Genesis TradingPair has an asset that is not whitelisted")]
+fn genesis_rejects_orderbook_of_asset_not_whitelisted() {
+    genesis_ext(vec![(2, 0, FixedU128::from(0), FixedU128::from(0))], vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Genesis order price is not a multiple of the tick size")]
+fn genesis_rejects_order_off_the_tick_size() {
+    genesis_ext(vec![(1, 0, FixedU128::from(1), FixedU128::from(1))],
+                vec![(2, OrderType::AskLimit, 1, 0, FixedU128::saturating_from_rational(5, 2), FixedU128::from(3))]);
+}
+
+#[test]
+#[should_panic(expected = "Genesis order quantity is not a multiple of the lot size")]
+fn genesis_rejects_order_off_the_lot_size() {
+    genesis_ext(vec![(1, 0, FixedU128::from(0), FixedU128::from(2))],
+                vec![(2, OrderType::AskLimit, 1, 0, FixedU128::from(2), FixedU128::from(3))]);
+}
+
+// Account 2 sells 1 of asset 1 to account 1 at the price in the given block
+fn trade_at(trading_pair: H256, block: u64, price: FixedU128) {
+    frame_system::Module::<Test>::set_block_number(block);
+    assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, price, FixedU128::from(1), None));
+    assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, price, FixedU128::from(1), None));
+}
+
+#[test]
+fn migration_to_v6_indexes_market_info_of_every_block() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        trade_at(trading_pair, 1, FixedU128::from(2));
+        <crate::MarketInfoPairs<Test>>::remove(1);
+        StorageVersion::put(Releases::V5_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        assert_eq!(DEXModule::get_marketinfo_pairs(1), vec![trading_pair]);
+    });
+}
+
+#[test]
+fn old_market_data_is_rolled_up_and_removed() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        trade_at(trading_pair, 1, FixedU128::from(2));
+        trade_at(trading_pair, 5, FixedU128::from(4));
+        trade_at(trading_pair, 12, FixedU128::from(3));
+        assert_eq!(DEXModule::get_marketinfo_pairs(5), vec![trading_pair]);
+        let volume = DEXModule::get_market_info(trading_pair, 1).volume + DEXModule::get_market_info(trading_pair, 5).volume;
+
+        // Blocks before 10 are out of the retention, at most 5 of them are pruned per block
+        DEXModule::on_initialize(110);
+        assert!(DEXModule::get_marketdata(trading_pair, 1).is_none());
+        assert!(DEXModule::get_marketdata(trading_pair, 5).is_some());
+        assert_eq!(DEXModule::get_marketinfo_prune_cursor(), 5);
+
+        DEXModule::on_initialize(111);
+        assert!(DEXModule::get_marketdata(trading_pair, 5).is_none());
+        assert!(DEXModule::get_marketinfo_pairs(5).is_empty());
+        assert!(DEXModule::get_marketdata(trading_pair, 12).is_some());
+        assert_eq!(DEXModule::get_market_info_rollup(trading_pair, 0), Some(MarketDataRpc { low: 2 * UNIT, high: 4 * UNIT, volume }));
+        assert_eq!(DEXModule::get_market_info_rollup(trading_pair, 1), None);
+    });
+}
+
+#[test]
+fn ticker_rolls_over_the_ticker_window() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        trade_at(trading_pair, 5, FixedU128::from(2));
+        trade_at(trading_pair, 50, FixedU128::from(5));
+        trade_at(trading_pair, 95, FixedU128::from(3));
+
+        let ticker = DEXModule::get_ticker(trading_pair);
+        assert_eq!((ticker.open_price, ticker.last_price, ticker.price_change), (2 * UNIT, 3 * UNIT, UNIT as i128));
+        assert_eq!((ticker.high, ticker.low), (5 * UNIT, 2 * UNIT));
+        assert_eq!((ticker.base_volume, ticker.quote_volume, ticker.trade_count), (10 * UNIT, 3 * UNIT, 3));
+
+        // At block 115 the window starts at block 10, the slot of the bucket of block 5 is reused
+        trade_at(trading_pair, 115, FixedU128::from(4));
+        let ticker = DEXModule::get_ticker(trading_pair);
+        assert_eq!((ticker.open_price, ticker.last_price, ticker.price_change), (5 * UNIT, 4 * UNIT, -(UNIT as i128)));
+        assert_eq!((ticker.high, ticker.low), (5 * UNIT, 3 * UNIT));
+        assert_eq!((ticker.base_volume, ticker.quote_volume, ticker.trade_count), (12 * UNIT, 3 * UNIT, 3));
+    });
+}
+
+#[test]
+fn oracle_averages_prices_over_the_requested_window() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        trade_at(trading_pair, 1, FixedU128::from(2));
+        trade_at(trading_pair, 11, FixedU128::from(4));
+        frame_system::Module::<Test>::set_block_number(21);
+
+        // Blocks 6 to 21 traded at 2 for 5 blocks and at 4 for 10 blocks
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::twap(trading_pair, 15), Some(FixedU128::saturating_from_rational(10, 3)));
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::twap(trading_pair, 20), Some(FixedU128::from(3)));
+        // No observation at or before the start of the window
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::twap(trading_pair, 21), None);
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::twap(trading_pair, 25), None);
+
+        // Only the trade of block 11 is in the window
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::vwap(trading_pair, 15), Some(FixedU128::from(4)));
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::vwap(trading_pair, 5), None);
+        assert_eq!(<DEXModule as DexPriceOracle<H256, u64>>::last_price(trading_pair), Some(FixedU128::from(4)));
+    });
+}
+
+// Books 1/0 with a bid of 2 and 2/0 with an ask of 4 so that asset 1 can be swapped to asset 2 through asset 0
+fn setup_swap_books() -> (H256, H256) {
+    let first_pair = setup_orderbook();
+    assert_ok!(DEXModule::whitelist_asset(Origin::root(), 2, 12));
+    assert_ok!(DEXModule::register_new_orderbook(Origin::root(), 2, 0, FixedU128::from(0), FixedU128::from(0)));
+    let second_pair = DEXModule::create_trading_pair_id(&2, &0);
+    assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, first_pair, FixedU128::from(2), FixedU128::from(5), None));
+    assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, second_pair, FixedU128::from(4), FixedU128::from(5), None));
+    (first_pair, second_pair)
+}
+
+#[test]
+fn swap_via_path_chains_market_orders() {
+    new_test_ext().execute_with(|| {
+        let (first_pair, second_pair) = setup_swap_books();
+
+        // 1 of asset 1 sells for 2 of asset 0 which buys 0.5 of asset 2
+        assert_ok!(DEXModule::swap_via_path(Origin::signed(1), 1, vec![first_pair, second_pair], FixedU128::from(1), FixedU128::saturating_from_rational(1, 2)));
+
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 999 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 1000 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &1), 1000 * UNIT + UNIT / 2);
+        assert_orderbook_integrity(first_pair);
+        assert_orderbook_integrity(second_pair);
+    });
+}
+
+#[test]
+fn swap_via_path_reverts_when_output_is_too_low() {
+    new_test_ext().execute_with(|| {
+        let (first_pair, second_pair) = setup_swap_books();
+
+        assert_noop!(DEXModule::swap_via_path(Origin::signed(1), 1, vec![first_pair, second_pair], FixedU128::from(1), FixedU128::from(1)),
+                     Error::<Test>::SwapOutputTooLow);
+        assert_noop!(DEXModule::swap_via_path(Origin::signed(1), 2, vec![first_pair, second_pair], FixedU128::from(1), FixedU128::from(0)),
+                     Error::<Test>::InvalidSwapPath);
+    });
+}
+
+#[test]
+fn best_swap_path_goes_through_the_intermediate_asset() {
+    new_test_ext().execute_with(|| {
+        let (first_pair, second_pair) = setup_swap_books();
+
+        let best = DEXModule::get_best_swap_path(1, 2, FixedU128::from(1)).unwrap();
+        assert_eq!(best.path, vec![first_pair.to_fixed_bytes(), second_pair.to_fixed_bytes()]);
+        assert_eq!(best.amount_out, UNIT / 2);
+        assert_eq!(DEXModule::get_best_swap_path(2, 1, FixedU128::from(1)), None);
+    });
+}
+
+#[test]
+fn best_swap_path_quotes_the_pool_of_a_pair_without_orders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(100), FixedU128::from(0)));
+
+        // The quote pays the pool fee and price impact exactly like the swap does
+        let best = DEXModule::get_best_swap_path(0, 1, FixedU128::from(10)).unwrap();
+        assert_eq!(best.path, vec![trading_pair.to_fixed_bytes()]);
+        assert!(best.amount_out > 9 * UNIT && best.amount_out < 10 * UNIT);
+        assert_ok!(DEXModule::swap_via_path(Origin::signed(1), 0, vec![trading_pair], FixedU128::from(10), FixedU128::from(0)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1000 * UNIT + best.amount_out);
+
+        let best = DEXModule::get_best_swap_path(1, 0, FixedU128::from(5)).unwrap();
+        assert!(best.amount_out > 0 && best.amount_out < 6 * UNIT);
+        assert_ok!(DEXModule::swap_via_path(Origin::signed(2), 1, vec![trading_pair], FixedU128::from(5), FixedU128::from(0)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1000 * UNIT + best.amount_out);
+    });
+}
+
+#[test]
+fn liquidity_is_added_and_removed_at_the_pool_ratio() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(50), FixedU128::from(100)));
+        let share_asset_id = DEXModule::pool_share_asset(trading_pair).unwrap();
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&share_asset_id, &3), 100 * UNIT);
+
+        assert_ok!(DEXModule::remove_liquidity(Origin::signed(3), trading_pair, FixedU128::from(50), FixedU128::from(50), FixedU128::from(25)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &3), 950 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &3), 975 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&share_asset_id, &3), 50 * UNIT);
+
+        // The second provider adds at the 2:1 ratio of the pool
+        assert_noop!(DEXModule::add_liquidity(Origin::signed(2), trading_pair, FixedU128::from(10), FixedU128::from(4), FixedU128::from(0)),
+                     Error::<Test>::PoolRatioMismatch);
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(2), trading_pair, FixedU128::from(10), FixedU128::from(6), FixedU128::from(0)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 995 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&share_asset_id, &2), 10 * UNIT);
+
+        assert_noop!(DEXModule::remove_liquidity(Origin::signed(2), trading_pair, FixedU128::from(11), FixedU128::from(0), FixedU128::from(0)),
+                     Error::<Test>::InsufficientPoolShares);
+    });
+}
+
+#[test]
+fn market_order_is_filled_by_the_pool_without_resting_orders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(100), FixedU128::from(0)));
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidMarket, trading_pair, FixedU128::from(10), FixedU128::from(0), None));
+
+        // 10 of asset 0 buy a bit less than 100 * 9.97 / 109.97 of asset 1 after the pool fee
+        let received = pallet_generic_asset::Module::<Test>::free_balance(&1, &1) - 1000 * UNIT;
+        assert!(received > 9 * UNIT && received < 10 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 990 * UNIT);
+        let pool = DEXModule::pool_account(&trading_pair);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &pool), 110 * UNIT);
+    });
+}
+
+#[test]
+fn limit_order_takes_the_pool_when_it_is_cheaper_than_the_book() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(100), FixedU128::from(0)));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(5), None));
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(1), None));
+
+        // The pool sells at about 1 so the ask at 2 is left untouched and the unused reserve is released
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].quantity, FixedU128::from(5));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1001 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1) > 998 * UNIT);
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn pool_is_checked_again_before_every_level_of_the_book() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(150), FixedU128::from(100), FixedU128::from(0)));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(10), None));
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(20), None));
+
+        // The ask at 1 is cheaper than the pool at 1.5, the pool is cheaper than the ask at 2 for the other 10
+        assert!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(1)).orders.is_empty());
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].quantity, FixedU128::from(10));
+        let pool = DEXModule::pool_account(&trading_pair);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &pool), 90 * UNIT);
+        DEXModule::on_finalize(0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1020 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+// Rests an AskLimit order of trader directly in storage and reserves the quantity it requires
+fn put_resting_ask(trading_pair: H256, trader: u64, price: FixedU128, quantity: FixedU128) {
+    let order = Order::<Test> {
+        id: H256::repeat_byte(trader as u8),
+        trading_pair,
+        trader,
+        price,
+        quantity,
+        order_type: OrderType::AskLimit,
+        reserved: quantity,
+    };
+    let mut linked_pricelevel: LinkedPriceLevel<Test> = LinkedPriceLevel::default();
+    linked_pricelevel.orders.push_back(order);
+    <PriceLevels<Test>>::insert(trading_pair, price, linked_pricelevel);
+    <AsksLevels<Test>>::mutate(trading_pair, |asks_levels| {
+        asks_levels.push(price);
+        asks_levels.sort();
+    });
+    let reserved = DEXModule::convert_fixed_u128_to_balance(&1, quantity).unwrap();
+    assert_ok!(pallet_generic_asset::Module::<Test>::reserve(&1, &trader, reserved));
+}
+
+#[test]
+fn repair_orderbook_fixes_drifted_links_and_best_price() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(3);
+        DEXModule::create_order_book(1, 0, &trading_pair, FixedU128::from(0), FixedU128::from(0));
+        put_resting_ask(trading_pair, 1, FixedU128::from(5), FixedU128::from(1));
+        put_resting_ask(trading_pair, 2, FixedU128::from(7), FixedU128::from(1));
+
+        // Neither the links nor best_ask_price were written by put_resting_ask
+        let issues = DEXModule::check_orderbook_integrity(trading_pair);
+        assert!(issues.contains(&IntegrityIssue::BrokenLink(FixedU128::from(5))));
+        assert!(issues.contains(&IntegrityIssue::BestAskMismatch(FixedU128::from(0), FixedU128::from(5))));
+
+        assert_ok!(DEXModule::repair_orderbook(Origin::root(), trading_pair));
+
+        assert_orderbook_integrity(trading_pair);
+        assert_eq!(DEXModule::get_orderbooks(trading_pair).best_ask_price, FixedU128::from(5));
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(5)).next, Some(FixedU128::from(7)));
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(7)).prev, Some(FixedU128::from(5)));
+    });
+}
+
+#[test]
+fn integrity_check_reports_missing_reserve() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(4);
+        DEXModule::create_order_book(1, 0, &trading_pair, FixedU128::from(0), FixedU128::from(0));
+        put_resting_ask(trading_pair, 1, FixedU128::from(5), FixedU128::from(1));
+        DEXModule::rebuild_orderbook(trading_pair);
+        assert_orderbook_integrity(trading_pair);
+
+        pallet_generic_asset::Module::<Test>::unreserve(&1, &1, UNIT);
+
+        assert_eq!(DEXModule::check_orderbook_integrity(trading_pair),
+                   vec![IntegrityIssue::InsufficientReserve(1u64.encode(), 1)]);
+    });
+}
+
+#[test]
+fn migration_to_v7_records_resting_and_queued_orders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = H256::repeat_byte(4);
+        DEXModule::create_order_book(1, 0, &trading_pair, FixedU128::from(0), FixedU128::from(0));
+        put_resting_ask(trading_pair, 1, FixedU128::from(5), FixedU128::from(3));
+        let queued = Order::<Test> {
+            id: H256::repeat_byte(9),
+            trading_pair,
+            trader: 2,
+            price: FixedU128::from(4),
+            quantity: FixedU128::from(2),
+            order_type: OrderType::BidLimit,
+            reserved: FixedU128::from(8),
+        };
+        crate::PendingOrders::<Test>::insert(trading_pair, vec![queued]);
+        StorageVersion::put(Releases::V6_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        let resting = DEXModule::order_record(H256::repeat_byte(1)).unwrap();
+        assert_eq!((resting.trader, resting.order_type, resting.price, resting.remaining, resting.status),
+                   (1, OrderType::AskLimit, FixedU128::from(5), FixedU128::from(3), OrderStatus::Open));
+        let queued = DEXModule::order_record(H256::repeat_byte(9)).unwrap();
+        assert_eq!((queued.trader, queued.order_type, queued.remaining, queued.status, queued.closed_at),
+                   (2, OrderType::BidLimit, FixedU128::from(2), OrderStatus::Open, None));
+    });
+}
+
+#[test]
+fn migration_to_v8_starts_the_reward_period_from_its_index() {
+    new_test_ext().execute_with(|| {
+        RewardPeriodIndex::set(7);
+        StorageVersion::put(Releases::V7_0_0);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(DEXModule::storage_version(), migrations::LATEST_RELEASE);
+        assert_eq!(DEXModule::current_reward_period(), 7);
+    });
+}
+
+#[test]
+fn integrity_check_sums_reserves_across_trading_pairs() {
+    new_test_ext().execute_with(|| {
+        // Both books sell asset 1 so their asks share the reserved balance of asset 1
+        let (first_pair, second_pair) = (H256::repeat_byte(4), H256::repeat_byte(5));
+        DEXModule::create_order_book(1, 0, &first_pair, FixedU128::from(0), FixedU128::from(0));
+        DEXModule::create_order_book(1, 2, &second_pair, FixedU128::from(0), FixedU128::from(0));
+        put_resting_ask(first_pair, 1, FixedU128::from(5), FixedU128::from(3));
+        put_resting_ask(second_pair, 1, FixedU128::from(5), FixedU128::from(4));
+        assert_orderbook_integrity(first_pair);
+        assert_orderbook_integrity(second_pair);
+
+        // 5 still covers the asks of either book alone but not both
+        pallet_generic_asset::Module::<Test>::unreserve(&1, &1, 2 * UNIT);
+
+        assert_eq!(DEXModule::check_orderbook_integrity(first_pair), vec![IntegrityIssue::InsufficientReserve(1u64.encode(), 1)]);
+        assert_eq!(DEXModule::check_orderbook_integrity(second_pair), vec![IntegrityIssue::InsufficientReserve(1u64.encode(), 1)]);
+    });
+}
+
+#[test]
+fn maker_rewards_are_split_by_points_near_the_mid_price() {
+    new_test_ext().execute_with(|| {
+        RewardPeriodIndex::set(0);
+        let trading_pair = setup_orderbook();
+        // Mid price is 1.01 so orders between 0.9595 and 1.0605 earn points
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::saturating_from_rational(102, 100), FixedU128::from(30), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(1, 2), FixedU128::from(10), None));
+
+        assert_noop!(DEXModule::set_maker_reward(Origin::signed(1), trading_pair, 2, FixedU128::from(40)), sp_runtime::DispatchError::BadOrigin);
+        assert_noop!(DEXModule::set_maker_reward(Origin::root(), H256::default(), 2, FixedU128::from(40)), Error::<Test>::InvalidTradingPair);
+        assert_ok!(DEXModule::set_maker_reward(Origin::root(), trading_pair, 2, FixedU128::from(40)));
+        assert_ok!(pallet_generic_asset::Module::<Test>::make_transfer(&2, &1, &DEXModule::maker_reward_account(), 100 * UNIT));
+
+        DEXModule::on_initialize(2);
+        DEXModule::on_initialize(3);
+        assert_eq!(DEXModule::maker_reward_points(trading_pair, 2), FixedU128::from(20));
+        assert_eq!(DEXModule::maker_reward_points(trading_pair, 3), FixedU128::from(60));
+        assert_eq!(DEXModule::maker_reward_points(trading_pair, 1), FixedU128::from(0));
+
+        // The reward of the period is paid when the next one starts
+        RewardPeriodIndex::set(1);
+        DEXModule::on_initialize(4);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &2), 1010 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &3), 1030 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &DEXModule::maker_reward_account()), 60 * UNIT);
+        assert_eq!(DEXModule::maker_reward_points(trading_pair, 2), FixedU128::from(10));
+        assert_eq!(DEXModule::maker_reward_total_points(trading_pair), FixedU128::from(40));
+
+        // Ending the program forfeits the points of the current period
+        assert_ok!(DEXModule::set_maker_reward(Origin::root(), trading_pair, 2, FixedU128::from(0)));
+        RewardPeriodIndex::set(2);
+        DEXModule::on_initialize(5);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &2), 1010 * UNIT);
+        assert_eq!(DEXModule::maker_reward_points(trading_pair, 2), FixedU128::from(0));
+    });
+}
+
+// Orderbook that traded at 1 for MarginPriceWindow blocks to value margin accounts at, account 3 still asks 395 at 1
+fn setup_margin_market() -> H256 {
+    let trading_pair = setup_orderbook();
+    assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(400), None));
+    assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(5), None));
+    DEXModule::on_finalize(0);
+    frame_system::Module::<Test>::set_block_number(10);
+    trading_pair
+}
+
+#[test]
+fn margin_loans_accrue_interest_for_lenders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_margin_market();
+        assert_ok!(DEXModule::deposit_lending(Origin::signed(1), 0, FixedU128::from(500)));
+        assert_noop!(DEXModule::borrow(Origin::signed(2), trading_pair, 0, FixedU128::from(250)), Error::<Test>::NoMarginAccount);
+        assert_ok!(DEXModule::deposit_margin(Origin::signed(2), trading_pair, 0, FixedU128::from(100)));
+        assert_noop!(DEXModule::borrow(Origin::signed(2), trading_pair, 2, FixedU128::from(250)), Error::<Test>::AssetNotInTradingPair);
+        // 400 of debt would be above 75% of the 500 held
+        assert_noop!(DEXModule::borrow(Origin::signed(2), trading_pair, 0, FixedU128::from(400)), Error::<Test>::MarginAccountUnhealthy);
+        assert_ok!(DEXModule::borrow(Origin::signed(2), trading_pair, 0, FixedU128::from(250)));
+        assert_noop!(DEXModule::withdraw_lending(Origin::signed(1), 0, FixedU128::from(500)), Error::<Test>::InsufficientLendingLiquidity);
+
+        // Half of the pool is borrowed for a tenth of a year at 2% + 20% * 0.5
+        frame_system::Module::<Test>::set_block_number(110);
+        assert_ok!(DEXModule::repay(Origin::signed(2), trading_pair, 0, FixedU128::from(1000)));
+        assert_eq!(DEXModule::margin_account(trading_pair, 2), Some(MarginAccount::default()));
+        let margin_account_id = DEXModule::margin_account_id(&trading_pair, &2);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &margin_account_id), 97 * UNIT);
+
+        assert_ok!(DEXModule::withdraw_lending(Origin::signed(1), 0, FixedU128::from(500)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 998 * UNIT);
+        assert_eq!(DEXModule::lending_shares(0, 1), FixedU128::from(0));
+    });
+}
+
+#[test]
+fn margin_account_is_liquidated_with_market_orders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_margin_market();
+        assert_ok!(DEXModule::deposit_lending(Origin::signed(1), 0, FixedU128::from(500)));
+        assert_ok!(DEXModule::deposit_margin(Origin::signed(2), trading_pair, 0, FixedU128::from(100)));
+        assert_ok!(DEXModule::borrow(Origin::signed(2), trading_pair, 0, FixedU128::from(250)));
+        // Buying 295 of the quote asset leaves 55 of the base asset
+        assert_ok!(DEXModule::submit_margin_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(295)));
+        assert_noop!(DEXModule::withdraw_margin(Origin::signed(2), trading_pair, 0, FixedU128::from(50)), Error::<Test>::MarginAccountUnhealthy);
+        assert_noop!(DEXModule::liquidate_margin_account(Origin::signed(3), trading_pair, 2), Error::<Test>::MarginAccountHealthy);
+        // A resting margin order is tracked so the liquidation can cancel it
+        assert_ok!(DEXModule::submit_margin_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(5, 10), FixedU128::from(20)));
+        assert_eq!(DEXModule::margin_open_orders(trading_pair, 2).len(), 1);
+
+        // The quote asset drops to 0.7, a single trade doesn't move the average price margin accounts are valued at
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(7, 10), FixedU128::from(100), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(5, 10), FixedU128::from(400), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::saturating_from_rational(7, 10), FixedU128::from(10), None));
+        assert_noop!(DEXModule::liquidate_margin_account(Origin::signed(3), trading_pair, 2), Error::<Test>::MarginAccountHealthy);
+        // The quote asset the margin account bought is paid to it when the block is settled
+        DEXModule::on_finalize(10);
+
+        // Once the window only covers the price of 0.7 the health factor is (55 + 295 * 0.7) * 0.8 / 250.6,
+        // the debt grew by 12% a year for 20 blocks
+        frame_system::Module::<Test>::set_block_number(30);
+        assert_eq!(DEXModule::margin_health(&trading_pair, &2), Some(FixedU128::saturating_from_rational(2092, 2506)));
+
+        // The 55 held and the 10 reserved by the cancelled order repay part of the debt, the bids at 0.5 are
+        // beyond the 10% slippage allowed so only the 90 left at 0.7 are sold for another 63
+        let liquidator_quote = pallet_generic_asset::Module::<Test>::free_balance(&1, &3);
+        assert_ok!(DEXModule::liquidate_margin_account(Origin::signed(3), trading_pair, 2));
+        assert!(DEXModule::margin_account(trading_pair, 2).unwrap().base_debt > FixedU128::from(0));
+        assert!(DEXModule::margin_open_orders(trading_pair, 2).is_empty());
+        let margin_account_id = DEXModule::margin_account_id(&trading_pair, &2);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &margin_account_id), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &margin_account_id), 0);
+        // The liquidator earns 5% of the 118 repaid, 5.9 paid in the quote asset at 0.7
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &margin_account_id), 196_571_428_571_429);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &3), liquidator_quote + 8_428_571_428_571);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &DEXModule::lending_account(&0)), 368 * UNIT);
+        let bids_at_half = DEXModule::get_pricelevels(trading_pair, FixedU128::saturating_from_rational(5, 10)).orders;
+        assert_eq!(bids_at_half.len(), 1);
+        assert_eq!(bids_at_half[0].quantity, FixedU128::from(400));
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn offchain_matched_batch_is_settled() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::set_offchain_matching(Origin::root(), trading_pair, true));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(11, 10), FixedU128::from(4), None));
+        // Nothing is matched in the block
+        assert_eq!(DEXModule::pending_orders(trading_pair).len(), 2);
+        assert_eq!(DEXModule::get_orderbooks(trading_pair).best_ask_price, FixedU128::from(0));
+        assert_noop!(DEXModule::set_offchain_matching(Origin::root(), trading_pair, false), Error::<Test>::PendingOrdersNotSettled);
+
+        let ask_id = DEXModule::pending_orders(trading_pair)[0].id;
+        let batch = DEXModule::compute_offchain_batch(trading_pair).unwrap();
+        assert_eq!(batch.orders, 2);
+        assert_eq!(batch.fills, vec![OffchainFill { maker: ask_id, quantity: FixedU128::from(4) }]);
+
+        // Fills that differ from what the matching engine would do are rejected
+        let mut tampered = batch.clone();
+        tampered.fills[0].quantity = FixedU128::from(3);
+        assert_noop!(DEXModule::settle_offchain_batch(Origin::none(), tampered), Error::<Test>::InvalidOffchainBatch);
+        let mut tampered = batch.clone();
+        tampered.fills.clear();
+        assert_noop!(DEXModule::settle_offchain_batch(Origin::none(), tampered), Error::<Test>::InvalidOffchainBatch);
+
+        assert_ok!(DEXModule::settle_offchain_batch(Origin::none(), batch));
+        DEXModule::on_finalize(1);
+        assert!(DEXModule::pending_orders(trading_pair).is_empty());
+        assert_eq!(DEXModule::get_orderbooks(trading_pair).best_ask_price, FixedU128::from(1));
+        // The bid pays the price of the resting ask
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 996 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &2), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 1004 * UNIT);
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn offchain_batch_must_follow_price_time_priority() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::set_offchain_matching(Origin::root(), trading_pair, true));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(2), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(2), None));
+        let batch = DEXModule::compute_offchain_batch(trading_pair).unwrap();
+        assert!(batch.fills.is_empty());
+        assert_ok!(DEXModule::settle_offchain_batch(Origin::none(), batch));
+        let resting = DEXModule::get_pricelevels(trading_pair, FixedU128::from(1)).orders;
+        let (oldest, newest) = (resting[0].id, resting[1].id);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(1), None));
+        let batch = DEXModule::compute_offchain_batch(trading_pair).unwrap();
+        assert_eq!(batch.fills, vec![OffchainFill { maker: oldest, quantity: FixedU128::from(1) }]);
+        // The fill is executed on chain, the worker only picks its maker and can't pick another one
+        let mut tampered = batch.clone();
+        tampered.fills[0].maker = newest;
+        assert_noop!(DEXModule::settle_offchain_batch(Origin::none(), tampered), Error::<Test>::InvalidOffchainBatch);
+
+        assert_ok!(DEXModule::settle_offchain_batch(Origin::none(), batch));
+        let resting = DEXModule::get_pricelevels(trading_pair, FixedU128::from(1)).orders;
+        assert_eq!((resting[0].id, resting[0].quantity), (oldest, FixedU128::from(1)));
+        assert_eq!((resting[1].id, resting[1].quantity), (newest, FixedU128::from(2)));
+        assert_orderbook_integrity(trading_pair);
+    });
+}
+
+#[test]
+fn queued_order_can_be_cancelled() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::set_offchain_matching(Origin::root(), trading_pair, true));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(4), None));
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &2), 4 * UNIT);
+        let order_id = DEXModule::pending_orders(trading_pair)[0].id;
+        assert_noop!(DEXModule::cancel_order(Origin::signed(3), order_id, trading_pair, FixedU128::from(1), None), Error::<Test>::InvalidOrigin);
+        assert_ok!(DEXModule::cancel_order(Origin::signed(2), order_id, trading_pair, FixedU128::from(1), None));
+        assert!(DEXModule::pending_orders(trading_pair).is_empty());
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &2), 0);
+        assert_eq!(DEXModule::compute_offchain_batch(trading_pair), None);
+        assert_ok!(DEXModule::set_offchain_matching(Origin::root(), trading_pair, false));
+    });
+}
+
+#[test]
+fn relayer_submits_orders_signed_by_traders() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        let order = SignedOrder {
+            trader: 3,
+            trading_pair,
+            order_type: OrderType::AskLimit,
+            price: FixedU128::from(1),
+            quantity: FixedU128::from(10),
+            expiry: 5,
+            salt: 7,
+        };
+        let signature = TestSignature(3, DEXModule::signed_order_payload(&order));
+        // Signed by another account than the trader
+        assert_noop!(DEXModule::submit_signed_orders(Origin::signed(1), vec![(order.clone(), TestSignature(1, DEXModule::signed_order_payload(&order)))]),
+                     Error::<Test>::InvalidOrderSignature);
+        // Signed without the domain and the genesis hash of the chain
+        assert_noop!(DEXModule::submit_signed_orders(Origin::signed(1), vec![(order.clone(), TestSignature(3, order.encode()))]),
+                     Error::<Test>::InvalidOrderSignature);
+        let mut altered = order.clone();
+        altered.quantity = FixedU128::from(20);
+        assert_noop!(DEXModule::submit_signed_orders(Origin::signed(1), vec![(altered, signature.clone())]), Error::<Test>::InvalidOrderSignature);
+
+        assert_ok!(DEXModule::submit_signed_orders(Origin::signed(1), vec![(order.clone(), signature.clone())]));
+        assert!(DEXModule::is_signed_order_salt_used(3, 7));
+        assert_eq!(DEXModule::get_orderbooks(trading_pair).best_ask_price, FixedU128::from(1));
+        // The trader's assets are reserved, the relayer's are untouched
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &3), 10 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &1), 0);
+        assert_noop!(DEXModule::submit_signed_orders(Origin::signed(2), vec![(order.clone(), signature)]), Error::<Test>::SignedOrderReplayed);
+
+        let mut expired = order;
+        expired.salt = 8;
+        let signature = TestSignature(3, DEXModule::signed_order_payload(&expired));
+        frame_system::Module::<Test>::set_block_number(6);
+        assert_noop!(DEXModule::submit_signed_orders(Origin::signed(1), vec![(expired, signature)]), Error::<Test>::SignedOrderExpired);
+        assert_orderbook_integrity(trading_pair);
+
+        // The salt is forgotten once the order can't be executed any more
+        DEXModule::on_initialize(6);
+        assert!(!DEXModule::is_signed_order_salt_used(3, 7));
+    });
+}
+
+#[test]
+fn committed_orders_are_executed_the_block_after_their_reveal() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        frame_system::Module::<Test>::set_block_number(1);
+        let ask = DEXModule::order_commitment_hash(&3, &OrderType::AskLimit, &trading_pair, FixedU128::from(1), FixedU128::from(10), 1);
+        let bid = DEXModule::order_commitment_hash(&2, &OrderType::BidLimit, &trading_pair, FixedU128::from(1), FixedU128::from(4), 2);
+        let unrevealed = DEXModule::order_commitment_hash(&1, &OrderType::BidLimit, &trading_pair, FixedU128::from(1), FixedU128::from(1), 3);
+        assert_ok!(DEXModule::commit_order(Origin::signed(3), ask));
+        assert_ok!(DEXModule::commit_order(Origin::signed(2), bid));
+        assert_ok!(DEXModule::commit_order(Origin::signed(1), unrevealed));
+        assert_noop!(DEXModule::commit_order(Origin::signed(3), ask), Error::<Test>::OrderCommitmentExists);
+        // Every commitment reserves the deposit in the spending asset
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), UNIT);
+        assert_noop!(DEXModule::reveal_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), 1),
+                     Error::<Test>::RevealTooEarly);
+
+        frame_system::Module::<Test>::set_block_number(2);
+        // The salt is part of the commitment
+        assert_noop!(DEXModule::reveal_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), 2),
+                     Error::<Test>::NoOrderCommitment);
+        assert_ok!(DEXModule::reveal_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(4), 2));
+        assert_ok!(DEXModule::reveal_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(1), FixedU128::from(10), 1));
+        assert_eq!(DEXModule::order_commitment(3, ask), None);
+        assert_eq!(DEXModule::revealed_orders().len(), 2);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &3), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &3), 0);
+        // Only MaxRevealsPerBlock orders are revealed in a block
+        assert_noop!(DEXModule::reveal_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(1), 3),
+                     Error::<Test>::TooManyReveals);
+
+        frame_system::Module::<Test>::set_block_number(3);
+        DEXModule::on_initialize(3);
+        DEXModule::on_finalize(3);
+        assert!(DEXModule::revealed_orders().is_empty());
+        // Both orders are committed in the same block so either can be the maker, at the same price
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 1004 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 996 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &3), 6 * UNIT);
+        assert_orderbook_integrity(trading_pair);
+
+        assert_eq!(DEXModule::order_commitment(1, unrevealed), Some(1));
+        frame_system::Module::<Test>::set_block_number(5);
+        DEXModule::on_initialize(5);
+        assert_eq!(DEXModule::order_commitment(1, unrevealed), None);
+        // The deposit of the commitment that was never revealed is forfeited
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 999 * UNIT);
+    });
+}
+
+#[test]
+fn delegate_trades_on_behalf_of_the_granting_account() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_noop!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), Some(1)),
+                     Error::<Test>::NoTradingPermission);
+        assert_ok!(DEXModule::grant_trading_permission(Origin::signed(1), 2, vec![trading_pair], Some(FixedU128::from(10))));
+        assert_noop!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(6), Some(1)),
+                     Error::<Test>::NotionalAboveLimit);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), Some(1)));
+        // The order belongs to the granting account
+        let order = DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].clone();
+        assert_eq!(order.trader, 1);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 6 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &2), 0);
+        assert_noop!(DEXModule::cancel_order(Origin::signed(3), order.id, trading_pair, FixedU128::from(2), Some(1)), Error::<Test>::NoTradingPermission);
+        assert_ok!(DEXModule::cancel_order(Origin::signed(2), order.id, trading_pair, FixedU128::from(2), Some(1)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&0, &1), 0);
+
+        // A market ask with nothing to sell into can't be valued, once there is a pool it is valued at the pool price of 2
+        assert_noop!(DEXModule::submit_order(Origin::signed(2), OrderType::AskMarket, trading_pair, FixedU128::from(0), FixedU128::from(3), Some(1)),
+                     Error::<Test>::UnknownNotional);
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(50), FixedU128::from(100)));
+        assert_noop!(DEXModule::submit_order(Origin::signed(2), OrderType::AskMarket, trading_pair, FixedU128::from(0), FixedU128::from(6), Some(1)),
+                     Error::<Test>::NotionalAboveLimit);
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskMarket, trading_pair, FixedU128::from(0), FixedU128::from(5), Some(1)));
+
+        assert_ok!(DEXModule::revoke_trading_permission(Origin::signed(1), 2));
+        assert_noop!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), Some(1)),
+                     Error::<Test>::NoTradingPermission);
+    });
+}
+
+#[test]
+fn market_makers_are_rewarded_only_for_meeting_obligations() {
+    new_test_ext().execute_with(|| {
+        RewardPeriodIndex::set(0);
+        let trading_pair = setup_orderbook();
+        assert_noop!(DEXModule::register_market_maker(Origin::root(), trading_pair, 2), Error::<Test>::NoMarketMakerObligation);
+        assert_ok!(DEXModule::set_market_maker_obligation(Origin::root(), trading_pair, Permill::from_percent(10), FixedU128::from(5),
+                                                          Permill::from_percent(50), 2, FixedU128::from(100)));
+        assert_ok!(DEXModule::register_market_maker(Origin::root(), trading_pair, 2));
+        assert_ok!(DEXModule::register_market_maker(Origin::root(), trading_pair, 3));
+        assert_noop!(DEXModule::register_market_maker(Origin::root(), trading_pair, 3), Error::<Test>::AlreadyMarketMaker);
+        assert_ok!(pallet_generic_asset::Module::<Test>::make_transfer(&2, &1, &DEXModule::maker_reward_account(), 500 * UNIT));
+
+        // Mid price is 1.005, account 2 quotes 0.04 wide while the bid of account 3 is too small
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(98, 100), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::saturating_from_rational(102, 100), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(99, 100), FixedU128::from(1), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::saturating_from_rational(15, 10), FixedU128::from(10), None));
+
+        DEXModule::on_initialize(2);
+        DEXModule::on_initialize(3);
+        assert_eq!(DEXModule::market_maker_measured_blocks(trading_pair), 2);
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 2), Some(2));
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 3), Some(0));
+
+        RewardPeriodIndex::set(1);
+        DEXModule::on_initialize(4);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &2), 1100 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &3), 1000 * UNIT);
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 3), None);
+        // The next period is measured from the block that started it
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 2), Some(1));
+        assert_eq!(DEXModule::market_maker_measured_blocks(trading_pair), 1);
+
+        // A maker registered during the period is only measured against the blocks since its registration
+        DEXModule::on_initialize(5);
+        DEXModule::on_initialize(6);
+        assert_ok!(DEXModule::register_market_maker(Origin::root(), trading_pair, 1));
+        assert_eq!(DEXModule::market_maker_registered_at(trading_pair, 1), 3);
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::saturating_from_rational(99, 100), FixedU128::from(5), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::AskLimit, trading_pair, FixedU128::saturating_from_rational(101, 100), FixedU128::from(5), None));
+        DEXModule::on_initialize(7);
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 1), Some(1));
+        RewardPeriodIndex::set(2);
+        DEXModule::on_initialize(8);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &1), 600 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&2, &2), 1200 * UNIT);
+        assert_eq!(DEXModule::market_maker_registered_at(trading_pair, 1), 0);
+
+        assert_ok!(DEXModule::end_market_maker_program(Origin::root(), trading_pair));
+        assert_eq!(DEXModule::market_maker_quoted_blocks(trading_pair, 2), None);
+        assert_noop!(DEXModule::remove_market_maker(Origin::root(), trading_pair, 2), Error::<Test>::NotMarketMaker);
+    });
+}
+
+#[test]
+fn orders_trade_from_the_trading_ledger() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::deposit_trading_balance(Origin::signed(2), 0, FixedU128::from(50)));
+        assert_ok!(DEXModule::deposit_trading_balance(Origin::signed(2), 1, FixedU128::from(1)));
+        assert_ok!(DEXModule::deposit_trading_balance(Origin::signed(3), 1, FixedU128::from(100)));
+        assert_ok!(DEXModule::deposit_trading_balance(Origin::signed(3), 0, FixedU128::from(1)));
+        assert_noop!(DEXModule::deposit_trading_balance(Origin::signed(3), 0, FixedU128::from(0)), Error::<Test>::InvalidTradingBalanceAmount);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(10), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(4), None));
+        DEXModule::on_finalize(1);
+        assert_eq!(DEXModule::trading_balance(2, 0), Some(TradingBalance { free: FixedU128::from(42), reserved: FixedU128::from(0) }));
+        assert_eq!(DEXModule::trading_balance(2, 1), Some(TradingBalance { free: FixedU128::from(5), reserved: FixedU128::from(0) }));
+        assert_eq!(DEXModule::trading_balance(3, 0), Some(TradingBalance { free: FixedU128::from(9), reserved: FixedU128::from(0) }));
+        assert_eq!(DEXModule::trading_balance(3, 1), Some(TradingBalance { free: FixedU128::from(90), reserved: FixedU128::from(6) }));
+        // Nothing moved in pallet_generic_asset while matching
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 950 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &3), 900 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::reserved_balance(&1, &3), 0);
+
+        // A trader outside the ledger trades with the ledger through the ledger account
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+        DEXModule::on_finalize(1);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 996 * UNIT);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &1), 1002 * UNIT);
+        assert_eq!(DEXModule::trading_balance(3, 0).unwrap().free, FixedU128::from(13));
+        assert_eq!(DEXModule::trading_balance_total(1), FixedU128::from(99));
+        assert_orderbook_integrity(trading_pair);
+
+        assert_noop!(DEXModule::withdraw_trading_balance(Origin::signed(2), 1, FixedU128::from(6)), Error::<Test>::InsufficientTradingBalance);
+        assert_ok!(DEXModule::withdraw_trading_balance(Origin::signed(2), 1, FixedU128::from(5)));
+        assert_eq!(DEXModule::trading_balance(2, 1), None);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&1, &2), 1004 * UNIT);
+        assert!(DEXModule::is_trading_ledger_balanced(&1));
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(1), None));
+        assert_noop!(DEXModule::deposit_trading_balance(Origin::signed(1), 0, FixedU128::from(10)), Error::<Test>::ReservedOutsideTradingLedger);
+    });
+}
+
+#[test]
+fn referrer_earns_a_share_of_the_pool_fees_of_its_referee() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::add_liquidity(Origin::signed(3), trading_pair, FixedU128::from(100), FixedU128::from(100), FixedU128::from(0)));
+        assert_noop!(DEXModule::register_referrer(Origin::signed(1), 1), Error::<Test>::InvalidReferrer);
+        assert_ok!(DEXModule::register_referrer(Origin::signed(1), 2));
+        assert_noop!(DEXModule::register_referrer(Origin::signed(1), 3), Error::<Test>::ReferrerAlreadyRegistered);
+
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidMarket, trading_pair, FixedU128::from(10), FixedU128::from(0), None));
+
+        // 20% of the 0.3% pool fee on the 10 of asset 0 paid into the pool
+        let share = FixedU128::saturating_from_rational(6, 1000);
+        assert_eq!(DEXModule::referral_reward(2, 0), share);
+        let pool = DEXModule::pool_account(&trading_pair);
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &pool), 110 * UNIT - 6 * UNIT / 1000);
+        let mut referrer = [0u8; 32];
+        referrer[0] = 2;
+        assert_eq!(DEXModule::get_referral_rewards(referrer).len(), 1);
+
+        assert_ok!(DEXModule::claim_referral_rewards(Origin::signed(2)));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &2), 1000 * UNIT + 6 * UNIT / 1000);
+        assert_eq!(DEXModule::referral_reward(2, 0), FixedU128::from(0));
+        assert_noop!(DEXModule::claim_referral_rewards(Origin::signed(2)), Error::<Test>::NoReferralRewards);
+    });
+}
+
+#[test]
+fn order_record_follows_the_order_until_it_is_pruned() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        frame_system::Module::<Test>::set_block_number(1);
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(5), None));
+        let order_id = DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].id;
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(2), FixedU128::from(2), None));
+
+        let record = DEXModule::order_record(order_id).unwrap();
+        assert_eq!((record.remaining, record.status, record.closed_at), (FixedU128::from(3), OrderStatus::Open, None));
+        let rpc = DEXModule::get_order(order_id).unwrap();
+        assert_eq!((rpc.remaining_quantity, rpc.filled_quantity, rpc.status), (3 * UNIT, 2 * UNIT, OrderStatus::Open));
+
+        frame_system::Module::<Test>::set_block_number(2);
+        assert_ok!(DEXModule::cancel_order(Origin::signed(2), order_id, trading_pair, FixedU128::from(2), None));
+        let record = DEXModule::order_record(order_id).unwrap();
+        assert_eq!((record.status, record.closed_at), (OrderStatus::Cancelled, Some(2)));
+
+        // Kept for OrderHistoryRetention blocks after it was cancelled
+        DEXModule::on_initialize(11);
+        assert!(DEXModule::order_record(order_id).is_some());
+        DEXModule::on_initialize(12);
+        assert!(DEXModule::order_record(order_id).is_none());
+        assert!(DEXModule::get_order(order_id).is_none());
+    });
+}
+
+#[test]
+fn depth_aggregates_the_best_price_levels_first() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(3), FixedU128::from(1), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(3), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(4), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(1), OrderType::BidLimit, trading_pair, FixedU128::from(1), FixedU128::from(5), None));
+
+        let depth = DEXModule::get_depth(trading_pair, 1);
+        assert_eq!(depth.asks.len(), 1);
+        assert_eq!((depth.asks[0].price, depth.asks[0].quantity, depth.asks[0].order_count), (2 * UNIT, 7 * UNIT, 2));
+        assert_eq!((depth.bids[0].price, depth.bids[0].quantity, depth.bids[0].order_count), (UNIT, 5 * UNIT, 1));
+
+        let depth = DEXModule::get_depth(trading_pair, 10);
+        assert_eq!(depth.asks.iter().map(|level| level.price).collect::<Vec<u128>>(), vec![2 * UNIT, 3 * UNIT]);
+        assert_eq!(depth.bids.len(), 1);
+    });
+}
+
+#[test]
+fn simulated_order_reports_its_fills_without_executing() {
+    new_test_ext().execute_with(|| {
+        let trading_pair = setup_orderbook();
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(2), FixedU128::from(3), None));
+        assert_ok!(DEXModule::submit_order(Origin::signed(2), OrderType::AskLimit, trading_pair, FixedU128::from(3), FixedU128::from(4), None));
+        let mut trader = [0u8; 32];
+        trader[0] = 1;
+
+        let simulation = DEXModule::simulate_order(trader, OrderType::BidLimit, trading_pair, FixedU128::from(3), FixedU128::from(6));
+        assert_eq!(simulation.error, None);
+        assert_eq!(simulation.fills.iter().map(|fill| (fill.price, fill.quantity, fill.from_pool)).collect::<Vec<_>>(),
+                   vec![(2 * UNIT, 3 * UNIT, false), (3 * UNIT, 3 * UNIT, false)]);
+        // 15 of asset 0 for 6 of asset 1
+        assert_eq!((simulation.filled_quantity, simulation.average_price), (6 * UNIT, 25 * UNIT / 10));
+        assert_eq!((simulation.fee, simulation.remaining_quantity, simulation.resting), (0, 0, false));
+
+        // Nothing was executed
+        assert_eq!(DEXModule::get_pricelevels(trading_pair, FixedU128::from(2)).orders[0].quantity, FixedU128::from(3));
+        assert_eq!(pallet_generic_asset::Module::<Test>::free_balance(&0, &1), 1000 * UNIT);
+
+        let simulation = DEXModule::simulate_order(trader, OrderType::BidLimit, trading_pair, FixedU128::from(3), FixedU128::from(1000));
+        assert_eq!(simulation.error, Some(b"InsufficientAssetBalance".to_vec()));
+    });
+}
@@ -44,7 +44,7 @@ pub use pallet_balances::Call as BalancesCall;
 pub use sp_runtime::{Permill, Perbill};
 pub use frame_support::{
 	construct_runtime, parameter_types, StorageValue,
-	traits::{KeyOwnerProofSystem, Randomness},
+	traits::{Get, KeyOwnerProofSystem, Randomness},
 	weights::{
 		Weight, IdentityFee,
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
@@ -53,7 +53,16 @@ pub use frame_support::{
 
 /// Import the template pallet.
 pub use template;
+use template::IntegrityIssue;
+use template::SwapPathRpc;
+use template::LiquidityPoolRpc;
+use template::ReferralRewardRpc;
+use template::OrderRecordRpc;
+use template::DepthRpc;
+use template::OrderType;
+use template::SimulatedOrderRpc;
 use template::LinkedPriceLevelRpc;
+use template::TickerRpc;
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -419,12 +428,95 @@ impl pallet_generic_asset::Trait for Runtime {
 parameter_types! {
 	/// Cost for Registering a Trading Pair
 	pub const TradingPairReservationFee: u128 = 1_000_000_000_000;
+	/// MarketData of a block is kept for a week
+	pub const MarketDataRetention: BlockNumber = 7 * DAYS;
+	/// Pruned MarketData is rolled up into hourly buckets
+	pub const MarketDataRollupPeriod: BlockNumber = HOURS;
+	pub const MaxMarketDataPrunedPerBlock: u32 = 10;
+	/// Ticker statistics cover the last 24 hours in hourly steps
+	pub const TickerWindow: BlockNumber = DAYS;
+	pub const TickerBucketPeriod: BlockNumber = HOURS;
+	/// Price oracle snapshots are taken at most every 10 minutes and kept for a week
+	pub const OracleObservationPeriod: BlockNumber = 10 * MINUTES;
+	pub const OracleMaxObservations: u32 = 7 * 24 * 6;
+	/// Liquidity pools keep 0.3% of every amount paid in
+	pub const PoolSwapFee: Permill = Permill::from_perthousand(3);
+	/// Orders within 2% of the mid price earn maker rewards
+	pub const MakerRewardSpread: Permill = Permill::from_percent(2);
+	/// Borrow rates are yearly
+	pub const BlocksPerYear: BlockNumber = 365 * DAYS;
+	/// Loans cost 2% a year plus up to 20% more as their lending pool is borrowed out
+	pub const BaseBorrowRate: Permill = Permill::from_percent(2);
+	pub const BorrowRateSlope: Permill = Permill::from_percent(20);
+	/// Margin accounts can borrow up to 75% of their holdings and are liquidated above 80%
+	pub const MaxLoanToValue: Permill = Permill::from_percent(75);
+	pub const LiquidationLoanToValue: Permill = Permill::from_percent(80);
+	/// Margin accounts are valued at the average price of the last hour
+	pub const MarginPriceWindow: BlockNumber = HOURS;
+	/// Liquidations sell within 5% of that price and pay 2% of the debt they repay to the liquidator
+	pub const MaxLiquidationSlippage: Permill = Permill::from_percent(5);
+	pub const LiquidationReward: Permill = Permill::from_percent(2);
+	/// Off-chain workers settle at most 100 queued orders per batch
+	pub const MaxOffchainBatchOrders: u32 = 100;
+	pub const OffchainBatchPriority: TransactionPriority = TransactionPriority::max_value() / 2;
+	pub const MaxSignedOrdersPerBatch: u32 = 100;
+	pub const RevealWindow: BlockNumber = 10;
+	/// Committing to an order reserves 1 unit of the spending asset until it is revealed
+	pub const CommitmentDeposit: u128 = 1_000_000_000_000;
+	pub const MaxRevealsPerBlock: u32 = 100;
+	/// Referrers earn 20% of the pool swap fees paid by the traders they referred
+	pub const ReferralFeeShare: Permill = Permill::from_percent(20);
+	/// Filled and cancelled orders can be queried for a week
+	pub const OrderHistoryRetention: BlockNumber = 7 * DAYS;
+	/// Limit order matches of a block settle at most 200 trader balances
+	pub const MaxSettlementsPerBlock: u32 = 200;
+}
+
+/// Maker rewards of the DEX are paid whenever a new session starts
+pub struct CurrentSessionIndex;
+
+impl Get<u32> for CurrentSessionIndex {
+	fn get() -> u32 {
+		Session::current_index()
+	}
 }
 
 /// Configure the pallet template in pallets/template.
 impl template::Trait for Runtime {
 	type Event = Event;
 	type TradingPairReservationFee = TradingPairReservationFee;
+	type MarketDataRetention = MarketDataRetention;
+	type MarketDataRollupPeriod = MarketDataRollupPeriod;
+	type MaxMarketDataPrunedPerBlock = MaxMarketDataPrunedPerBlock;
+	type TickerWindow = TickerWindow;
+	type TickerBucketPeriod = TickerBucketPeriod;
+	type OracleObservationPeriod = OracleObservationPeriod;
+	type OracleMaxObservations = OracleMaxObservations;
+	type RegisterPairOrigin = EnsureRoot<AccountId>;
+	type RepairOrigin = EnsureRoot<AccountId>;
+	type PoolSwapFee = PoolSwapFee;
+	type RewardPeriodIndex = CurrentSessionIndex;
+	type MakerRewardSpread = MakerRewardSpread;
+	type BlocksPerYear = BlocksPerYear;
+	type BaseBorrowRate = BaseBorrowRate;
+	type BorrowRateSlope = BorrowRateSlope;
+	type MaxLoanToValue = MaxLoanToValue;
+	type LiquidationLoanToValue = LiquidationLoanToValue;
+	type MarginPriceWindow = MarginPriceWindow;
+	type MaxLiquidationSlippage = MaxLiquidationSlippage;
+	type LiquidationReward = LiquidationReward;
+	type MaxOffchainBatchOrders = MaxOffchainBatchOrders;
+	type OffchainBatchPriority = OffchainBatchPriority;
+	type OrderSignature = Signature;
+	type OrderSigner = <Signature as Verify>::Signer;
+	type MaxSignedOrdersPerBatch = MaxSignedOrdersPerBatch;
+	type RevealWindow = RevealWindow;
+	type CommitmentDeposit = CommitmentDeposit;
+	type MaxRevealsPerBlock = MaxRevealsPerBlock;
+	type Randomness = RandomnessCollectiveFlip;
+	type ReferralFeeShare = ReferralFeeShare;
+	type OrderHistoryRetention = OrderHistoryRetention;
+	type MaxSettlementsPerBlock = MaxSettlementsPerBlock;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -447,7 +539,7 @@ construct_runtime!(
 		Session: pallet_session::{Module, Call, Storage, Event, Config<T>},
 		Historical: pallet_session_historical::{Module},
 		// Include the custom logic from the template pallet in the runtime.
-		TemplateModule: template::{Module, Call, Storage, Event<T>},
+		TemplateModule: template::{Module, Call, Storage, Event<T>, Config<T>, ValidateUnsigned},
 	}
 );
 
@@ -671,6 +763,62 @@ impl_runtime_apis! {
 		    TemplateModule::get_market_info(trading_pair,blocknum)
 		}
 
+		fn get_market_info_rollup(trading_pair: Hash, bucket: u32) -> Option<MarketDataRpc> {
+		    TemplateModule::get_market_info_rollup(trading_pair, bucket)
+		}
+
+		fn get_ticker(trading_pair: Hash) -> TickerRpc {
+		    TemplateModule::get_ticker(trading_pair)
+		}
+
+		fn get_all_tickers() -> Vec<TickerRpc> {
+		    TemplateModule::get_all_tickers()
+		}
+
+		fn get_twap(trading_pair: Hash, window: u32) -> Option<u128> {
+		    TemplateModule::get_twap(trading_pair, window)
+		}
+
+		fn get_vwap(trading_pair: Hash, window: u32) -> Option<u128> {
+		    TemplateModule::get_vwap(trading_pair, window)
+		}
+
+		fn check_orderbook_integrity(trading_pair: Hash) -> Vec<IntegrityIssue> {
+		    TemplateModule::check_orderbook_integrity(trading_pair)
+		}
+
+		fn get_trading_pair(base_asset_id: u32, quote_asset_id: u32) -> Option<Hash> {
+		    TemplateModule::get_trading_pair(base_asset_id, quote_asset_id)
+		}
+
+		fn get_trading_pair_by_symbol(base_symbol: Vec<u8>, quote_symbol: Vec<u8>) -> Option<Hash> {
+		    TemplateModule::get_trading_pair_by_symbol(base_symbol, quote_symbol)
+		}
+
+		fn get_best_swap_path(asset_in: u32, asset_out: u32, amount_in: FixedU128) -> Option<SwapPathRpc> {
+		    TemplateModule::get_best_swap_path(asset_in, asset_out, amount_in)
+		}
+
+		fn get_liquidity_pool(trading_pair: Hash) -> Option<LiquidityPoolRpc> {
+		    TemplateModule::get_liquidity_pool(trading_pair)
+		}
+
+		fn get_referral_rewards(referrer: [u8; 32]) -> Vec<ReferralRewardRpc> {
+		    TemplateModule::get_referral_rewards(referrer)
+		}
+
+		fn get_order(order_id: Hash) -> Option<OrderRecordRpc> {
+		    TemplateModule::get_order(order_id)
+		}
+
+		fn get_depth(trading_pair: Hash, levels: u32) -> DepthRpc {
+		    TemplateModule::get_depth(trading_pair, levels)
+		}
+
+		fn simulate_order(trader: [u8; 32], order_type: OrderType, trading_pair: Hash, price: FixedU128, quantity: FixedU128) -> SimulatedOrderRpc {
+		    TemplateModule::simulate_order(trader, order_type, trading_pair, price, quantity)
+		}
+
 	 }
 
 }